@@ -0,0 +1,451 @@
+use crate::gameboy::CLOCK_SPEED;
+use crate::savestate::{StateReader, StateWriter};
+
+pub const SAMPLE_RATE: u32 = 48000;
+
+// T-cycles between frame sequencer steps (512 Hz).
+const FRAME_SEQUENCER_PERIOD: u32 = 8192;
+
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0],
+];
+
+const NOISE_DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+// Bits that always read back as 1 for NR10 through NR52.
+const READ_MASK: [u8; 0x17] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF,
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF,
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF,
+    0xFF, 0xFF, 0x00, 0x00, 0xBF,
+    0x00, 0x00, 0x70,
+];
+
+#[derive(Default)]
+struct Envelope {
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    fn trigger(&mut self, nrx2: u8) {
+        self.volume = nrx2 >> 4;
+        self.timer = nrx2 & 0x07;
+    }
+
+    fn clock(&mut self, nrx2: u8) {
+        let period = nrx2 & 0x07;
+        if period == 0 {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = period;
+            if nrx2 & 0x08 != 0 && self.volume < 15 {
+                self.volume += 1;
+            } else if nrx2 & 0x08 == 0 && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Channel {
+    enabled: bool,
+    length: u16,
+    timer: u32,
+    position: u8,
+    envelope: Envelope,
+}
+
+impl Channel {
+    fn clock_length(&mut self, length_enabled: bool) {
+        if length_enabled && self.length > 0 {
+            self.length -= 1;
+            if self.length == 0 {
+                self.enabled = false;
+            }
+        }
+    }
+}
+
+/// The audio processing unit: two square channels, a wave channel and a
+/// noise channel mixed to stereo.
+pub struct Apu {
+    regs: [u8; 0x30],
+    channels: [Channel; 4],
+    sweep_timer: u8,
+    sweep_shadow: u16,
+    sweep_enabled: bool,
+    lfsr: u16,
+    sequencer_timer: u32,
+    sequencer_step: u8,
+    sample_timer: u32,
+    capture: bool,
+    samples: Vec<i16>,
+}
+
+impl Default for Apu {
+    fn default() -> Apu {
+        Apu::new()
+    }
+}
+
+impl Apu {
+    pub fn new() -> Apu {
+        let mut apu = Apu {
+            regs: [0; 0x30],
+            channels: Default::default(),
+            sweep_timer: 0,
+            sweep_shadow: 0,
+            sweep_enabled: false,
+            lfsr: 0x7FFF,
+            sequencer_timer: 0,
+            sequencer_step: 0,
+            sample_timer: 0,
+            capture: false,
+            samples: vec![],
+        };
+        // Register values as left behind by the DMG boot ROM.
+        apu.regs[0x16] = 0x80;
+        for (i, &data) in [0x80, 0xBF, 0xF3, 0xFF, 0xBF, 0xFF, 0x3F, 0x00, 0xFF, 0xBF,
+                           0x7F, 0xFF, 0x9F, 0xFF, 0xBF, 0xFF, 0xFF, 0x00, 0x00, 0xBF,
+                           0x77, 0xF3].iter().enumerate() {
+            apu.write(0xFF10 + i as u16, data);
+        }
+        apu
+    }
+
+    /// When enabled, mixed stereo samples at SAMPLE_RATE are accumulated
+    /// until collected with take_samples.
+    pub fn set_capture(&mut self, capture: bool) {
+        self.capture = capture;
+        if !capture {
+            self.samples.clear();
+        }
+    }
+
+    /// Returns the interleaved left/right samples produced since the last
+    /// call.
+    pub fn take_samples(&mut self) -> Vec<i16> {
+        std::mem::take(&mut self.samples)
+    }
+
+    fn reg(&self, addr: u16) -> u8 {
+        self.regs[(addr - 0xFF10) as usize]
+    }
+
+    fn powered(&self) -> bool {
+        self.reg(0xFF26) & 0x80 != 0
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF26 => {
+                let status = self.channels.iter().enumerate()
+                    .fold(0, |s, (i, ch)| if ch.enabled { s | 1 << i } else { s });
+                (self.reg(addr) & 0x80) | READ_MASK[0x16] | status
+            }
+            0xFF10..=0xFF25 => self.reg(addr) | READ_MASK[(addr - 0xFF10) as usize],
+            0xFF30..=0xFF3F => self.reg(addr),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8) {
+        if addr == 0xFF26 {
+            if data & 0x80 == 0 {
+                self.regs[..0x16].fill(0);
+                for ch in self.channels.iter_mut() {
+                    ch.enabled = false;
+                }
+            } else if !self.powered() {
+                self.sequencer_step = 0;
+            }
+            self.regs[0x16] = data & 0x80;
+            return;
+        }
+        if (0xFF30..=0xFF3F).contains(&addr) {
+            self.regs[(addr - 0xFF10) as usize] = data;
+            return;
+        }
+        if !self.powered() || !(0xFF10..=0xFF25).contains(&addr) {
+            return;
+        }
+        self.regs[(addr - 0xFF10) as usize] = data;
+        match addr {
+            0xFF11 => self.channels[0].length = 64 - (data & 0x3F) as u16,
+            0xFF16 => self.channels[1].length = 64 - (data & 0x3F) as u16,
+            0xFF1B => self.channels[2].length = 256 - data as u16,
+            0xFF20 => self.channels[3].length = 64 - (data & 0x3F) as u16,
+            0xFF12 | 0xFF17 | 0xFF21 if data & 0xF8 == 0 => {
+                self.channels[((addr - 0xFF12) / 5) as usize].enabled = false;
+            }
+            0xFF1A if data & 0x80 == 0 => self.channels[2].enabled = false,
+            0xFF14 | 0xFF19 | 0xFF1E | 0xFF23 if data & 0x80 != 0 => {
+                self.trigger(((addr - 0xFF14) / 5) as usize);
+            }
+            _ => (),
+        }
+    }
+
+    fn frequency(&self, channel: usize) -> u16 {
+        let base = 0xFF13 + channel as u16 * 5;
+        ((self.reg(base + 1) & 0x07) as u16) << 8 | self.reg(base) as u16
+    }
+
+    fn set_frequency(&mut self, channel: usize, frequency: u16) {
+        let base = 0x03 + channel * 5;
+        self.regs[base] = frequency as u8;
+        self.regs[base + 1] = (self.regs[base + 1] & 0xF8) | ((frequency >> 8) as u8 & 0x07);
+    }
+
+    fn trigger(&mut self, channel: usize) {
+        let nrx2 = self.reg(0xFF12 + channel as u16 * 5);
+        let dac = if channel == 2 { self.reg(0xFF1A) & 0x80 != 0 } else { nrx2 & 0xF8 != 0 };
+        let period = self.period(channel);
+        let ch = &mut self.channels[channel];
+        ch.enabled = dac;
+        if ch.length == 0 {
+            ch.length = if channel == 2 { 256 } else { 64 };
+        }
+        ch.timer = period;
+        ch.position = 0;
+        if channel != 2 {
+            ch.envelope.trigger(nrx2);
+        }
+        if channel == 3 {
+            self.lfsr = 0x7FFF;
+        }
+        if channel == 0 {
+            let nr10 = self.reg(0xFF10);
+            self.sweep_shadow = self.frequency(0);
+            self.sweep_timer = if nr10 & 0x70 == 0 { 8 } else { (nr10 >> 4) & 0x07 };
+            self.sweep_enabled = nr10 & 0x77 != 0;
+            if nr10 & 0x07 != 0 {
+                self.sweep_frequency();
+            }
+        }
+    }
+
+    // T-cycles between steps of a channel's waveform.
+    fn period(&self, channel: usize) -> u32 {
+        match channel {
+            0 | 1 => (2048 - self.frequency(channel) as u32) * 4,
+            2 => (2048 - self.frequency(channel) as u32) * 2,
+            _ => {
+                let nr43 = self.reg(0xFF22);
+                NOISE_DIVISORS[(nr43 & 0x07) as usize] << (nr43 >> 4)
+            }
+        }
+    }
+
+    // Computes the next sweep frequency, disabling channel 1 on overflow.
+    fn sweep_frequency(&mut self) -> u16 {
+        let nr10 = self.reg(0xFF10);
+        let delta = self.sweep_shadow >> (nr10 & 0x07);
+        let frequency = if nr10 & 0x08 != 0 {
+            self.sweep_shadow.wrapping_sub(delta)
+        } else {
+            self.sweep_shadow + delta
+        };
+        if frequency > 2047 {
+            self.channels[0].enabled = false;
+        }
+        frequency
+    }
+
+    fn clock_sweep(&mut self) {
+        if self.sweep_timer > 0 {
+            self.sweep_timer -= 1;
+        }
+        if self.sweep_timer != 0 {
+            return;
+        }
+        let nr10 = self.reg(0xFF10);
+        let period = (nr10 >> 4) & 0x07;
+        self.sweep_timer = if period == 0 { 8 } else { period };
+        if self.sweep_enabled && period != 0 {
+            let frequency = self.sweep_frequency();
+            if frequency <= 2047 && nr10 & 0x07 != 0 {
+                self.sweep_shadow = frequency;
+                self.set_frequency(0, frequency);
+                self.sweep_frequency();
+            }
+        }
+    }
+
+    fn clock_sequencer(&mut self) {
+        let step = self.sequencer_step;
+        if step.is_multiple_of(2) {
+            for i in 0..4 {
+                let length_enabled = self.reg(0xFF14 + i as u16 * 5) & 0x40 != 0;
+                self.channels[i].clock_length(length_enabled);
+            }
+        }
+        if step == 2 || step == 6 {
+            self.clock_sweep();
+        }
+        if step == 7 {
+            for i in [0, 1, 3] {
+                let nrx2 = self.reg(0xFF12 + i as u16 * 5);
+                self.channels[i].envelope.clock(nrx2);
+            }
+        }
+        self.sequencer_step = (step + 1) % 8;
+    }
+
+    /// Advances the APU by the given number of T-cycles.
+    pub fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            if self.powered() {
+                self.sequencer_timer += 1;
+                if self.sequencer_timer == FRAME_SEQUENCER_PERIOD {
+                    self.sequencer_timer = 0;
+                    self.clock_sequencer();
+                }
+                for channel in 0..4 {
+                    self.clock_channel(channel);
+                }
+            }
+            if self.capture {
+                self.sample_timer += SAMPLE_RATE;
+                if self.sample_timer >= CLOCK_SPEED {
+                    self.sample_timer -= CLOCK_SPEED;
+                    self.mix();
+                }
+            }
+        }
+    }
+
+    fn clock_channel(&mut self, channel: usize) {
+        if self.channels[channel].timer > 1 {
+            self.channels[channel].timer -= 1;
+            return;
+        }
+        self.channels[channel].timer = self.period(channel);
+        match channel {
+            0 | 1 => {
+                let ch = &mut self.channels[channel];
+                ch.position = (ch.position + 1) % 8;
+            }
+            2 => {
+                let ch = &mut self.channels[channel];
+                ch.position = (ch.position + 1) % 32;
+            }
+            _ => {
+                let bit = (self.lfsr ^ (self.lfsr >> 1)) & 0x01;
+                self.lfsr = (self.lfsr >> 1) | (bit << 14);
+                if self.reg(0xFF22) & 0x08 != 0 {
+                    self.lfsr = (self.lfsr & !0x40) | (bit << 6);
+                }
+            }
+        }
+    }
+
+    // Digital output of a channel, 0-15.
+    fn output(&self, channel: usize) -> u8 {
+        let ch = &self.channels[channel];
+        if !ch.enabled {
+            return 0;
+        }
+        match channel {
+            0 | 1 => {
+                let duty = (self.reg(0xFF11 + channel as u16 * 5) >> 6) as usize;
+                DUTY_TABLE[duty][ch.position as usize] * ch.envelope.volume
+            }
+            2 => {
+                let byte = self.reg(0xFF30 + (ch.position / 2) as u16);
+                let sample = if ch.position.is_multiple_of(2) { byte >> 4 } else { byte & 0x0F };
+                match (self.reg(0xFF1C) >> 5) & 0x03 {
+                    0 => 0,
+                    shift => sample >> (shift - 1),
+                }
+            }
+            _ => ((!self.lfsr & 0x01) as u8) * ch.envelope.volume,
+        }
+    }
+
+    fn mix(&mut self) {
+        let nr50 = self.reg(0xFF24);
+        let nr51 = self.reg(0xFF25);
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for channel in 0..4 {
+            // Each DAC maps 0-15 onto -1.0..1.0.
+            let analog = self.output(channel) as f32 / 7.5 - 1.0;
+            let analog = if self.channels[channel].enabled { analog } else { 0.0 };
+            if nr51 & (0x10 << channel) != 0 {
+                left += analog;
+            }
+            if nr51 & (0x01 << channel) != 0 {
+                right += analog;
+            }
+        }
+        let left_volume = (((nr50 >> 4) & 0x07) + 1) as f32 / 8.0;
+        let right_volume = ((nr50 & 0x07) + 1) as f32 / 8.0;
+        let scale = i16::MAX as f32 / 4.0;
+        self.samples.push((left * left_volume * scale) as i16);
+        self.samples.push((right * right_volume * scale) as i16);
+    }
+
+    // Capture settings and buffered samples belong to the host, not the
+    // machine, so they are not part of the state.
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.regs);
+        for channel in &self.channels {
+            state.bool(channel.enabled);
+            state.u16(channel.length);
+            state.u32(channel.timer);
+            state.u8(channel.position);
+            state.u8(channel.envelope.volume);
+            state.u8(channel.envelope.timer);
+        }
+        state.u8(self.sweep_timer);
+        state.u16(self.sweep_shadow);
+        state.bool(self.sweep_enabled);
+        state.u16(self.lfsr);
+        state.u32(self.sequencer_timer);
+        state.u8(self.sequencer_step);
+        state.u32(self.sample_timer);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.fill(&mut self.regs)?;
+        for (i, channel) in self.channels.iter_mut().enumerate() {
+            channel.enabled = state.bool()?;
+            channel.length = state.u16()?;
+            channel.timer = state.u32()?;
+            channel.position = state.u8()?;
+            channel.envelope.volume = state.u8()?;
+            channel.envelope.timer = state.u8()?;
+            // The wave channel steps through 32 samples, the others through
+            // the 8 steps of a duty cycle.
+            let steps = if i == 2 { 32 } else { 8 };
+            if channel.position >= steps || channel.envelope.volume > 15 {
+                return Err(format!("invalid APU channel {} position {} volume {}",
+                    i + 1, channel.position, channel.envelope.volume));
+            }
+        }
+        self.sweep_timer = state.u8()?;
+        self.sweep_shadow = state.u16()?;
+        self.sweep_enabled = state.bool()?;
+        self.lfsr = state.u16()?;
+        self.sequencer_timer = state.u32()?;
+        self.sequencer_step = state.u8()?;
+        self.sample_timer = state.u32()?;
+        if self.sequencer_step >= 8 || self.sequencer_timer >= FRAME_SEQUENCER_PERIOD {
+            return Err(format!("invalid APU frame sequencer step {} timer {}", self.sequencer_step, self.sequencer_timer));
+        }
+        if self.sweep_shadow > 2047 || self.sample_timer >= CLOCK_SPEED {
+            return Err("invalid APU sweep or sample timer".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+// Readers for the archive formats ROMs and movies are distributed in. Only
+// what those need is supported: zip archives with stored or deflated
+// entries, without zip64 or encryption, and single-member gzip files.
+
+use std::borrow::Cow;
+
+use crate::cartridge::MAX_ROM_SIZE;
+use crate::deflate::{crc32, decompress};
+
+/// The extensions of the ROM files looked for inside zip archives.
+pub const ROM_EXTENSIONS: [&str; 2] = ["gb", "gbc"];
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const GZIP_DEFLATE: u8 = 8;
+const GZIP_FHCRC: u8 = 0x02;
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const GZIP_FCOMMENT: u8 = 0x10;
+
+const ZIP_LOCAL_HEADER: u32 = 0x04034B50;
+const ZIP_CENTRAL_HEADER: u32 = 0x02014B50;
+const ZIP_END_OF_DIRECTORY: u32 = 0x06054B50;
+
+const ZIP_STORED: u16 = 0;
+const ZIP_DEFLATED: u16 = 8;
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16, String> {
+    let bytes = data.get(pos..pos + 2).ok_or("zip archive is truncated")?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<u32, String> {
+    let bytes = data.get(pos..pos + 4).ok_or("zip archive is truncated")?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// A file in a zip archive, as listed in its central directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntry {
+    pub name: String,
+    pub size: u32,
+    method: u16,
+    flags: u16,
+    crc: u32,
+    compressed_size: u32,
+    offset: u32,
+}
+
+impl ZipEntry {
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+/// A zip archive held in memory.
+pub struct ZipArchive<'a> {
+    data: &'a [u8],
+    entries: Vec<ZipEntry>,
+}
+
+impl<'a> ZipArchive<'a> {
+    /// Reads the central directory of a zip archive.
+    pub fn parse(data: &'a [u8]) -> Result<ZipArchive<'a>, String> {
+        // The end of central directory record is the last thing in the file,
+        // followed only by a comment of up to 64 KiB.
+        let end = (0..data.len().saturating_sub(21)).rev().take(0x10000 + 22)
+            .find(|&pos| u32_at(data, pos) == Ok(ZIP_END_OF_DIRECTORY))
+            .ok_or("not a zip archive")?;
+        let count = u16_at(data, end + 10)?;
+        let mut pos = u32_at(data, end + 16)? as usize;
+        let mut entries = vec![];
+        for _ in 0..count {
+            if u32_at(data, pos)? != ZIP_CENTRAL_HEADER {
+                return Err("zip central directory is corrupt".to_string());
+            }
+            let name_len = u16_at(data, pos + 28)? as usize;
+            let extra_len = u16_at(data, pos + 30)? as usize;
+            let comment_len = u16_at(data, pos + 32)? as usize;
+            let name = data.get(pos + 46..pos + 46 + name_len).ok_or("zip archive is truncated")?;
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                size: u32_at(data, pos + 24)?,
+                method: u16_at(data, pos + 10)?,
+                flags: u16_at(data, pos + 8)?,
+                crc: u32_at(data, pos + 16)?,
+                compressed_size: u32_at(data, pos + 20)?,
+                offset: u32_at(data, pos + 42)?,
+            });
+            pos += 46 + name_len + extra_len + comment_len;
+        }
+        Ok(ZipArchive { data, entries })
+    }
+
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// Finds an entry by its full path within the archive.
+    pub fn find(&self, name: &str) -> Option<&ZipEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Extracts an entry and verifies its checksum.
+    pub fn read(&self, entry: &ZipEntry) -> Result<Vec<u8>, String> {
+        if entry.flags & 0x0001 != 0 {
+            return Err(format!("{} is encrypted", entry.name));
+        }
+        let pos = entry.offset as usize;
+        if u32_at(self.data, pos)? != ZIP_LOCAL_HEADER {
+            return Err(format!("{} has a corrupt local header", entry.name));
+        }
+        // The local header's name and extra field may differ in length from
+        // the central directory's.
+        let start = pos + 30 + u16_at(self.data, pos + 26)? as usize + u16_at(self.data, pos + 28)? as usize;
+        let compressed = self.data.get(start..start + entry.compressed_size as usize)
+            .ok_or("zip archive is truncated")?;
+        let data = match entry.method {
+            ZIP_STORED => compressed.to_vec(),
+            ZIP_DEFLATED => decompress(compressed, entry.size as usize).map_err(|err| format!("{}: {}", entry.name, err))?,
+            method => return Err(format!("{} uses unsupported compression method {}", entry.name, method)),
+        };
+        if data.len() != entry.size as usize || crc32(&data) != entry.crc {
+            return Err(format!("{} is corrupt: checksum mismatch", entry.name));
+        }
+        Ok(data)
+    }
+}
+
+pub fn is_zip(data: &[u8]) -> bool {
+    u32_at(data, 0) == Ok(ZIP_LOCAL_HEADER) || u32_at(data, 0) == Ok(ZIP_END_OF_DIRECTORY)
+}
+
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&GZIP_MAGIC)
+}
+
+/// Decompresses a gzip (RFC 1952) file and verifies its checksum and
+/// length. Decompression stops as soon as the output is longer than the
+/// length the file records.
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_gzip(data) || data.len() < 18 || data[2] != GZIP_DEFLATE {
+        return Err("not a gzip file".to_string());
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & GZIP_FEXTRA != 0 {
+        pos += 2 + u16_at(data, pos).map_err(|_| "gzip file is truncated")? as usize;
+    }
+    // The original file name and a comment, both zero-terminated.
+    for flag in [GZIP_FNAME, GZIP_FCOMMENT] {
+        if flags & flag != 0 {
+            let len = data.get(pos..).and_then(|rest| rest.iter().position(|&byte| byte == 0))
+                .ok_or("gzip file is truncated")?;
+            pos += len + 1;
+        }
+    }
+    if flags & GZIP_FHCRC != 0 {
+        pos += 2;
+    }
+    let trailer = data.len() - 8;
+    let compressed = data.get(pos..trailer).ok_or("gzip file is truncated")?;
+    let crc = u32::from_le_bytes(data[trailer..trailer + 4].try_into().unwrap());
+    let len = u32::from_le_bytes(data[trailer + 4..].try_into().unwrap());
+    let out = decompress(compressed, len as usize)?;
+    if crc32(&out) != crc || out.len() as u32 != len {
+        return Err("gzip file is corrupt: checksum mismatch".to_string());
+    }
+    Ok(out)
+}
+
+/// Returns the ROM in `data`: the data itself, the decompressed contents of
+/// a gzip file, or an entry of a zip archive. The zip entry is the one
+/// named `entry`, or else the first with a ROM extension. Archives are
+/// recognised by their magic bytes, which no ROM in practice starts with,
+/// since they are not the jump or interrupt code found at 0x0000.
+pub fn unpack_rom<'a>(data: &'a [u8], entry: Option<&str>) -> Result<Cow<'a, [u8]>, String> {
+    if is_gzip(data) {
+        // The last four bytes of a gzip file are the uncompressed length.
+        check_rom_size(u32_at(data, data.len().saturating_sub(4)).unwrap_or(0))?;
+        return gunzip(data).map(Cow::Owned);
+    }
+    if !is_zip(data) {
+        return Ok(Cow::Borrowed(data));
+    }
+    let archive = ZipArchive::parse(data)?;
+    let found = match entry {
+        Some(name) => archive.find(name).ok_or(format!("archive has no entry '{}'", name))?,
+        None => archive.entries().iter().find(|entry| {
+            let extension = entry.name.rsplit_once('.').map_or("", |(_, extension)| extension);
+            !entry.is_dir() && ROM_EXTENSIONS.iter().any(|rom| extension.eq_ignore_ascii_case(rom))
+        }).ok_or("archive has no .gb or .gbc file")?,
+    };
+    check_rom_size(found.size)?;
+    archive.read(found).map(Cow::Owned)
+}
+
+fn check_rom_size(size: u32) -> Result<(), String> {
+    if size as usize > MAX_ROM_SIZE {
+        return Err(format!("archive holds {} bytes, more than the largest Game Boy ROM", size));
+    }
+    Ok(())
+}
@@ -1,41 +1,321 @@
+use std::cell::Cell;
+
+use crate::cheats::{Cheat, Effect};
+use crate::Apu;
 use crate::Cartridge;
+use crate::Joypad;
+use crate::Ppu;
+use crate::Serial;
+use crate::savestate::{StateReader, StateWriter};
 
+/// The CPU's view of the 16-bit address space.
 pub trait BusTrait {
     fn write(&mut self, addr: u16, data: u8);
     fn read(&self, addr: u16) -> u8;
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Stops execution when the CPU reads and/or writes an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addr: u16,
+    pub read: bool,
+    pub write: bool,
+}
+
+/// The access that triggered a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub addr: u16,
+    pub value: u8,
+    pub access: Access,
+}
+
+/// The DMG memory map: cartridge, PPU and APU registers and RAM.
 pub struct Bus {
     pub cartridge: Cartridge,
+    pub ppu: Ppu,
+    pub apu: Apu,
+    pub joypad: Joypad,
+    pub serial: Serial,
+    boot_rom: Option<Vec<u8>>,
     memory : [u8; 0x10000],
+    watchpoints: Vec<Watchpoint>,
+    watch_hit: Cell<Option<WatchHit>>,
+    write_log: Option<Vec<(u16, u8)>>,
+    cheats: Vec<Cheat>,
+    // The enabled Game Genie patches, checked on every ROM read.
+    rom_patches: Vec<(u16, u8, Option<u8>)>,
+}
+
+static IF_ADDR: usize = 0xFF0F;
+static DMA_ADDR: u16 = 0xFF46;
+static BOOT_ROM_DISABLE_ADDR: u16 = 0xFF50;
+
+impl Default for Bus {
+    fn default() -> Bus {
+        Bus::new()
+    }
 }
 
 impl Bus {
     pub fn new() -> Bus {
-        Bus { cartridge: Cartridge::new(), memory: [0; 0x10000] }
+        Bus { cartridge: Cartridge::new(), ppu: Ppu::new(), apu: Apu::new(), joypad: Joypad::new(),
+            serial: Serial::new(), boot_rom: None, memory: [0; 0x10000], watchpoints: vec![],
+            watch_hit: Cell::new(None), write_log: None, cheats: vec![], rom_patches: vec![] }
     }
-}
 
-impl BusTrait for Bus {
-    fn write(&mut self, addr: u16, data: u8) {
-        match addr {
-            0x0000..=0x7FFF => {
+    /// Maps a boot ROM over the start of the cartridge until the program
+    /// writes to 0xFF50.
+    pub fn map_boot_rom(&mut self, boot_rom: Vec<u8>) {
+        self.boot_rom = Some(boot_rom);
+    }
+
+    /// Updates the pressed buttons, raising the joypad interrupt on a new
+    /// press.
+    pub fn set_buttons(&mut self, pressed: u8) {
+        self.memory[IF_ADDR] |= self.joypad.set_buttons(pressed);
+    }
+
+    /// Advances every component clocked alongside the CPU by `cycles`
+    /// T-cycles.
+    pub fn tick(&mut self, cycles: u32) {
+        self.memory[IF_ADDR] |= self.ppu.tick(cycles);
+        self.memory[IF_ADDR] |= self.serial.tick(cycles);
+        self.apu.tick(cycles);
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
 
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        if index < self.watchpoints.len() { Some(self.watchpoints.remove(index)) } else { None }
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Returns the first CPU access that hit a watchpoint since the last
+    /// call.
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    /// Records every CPU write until disabled; see take_writes.
+    pub fn log_writes(&mut self, enabled: bool) {
+        self.write_log = if enabled { Some(vec![]) } else { None };
+    }
+
+    /// The address and value of every CPU write since the last call, in
+    /// order.
+    pub fn take_writes(&mut self) -> Vec<(u16, u8)> {
+        self.write_log.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub fn add_cheat(&mut self, cheat: Cheat) {
+        self.cheats.push(cheat);
+        self.update_rom_patches();
+    }
+
+    pub fn remove_cheat(&mut self, index: usize) -> Option<Cheat> {
+        let cheat = if index < self.cheats.len() { Some(self.cheats.remove(index)) } else { None };
+        self.update_rom_patches();
+        cheat
+    }
+
+    /// Switches a cheat on or off. Returns false if there is no such cheat.
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.cheats.get_mut(index) {
+            Some(cheat) => cheat.enabled = enabled,
+            None => return false,
+        }
+        self.update_rom_patches();
+        true
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    fn update_rom_patches(&mut self) {
+        self.rom_patches = self.cheats.iter().filter(|cheat| cheat.enabled)
+            .filter_map(|cheat| match cheat.effect {
+                Effect::RomPatch { addr, value, compare } => Some((addr, value, compare)),
+                Effect::RamWrite { .. } => None,
+            })
+            .collect();
+    }
+
+    /// Performs the writes of the enabled GameShark codes, as the real
+    /// device does once a frame.
+    pub fn apply_cheats(&mut self) {
+        let cheats = std::mem::take(&mut self.cheats);
+        for cheat in cheats.iter().filter(|cheat| cheat.enabled) {
+            match cheat.effect {
+                Effect::RamWrite { addr, value, bank: Some(bank) } => {
+                    self.cartridge.poke_ram(bank as usize, addr, value);
+                }
+                Effect::RamWrite { addr, value, bank: None } => self.poke(addr, value),
+                Effect::RomPatch { .. } => {}
+            }
+        }
+        self.cheats = cheats;
+    }
+
+    fn watch(&self, addr: u16, value: u8, access: Access) {
+        let hit = self.watchpoints.iter().any(|watchpoint| watchpoint.addr == addr && match access {
+            Access::Read => watchpoint.read,
+            Access::Write => watchpoint.write,
+        });
+        if hit && self.watch_hit.get().is_none() {
+            self.watch_hit.set(Some(WatchHit { addr, value, access }));
+        }
+    }
+
+    /// Saves the memory-mapped state: every component on the bus, work and
+    /// high RAM, and the I/O registers kept in bus memory such as the timer
+    /// and interrupt flags.
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.section(b"RAM ", |state| {
+            state.bytes(&self.memory);
+            state.blob(self.boot_rom.as_deref().unwrap_or(&[]));
+        });
+        state.section(b"CART", |state| self.cartridge.save_state(state));
+        state.section(b"PPU ", |state| self.ppu.save_state(state));
+        state.section(b"APU ", |state| self.apu.save_state(state));
+        state.section(b"JOYP", |state| self.joypad.save_state(state));
+        state.section(b"SERL", |state| self.serial.save_state(state));
+    }
+
+    /// Loads one section written by save_state. Returns false if the tag
+    /// does not belong to the bus.
+    pub fn load_section(&mut self, tag: &[u8; 4], state: &mut StateReader) -> Result<bool, String> {
+        match tag {
+            b"RAM " => {
+                state.fill(&mut self.memory)?;
+                let boot_rom = state.blob()?;
+                self.boot_rom = if boot_rom.is_empty() { None } else { Some(boot_rom.to_vec()) };
+            }
+            b"CART" => self.cartridge.load_state(state)?,
+            b"PPU " => self.ppu.load_state(state)?,
+            b"APU " => self.apu.load_state(state)?,
+            b"JOYP" => self.joypad.load_state(state)?,
+            b"SERL" => self.serial.load_state(state)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // OAM DMA copies 160 bytes from XX00 into OAM. It is performed at once
+    // rather than over 160 M-cycles.
+    fn dma(&mut self, source: u8) {
+        let base = (source as u16) << 8;
+        for i in 0..0xA0 {
+            let data = self.peek(base + i);
+            self.ppu.write(0xFE00 + i, data);
+        }
+    }
+
+    /// Writes a byte like the CPU would, without triggering watchpoints.
+    pub fn poke(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => {
+                self.cartridge.write(addr, data);
+            },
+            0x8000..=0x9FFF | 0xFE00..=0xFE9F |
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B => {
+                self.ppu.write(addr, data);
             },
-            0x8000..=0xFFFF => {
+            0xFF00 => {
+                self.joypad.write(data);
+            },
+            0xFF01..=0xFF02 => {
+                self.serial.write(addr, data);
+            },
+            0xFF10..=0xFF3F => {
+                self.apu.write(addr, data);
+            },
+            _ if addr == DMA_ADDR => {
+                self.memory[usize::from(addr)] = data;
+                self.dma(data);
+            },
+            _ if addr == BOOT_ROM_DISABLE_ADDR => {
+                if data != 0 {
+                    self.boot_rom = None;
+                }
+            },
+            0xE000..=0xFDFF => {
+                self.memory[usize::from(addr - 0x2000)] = data;
+            },
+            0xC000..=0xFFFF => {
                 self.memory[usize::from(addr)] = data;
             }
         }
     }
 
-    fn read(&self, addr: u16) -> u8 {
+    /// Reads a byte like the CPU would, without triggering watchpoints.
+    pub fn peek(&self, addr: u16) -> u8 {
         match addr {
-            0x0000..=0x7FFF => {
-                return self.cartridge.read(addr);
+            0x0000..=0x00FF if self.boot_rom.is_some() => {
+                let boot_rom = self.boot_rom.as_ref().unwrap();
+                boot_rom.get(usize::from(addr)).copied().unwrap_or(0xFF)
+            },
+            0x0000..=0x7FFF if !self.rom_patches.is_empty() => {
+                let value = self.cartridge.read(addr);
+                self.rom_patches.iter()
+                    .find(|&&(patch, _, compare)| patch == addr && compare.is_none_or(|compare| compare == value))
+                    .map_or(value, |&(_, patched, _)| patched)
+            },
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => {
+                self.cartridge.read(addr)
+            },
+            0x8000..=0x9FFF | 0xFE00..=0xFE9F |
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B => {
+                self.ppu.read(addr)
             },
-            0x8000..=0xFFFF => {
-                return self.memory[usize::from(addr)];
+            0xFF00 => {
+                self.joypad.read()
+            },
+            0xFF01..=0xFF02 => {
+                self.serial.read(addr)
+            },
+            0xFF10..=0xFF3F => {
+                self.apu.read(addr)
+            },
+            // Echo RAM mirrors work RAM.
+            0xE000..=0xFDFF => {
+                self.memory[usize::from(addr - 0x2000)]
+            },
+            0xC000..=0xFFFF => {
+                self.memory[usize::from(addr)]
             }
         }
     }
 }
+
+impl BusTrait for Bus {
+    fn write(&mut self, addr: u16, data: u8) {
+        if !self.watchpoints.is_empty() {
+            self.watch(addr, data, Access::Write);
+        }
+        if let Some(log) = self.write_log.as_mut() {
+            log.push((addr, data));
+        }
+        self.poke(addr, data);
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        let value = self.peek(addr);
+        if !self.watchpoints.is_empty() {
+            self.watch(addr, value, Access::Read);
+        }
+        value
+    }
+}
@@ -1,6 +1,22 @@
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::Path;
 
+use crate::archive::unpack_rom;
+use crate::deflate::crc32;
+use crate::patch::apply_patch;
+use crate::savestate::{StateReader, StateWriter};
+
+#[cfg(test)]
+mod test;
+
+/// The size of the largest Game Boy ROM, 8 MiB. Archives and patches that
+/// would produce anything bigger are rejected before it is allocated.
+pub const MAX_ROM_SIZE: usize = 8 * 1024 * 1024;
+
+/// The memory bank controller declared at 0x0147 of the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CartridgeType {
     RomOnly = 0x00,
     Mbc1 = 0x01,
@@ -16,7 +32,7 @@ pub enum CartridgeType {
 }
 
 impl CartridgeType {
-    fn from_u8(n: u8) -> Option<CartridgeType> {
+    pub fn from_u8(n: u8) -> Option<CartridgeType> {
         match n {
             0x00 => Some(CartridgeType::RomOnly),
             0x01 => Some(CartridgeType::Mbc1),
@@ -32,55 +48,321 @@ impl CartridgeType {
             _ => None,
         }
     }
+
+    pub fn has_ram(&self) -> bool {
+        matches!(self,
+            CartridgeType::Mbc1Ram | CartridgeType::Mbc1RamBattery |
+            CartridgeType::Mbc2 | CartridgeType::Mbc2Battery |
+            CartridgeType::RomRam | CartridgeType::RomRamBattery |
+            CartridgeType::Mmm01Ram | CartridgeType::Mmm01RamBattery)
+    }
+
+    pub fn has_battery(&self) -> bool {
+        matches!(self,
+            CartridgeType::Mbc1RamBattery | CartridgeType::Mbc2Battery |
+            CartridgeType::RomRamBattery | CartridgeType::Mmm01RamBattery)
+    }
+
+    fn is_mbc1(&self) -> bool {
+        matches!(self,
+            CartridgeType::Mbc1 | CartridgeType::Mbc1Ram | CartridgeType::Mbc1RamBattery)
+    }
+
+    fn is_mbc2(&self) -> bool {
+        matches!(self, CartridgeType::Mbc2 | CartridgeType::Mbc2Battery)
+    }
+
+    /// Whether the memory controller is emulated. MMM01 cartridges are
+    /// recognised, but their bank switching is not.
+    pub fn is_implemented(&self) -> bool {
+        matches!(self,
+            CartridgeType::RomOnly | CartridgeType::RomRam | CartridgeType::RomRamBattery)
+            || self.is_mbc1() || self.is_mbc2()
+    }
+}
+
+/// Human readable name of any cartridge type code, including the ones the
+/// emulator does not support.
+pub fn cartridge_type_name(code: u8) -> &'static str {
+    match code {
+        0x00 => "ROM ONLY",
+        0x01 => "MBC1",
+        0x02 => "MBC1+RAM",
+        0x03 => "MBC1+RAM+BATTERY",
+        0x05 => "MBC2",
+        0x06 => "MBC2+BATTERY",
+        0x08 => "ROM+RAM",
+        0x09 => "ROM+RAM+BATTERY",
+        0x0B => "MMM01",
+        0x0C => "MMM01+RAM",
+        0x0D => "MMM01+RAM+BATTERY",
+        0x0F => "MBC3+TIMER+BATTERY",
+        0x10 => "MBC3+TIMER+RAM+BATTERY",
+        0x11 => "MBC3",
+        0x12 => "MBC3+RAM",
+        0x13 => "MBC3+RAM+BATTERY",
+        0x19 => "MBC5",
+        0x1A => "MBC5+RAM",
+        0x1B => "MBC5+RAM+BATTERY",
+        0x1C => "MBC5+RUMBLE",
+        0x1D => "MBC5+RUMBLE+RAM",
+        0x1E => "MBC5+RUMBLE+RAM+BATTERY",
+        0x20 => "MBC6",
+        0x22 => "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+        0xFC => "POCKET CAMERA",
+        0xFD => "BANDAI TAMA5",
+        0xFE => "HuC3",
+        0xFF => "HuC1+RAM+BATTERY",
+        _ => "UNKNOWN",
+    }
+}
+
+/// The fields of the cartridge header at 0x0100-0x014F.
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub title: String,
+    pub cgb_flag: u8,
+    pub new_licensee: [u8; 2],
+    pub sgb_flag: u8,
+    pub cartridge_type: u8,
+    pub rom_size: u8,
+    pub ram_size: u8,
+    pub destination: u8,
+    pub old_licensee: u8,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
 }
+
+static TITLE_ADDR: usize = 0x0134;
+static HEADER_END: usize = 0x0150;
+
+impl Header {
+    /// Decodes the header of a ROM image, or returns None if the image is
+    /// too short to contain one.
+    pub fn parse(rom: &[u8]) -> Option<Header> {
+        if rom.len() < HEADER_END {
+            return None;
+        }
+        // The title is 16 bytes on early cartridges; CGB-aware ones reuse the
+        // last byte as the CGB flag.
+        let title_end = if rom[0x0143] & 0x80 != 0 { 0x0143 } else { 0x0144 };
+        let title = rom[TITLE_ADDR..title_end].iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| if c.is_ascii_graphic() || c == b' ' { c as char } else { '?' })
+            .collect();
+        Some(Header {
+            title,
+            cgb_flag: rom[0x0143],
+            new_licensee: [rom[0x0144], rom[0x0145]],
+            sgb_flag: rom[0x0146],
+            cartridge_type: rom[CTYPE_ADDR],
+            rom_size: rom[ROM_SIZE_ADDR],
+            ram_size: rom[RAM_SIZE_ADDR],
+            destination: rom[0x014A],
+            old_licensee: rom[0x014B],
+            version: rom[0x014C],
+            header_checksum: rom[0x014D],
+            global_checksum: (rom[0x014E] as u16) << 8 | rom[0x014F] as u16,
+        })
+    }
+
+    /// ROM size in bytes as declared by the header.
+    pub fn rom_bytes(&self) -> usize {
+        (32 * 1024) << self.rom_size
+    }
+
+    /// External RAM size in bytes as declared by the header.
+    pub fn ram_bytes(&self) -> usize {
+        match self.ram_size {
+            0x02 => 8 * 1024,
+            0x03 => 32 * 1024,
+            0x04 => 128 * 1024,
+            0x05 => 64 * 1024,
+            _ => 0,
+        }
+    }
+
+    pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+        rom[0x0134..=0x014C].iter().fold(0u8, |x, &b| x.wrapping_sub(b).wrapping_sub(1))
+    }
+
+    pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+        rom.iter().enumerate()
+            .filter(|&(i, _)| i != 0x014E && i != 0x014F)
+            .fold(0u16, |sum, (_, &b)| sum.wrapping_add(b as u16))
+    }
+}
+
+
+/// A cartridge's ROM image, external RAM and memory bank controller state.
 pub struct Cartridge {
     rom: Vec<u8>,
     rom_sz: usize,
+    rom_crc: u32,
     ctype: CartridgeType,
+    ram: Vec<u8>,
+    ram_dirty: bool,
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    banking_mode: u8,
+    patch: Option<Vec<u8>>,
+    archive_entry: Option<String>,
 }
 
 static CTYPE_ADDR: usize = 0x0147;
 static ROM_SIZE_ADDR: usize = 0x0148;
+static RAM_SIZE_ADDR: usize = 0x0149;
+static MBC2_RAM_SIZE: usize = 512;
+
+impl Default for Cartridge {
+    fn default() -> Cartridge {
+        Cartridge::new()
+    }
+}
 
 impl Cartridge {
     pub fn new() -> Cartridge {
         Cartridge {
             rom: vec![],
             rom_sz: (32 * 1024),
+            rom_crc: 0,
             ctype: CartridgeType::RomOnly,
+            ram: vec![],
+            ram_dirty: false,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            banking_mode: 0,
+            patch: None,
+            archive_entry: None,
+        }
+    }
+
+    /// Picks the file loaded from zip archives by its path in the archive,
+    /// instead of the first .gb or .gbc file.
+    pub fn set_archive_entry(&mut self, entry: Option<String>) {
+        self.archive_entry = entry;
+    }
+
+    // Replaces a zipped or gzipped ROM with its contents.
+    fn unpack(&mut self) -> bool {
+        match unpack_rom(&self.rom, self.archive_entry.as_deref()) {
+            Ok(Cow::Borrowed(_)) => true,
+            Ok(Cow::Owned(rom)) => {
+                self.rom = rom;
+                true
+            }
+            Err(err) => {
+                println!("Cartridge::unpack - Could not extract ROM. Error: {}", err);
+                false
+            }
+        }
+    }
+
+    /// Sets an IPS, UPS or BPS patch to apply to every ROM loaded from now
+    /// on, before its header is decoded.
+    pub fn set_patch(&mut self, patch: Option<Vec<u8>>) {
+        self.patch = patch;
+    }
+
+    fn apply_patch(&mut self) -> bool {
+        let patch = match &self.patch {
+            Some(patch) => patch,
+            None => return true,
+        };
+        match apply_patch(&self.rom, patch) {
+            Ok(rom) => {
+                self.rom = rom;
+                true
+            }
+            Err(err) => {
+                println!("Cartridge::apply_patch - Could not patch ROM. Error: {}", err);
+                false
+            }
         }
     }
     
     fn decode_cartridge_header(&mut self) -> bool {
+        let header = match Header::parse(&self.rom) {
+            Some(header) => header,
+            None => {
+                println!("Cartridge::decode_header - ROM is too small to contain a header.");
+                return false;
+            }
+        };
         // Get Cartridge Type.
-        let ctype_opt = CartridgeType::from_u8(self.rom[CTYPE_ADDR]);
+        let ctype_opt = CartridgeType::from_u8(header.cartridge_type);
         match ctype_opt {
             Some(ctype) => {
                 println!(
                     "Cartridge::decode_header - Cartridge Type: {}",
-                    self.rom[CTYPE_ADDR]
+                    header.cartridge_type
                 );
                 self.ctype = ctype
             }
             None => {
                 println!(
                     "Cartridge::decode_header - Unknown Cartridge Type: {}",
-                    self.rom[CTYPE_ADDR]
+                    header.cartridge_type
                 );
                 return false;
             }
         }
         // Get ROM size.
-        let rom_sz = self.rom[ROM_SIZE_ADDR];
-        if self.rom.len() != (32 * 1024) << rom_sz {
+        if self.rom.len() != header.rom_bytes() {
             println!("Cartridge::decode_header - Cartridge size from header does not match ROM size.");
             return false;
         }
-        self.rom_sz = (32 * 1024) << rom_sz;
+        self.rom_sz = header.rom_bytes();
         println!("Cartridge::decode_header - Cartridge Size: {}", self.rom_sz);
-        return true;
+        // Get RAM size. MBC2 has its RAM built in and reports none.
+        let ram_sz = if self.ctype.is_mbc2() {
+            MBC2_RAM_SIZE
+        } else if self.ctype.has_ram() {
+            header.ram_bytes()
+        } else {
+            0
+        };
+        self.ram = vec![0; ram_sz];
+        self.rom_crc = crc32(&self.rom);
+        self.ram_dirty = false;
+        self.ram_enabled = false;
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.banking_mode = 0;
+        true
     }
 
+    pub fn cartridge_type(&self) -> CartridgeType {
+        self.ctype
+    }
+
+    pub fn header(&self) -> Option<Header> {
+        Header::parse(&self.rom)
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// CRC-32 of the whole ROM image, identifying the game.
+    pub fn rom_crc(&self) -> u32 {
+        self.rom_crc
+    }
+
+    pub fn rom_size(&self) -> usize {
+        self.rom_sz
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    /// Loads a ROM image from disk, which may be zipped or gzipped, and
+    /// returns its size, or 0 if it could not be read or its header is
+    /// invalid.
     pub fn load_cartridge(&mut self, rom_path: &String) -> usize {
         let mut file = match File::open(rom_path) {
             Ok(file) => file,
@@ -92,11 +374,11 @@ impl Cartridge {
                 return 0;
             }
         };
-        Vec::resize(&mut self.rom, file.metadata().unwrap().len() as usize, 0);
-        match file.read(&mut self.rom) {
-            Ok(size) => {
-                if self.decode_cartridge_header() {
-                    return size;
+        self.rom.clear();
+        match file.read_to_end(&mut self.rom) {
+            Ok(_) => {
+                if self.unpack() && self.apply_patch() && self.decode_cartridge_header() {
+                    return self.rom.len();
                 }
             }
             Err(err) => {
@@ -104,23 +386,182 @@ impl Cartridge {
                 return 0;
             }
         };
-        return 0;
+        0
     }
 
-    pub fn load_cartridge_w_buffer(&mut self, buffer: &Vec<u8>) -> usize {
+    /// Loads a ROM image held in memory and returns its size, or 0 if its
+    /// header is invalid.
+    pub fn load_cartridge_w_buffer(&mut self, buffer: &[u8]) -> usize {
         self.rom.resize(buffer.len(), 0);
-        for i in 0..buffer.len() {
-            self.rom[i] = buffer[i];
+        self.rom.copy_from_slice(buffer);
+        if self.unpack() && self.apply_patch() && self.decode_cartridge_header() {
+            return self.rom_sz;
+        }
+        0
+    }
+
+    /// Restores battery-backed RAM from a save file. Returns false if the
+    /// cartridge has no battery or the file could not be read.
+    pub fn load_ram(&mut self, path: &Path) -> bool {
+        if !self.ctype.has_battery() {
+            return false;
         }
-        self.rom_sz = buffer.len();
-        if self.decode_cartridge_header() {
-            return 0;
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return false,
+        };
+        let mut data = vec![];
+        if let Err(err) = file.read_to_end(&mut data) {
+            println!("Cartridge::load_ram - Could not read save file {}. Error: {}", path.display(), err);
+            return false;
         }
-        return self.rom_sz;
+        let len = data.len().min(self.ram.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+        self.ram_dirty = false;
+        true
+    }
+
+    /// Writes battery-backed RAM to a save file if it changed since the last
+    /// load or save.
+    pub fn save_ram(&mut self, path: &Path) -> bool {
+        if !self.ctype.has_battery() || !self.ram_dirty {
+            return false;
+        }
+        let result = File::create(path).and_then(|mut file| file.write_all(&self.ram));
+        if let Err(err) = result {
+            println!("Cartridge::save_ram - Could not write save file {}. Error: {}", path.display(), err);
+            return false;
+        }
+        self.ram_dirty = false;
+        true
+    }
+
+    /// The ROM bank currently mapped at 0x4000-0x7FFF.
+    pub fn rom_bank(&self) -> usize {
+        let bank = if self.ctype.is_mbc1() {
+            (self.ram_bank as usize) << 5 | self.rom_bank as usize
+        } else {
+            self.rom_bank as usize
+        };
+        let banks = (self.rom.len() / 0x4000).max(1);
+        bank % banks
     }
 
-    // TODO: Implement memory mapper.
     pub fn read(&self, addr: u16) -> u8 {
-        return self.rom[usize::from(addr)];
+        match addr {
+            0x0000..=0x3FFF => {
+                let bank = if self.ctype.is_mbc1() && self.banking_mode == 1 {
+                    (self.ram_bank as usize) << 5
+                } else {
+                    0
+                };
+                self.read_rom(bank, addr)
+            }
+            0x4000..=0x7FFF => self.read_rom(self.rom_bank(), addr - 0x4000),
+            0xA000..=0xBFFF => {
+                match self.ram_offset(addr) {
+                    Some(offset) if self.ctype.is_mbc2() => 0xF0 | self.ram[offset],
+                    Some(offset) => self.ram[offset],
+                    None => 0xFF,
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x3FFF if self.ctype.is_mbc2() => {
+                // Bit 8 of the address selects between RAM enable and ROM bank.
+                if addr & 0x0100 == 0 {
+                    self.ram_enabled = data & 0x0F == 0x0A;
+                } else {
+                    self.rom_bank = if data & 0x0F == 0 { 1 } else { data & 0x0F };
+                }
+            }
+            0x0000..=0x1FFF => self.ram_enabled = data & 0x0F == 0x0A,
+            0x2000..=0x3FFF if self.ctype.is_mbc1() => {
+                self.rom_bank = if data & 0x1F == 0 { 1 } else { data & 0x1F };
+            }
+            0x4000..=0x5FFF if self.ctype.is_mbc1() => self.ram_bank = data & 0x03,
+            0x6000..=0x7FFF if self.ctype.is_mbc1() => self.banking_mode = data & 0x01,
+            0xA000..=0xBFFF => {
+                if let Some(offset) = self.ram_offset(addr) {
+                    let data = if self.ctype.is_mbc2() { data & 0x0F } else { data };
+                    if self.ram[offset] != data {
+                        self.ram[offset] = data;
+                        self.ram_dirty = true;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Writes cartridge RAM in `bank` directly, whichever bank is mapped and
+    /// whether or not RAM is enabled.
+    pub fn poke_ram(&mut self, bank: usize, addr: u16, data: u8) {
+        if self.ram.is_empty() || !(0xA000..=0xBFFF).contains(&addr) {
+            return;
+        }
+        let (offset, data) = if self.ctype.is_mbc2() {
+            ((addr - 0xA000) as usize % MBC2_RAM_SIZE, data & 0x0F)
+        } else {
+            ((bank * 0x2000 + (addr - 0xA000) as usize) % self.ram.len(), data)
+        };
+        if self.ram[offset] != data {
+            self.ram[offset] = data;
+            self.ram_dirty = true;
+        }
+    }
+
+    fn read_rom(&self, bank: usize, offset: u16) -> u8 {
+        if self.rom.is_empty() {
+            return 0xFF;
+        }
+        let index = (bank * 0x4000 + offset as usize) % self.rom.len();
+        self.rom[index]
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+        let offset = (addr - 0xA000) as usize;
+        if self.ctype.is_mbc2() {
+            // 512 half-bytes mirrored across the whole range.
+            return if self.ram_enabled { Some(offset % MBC2_RAM_SIZE) } else { None };
+        }
+        if self.ctype.is_mbc1() && !self.ram_enabled {
+            return None;
+        }
+        let bank = if self.ctype.is_mbc1() && self.banking_mode == 1 {
+            self.ram_bank as usize
+        } else {
+            0
+        };
+        Some((bank * 0x2000 + offset) % self.ram.len())
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.ram_enabled);
+        state.u8(self.rom_bank);
+        state.u8(self.ram_bank);
+        state.u8(self.banking_mode);
+        state.blob(&self.ram);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.ram_enabled = state.bool()?;
+        self.rom_bank = state.u8()?;
+        self.ram_bank = state.u8()?;
+        self.banking_mode = state.u8()?;
+        let ram = state.blob()?;
+        if ram.len() != self.ram.len() {
+            return Err(format!("save state has {} bytes of cartridge RAM, expected {}", ram.len(), self.ram.len()));
+        }
+        self.ram.copy_from_slice(ram);
+        self.ram_dirty = true;
+        Ok(())
     }
 }
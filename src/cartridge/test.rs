@@ -0,0 +1,107 @@
+use super::*;
+
+// A ROM of `banks` 16 KiB banks, each starting with its own bank number.
+fn rom(cartridge_type: u8, banks: usize, ram_size: u8) -> Vec<u8> {
+    let mut rom = vec![0u8; banks * 0x4000];
+    for bank in 0..banks {
+        rom[bank * 0x4000] = bank as u8;
+    }
+    rom[CTYPE_ADDR] = cartridge_type;
+    rom[ROM_SIZE_ADDR] = (banks / 2).trailing_zeros() as u8;
+    rom[RAM_SIZE_ADDR] = ram_size;
+    rom
+}
+
+fn cartridge(rom: &[u8]) -> Cartridge {
+    let mut cartridge = Cartridge::new();
+    assert_eq!(cartridge.load_cartridge_w_buffer(rom), rom.len());
+    cartridge
+}
+
+#[test]
+fn mbc1_rom_banking() {
+    let mut cartridge = cartridge(&rom(0x01, 8, 0));
+    assert_eq!(cartridge.read(0x0000), 0);
+    assert_eq!(cartridge.read(0x4000), 1);
+    cartridge.write(0x2000, 3);
+    assert_eq!(cartridge.read(0x4000), 3);
+    assert_eq!(cartridge.read(0x0000), 0);
+    // Bank 0 cannot be mapped at 0x4000; it selects bank 1 instead.
+    cartridge.write(0x3FFF, 0);
+    assert_eq!(cartridge.read(0x4000), 1);
+    // Only five bits are used, and banks past the end of the ROM wrap.
+    cartridge.write(0x2000, 0xE6);
+    assert_eq!(cartridge.read(0x4000), 6);
+    cartridge.write(0x2000, 0x0B);
+    assert_eq!(cartridge.read(0x4000), 3);
+}
+
+#[test]
+fn mbc1_upper_bank_bits() {
+    let mut cartridge = cartridge(&rom(0x01, 64, 0));
+    cartridge.write(0x2000, 0x02);
+    cartridge.write(0x4000, 0x01);
+    assert_eq!(cartridge.read(0x4000), 0x22);
+    // Bank 0x20 is out of reach: a zero low part still becomes 1.
+    cartridge.write(0x2000, 0x00);
+    assert_eq!(cartridge.read(0x4000), 0x21);
+    // Mode 0 keeps bank 0 at 0x0000; mode 1 applies the upper bits there too.
+    assert_eq!(cartridge.read(0x0000), 0x00);
+    cartridge.write(0x6000, 0x01);
+    assert_eq!(cartridge.read(0x0000), 0x20);
+    cartridge.write(0x6000, 0x00);
+    assert_eq!(cartridge.read(0x0000), 0x00);
+}
+
+#[test]
+fn mbc1_ram_enable_and_banking() {
+    let mut cartridge = cartridge(&rom(0x03, 4, 0x03));
+    assert_eq!(cartridge.ram().len(), 0x8000);
+    // Disabled RAM reads as open bus and ignores writes.
+    cartridge.write(0xA000, 0x12);
+    assert_eq!(cartridge.read(0xA000), 0xFF);
+    assert_eq!(cartridge.ram()[0], 0x00);
+
+    cartridge.write(0x0000, 0x0A);
+    cartridge.write(0xA000, 0x12);
+    assert_eq!(cartridge.read(0xA000), 0x12);
+    // RAM banks only switch in mode 1.
+    cartridge.write(0x4000, 0x02);
+    assert_eq!(cartridge.read(0xA000), 0x12);
+    cartridge.write(0x6000, 0x01);
+    assert_eq!(cartridge.read(0xA000), 0x00);
+    cartridge.write(0xBFFF, 0x34);
+    assert_eq!(cartridge.ram()[0x2 * 0x2000 + 0x1FFF], 0x34);
+
+    // Any value without 0xA in the low nibble disables RAM again.
+    cartridge.write(0x1FFF, 0x1B);
+    assert_eq!(cartridge.read(0xBFFF), 0xFF);
+    cartridge.write(0x1FFF, 0xFA);
+    assert_eq!(cartridge.read(0xBFFF), 0x34);
+}
+
+#[test]
+fn mbc2_banking_and_ram() {
+    let mut cartridge = cartridge(&rom(0x05, 16, 0));
+    assert_eq!(cartridge.ram().len(), MBC2_RAM_SIZE);
+    // Address bit 8 set selects the ROM bank, clear enables RAM.
+    cartridge.write(0x2100, 0x05);
+    assert_eq!(cartridge.read(0x4000), 5);
+    cartridge.write(0x0100, 0x07);
+    assert_eq!(cartridge.read(0x4000), 7);
+    cartridge.write(0x2100, 0xF0);
+    assert_eq!(cartridge.read(0x4000), 1);
+
+    assert_eq!(cartridge.read(0xA005), 0xFF);
+    // Unlike MBC1 this also works above 0x2000.
+    cartridge.write(0x2000, 0x0A);
+    assert_eq!(cartridge.read(0x4000), 1);
+    // Only the low nibble is stored, and the 512 bytes repeat through
+    // 0xA000-0xBFFF.
+    cartridge.write(0xA005, 0xAB);
+    assert_eq!(cartridge.read(0xA005), 0xFB);
+    assert_eq!(cartridge.read(0xA205), 0xFB);
+    assert_eq!(cartridge.read(0xBE05), 0xFB);
+    cartridge.write(0x0000, 0x00);
+    assert_eq!(cartridge.read(0xA005), 0xFF);
+}
@@ -0,0 +1,223 @@
+// Catalogues a directory of ROMs from their headers, to see at a glance
+// which games the emulator can run: whether each ROM's checksums hold and
+// whether its memory controller is one the emulator implements.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::archive::unpack_rom;
+use crate::cartridge::{cartridge_type_name, CartridgeType, Header};
+use crate::deflate::crc32;
+
+/// The file extensions `scan` looks at: ROMs and the archives they may be
+/// packed in.
+pub const SCAN_EXTENSIONS: [&str; 4] = ["gb", "gbc", "zip", "gz"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalogueFormat {
+    Json,
+    Csv,
+}
+
+impl CatalogueFormat {
+    pub fn from_name(name: &str) -> Option<CatalogueFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(CatalogueFormat::Json),
+            "csv" => Some(CatalogueFormat::Csv),
+            _ => None,
+        }
+    }
+
+    /// Picks the format from a file extension, defaulting to JSON.
+    pub fn from_path(path: &Path) -> CatalogueFormat {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(CatalogueFormat::from_name)
+            .unwrap_or(CatalogueFormat::Json)
+    }
+}
+
+/// What a ROM's header says about it and whether the emulator can run it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub path: PathBuf,
+    pub title: String,
+    pub cartridge_type: u8,
+    pub mbc: &'static str,
+    /// Whether the emulator implements the cartridge type.
+    pub supported: bool,
+    pub rom_size: usize,
+    pub file_size: usize,
+    pub ram_size: usize,
+    pub cgb_flag: u8,
+    pub sgb_flag: u8,
+    pub header_checksum_ok: bool,
+    pub global_checksum_ok: bool,
+    pub crc32: u32,
+}
+
+/// A scanned file: its details, or why it could not be read as a ROM.
+pub type ScanResult = (PathBuf, Result<RomInfo, String>);
+
+impl RomInfo {
+    /// Decodes the header of a ROM, or of the ROM inside a zip or gzip
+    /// archive, and checks it against the image.
+    pub fn from_rom(path: &Path, data: &[u8]) -> Result<RomInfo, String> {
+        let rom = unpack_rom(data, None)?;
+        let header = Header::parse(&rom).ok_or("too small to be a ROM")?;
+        Ok(RomInfo {
+            path: path.to_path_buf(),
+            title: header.title.clone(),
+            cartridge_type: header.cartridge_type,
+            mbc: cartridge_type_name(header.cartridge_type),
+            supported: CartridgeType::from_u8(header.cartridge_type).is_some_and(|kind| kind.is_implemented()),
+            rom_size: header.rom_bytes(),
+            file_size: rom.len(),
+            ram_size: header.ram_bytes(),
+            cgb_flag: header.cgb_flag,
+            sgb_flag: header.sgb_flag,
+            header_checksum_ok: Header::compute_header_checksum(&rom) == header.header_checksum,
+            global_checksum_ok: Header::compute_global_checksum(&rom) == header.global_checksum,
+            crc32: crc32(&rom),
+        })
+    }
+}
+
+/// Scans every file with a ROM or archive extension under `dir`, including
+/// subdirectories, in path order.
+pub fn scan(dir: &Path) -> Result<Vec<ScanResult>, String> {
+    let mut files = vec![];
+    find_roms(dir, &mut files)?;
+    files.sort();
+    Ok(files.into_iter().map(|path| {
+        let info = fs::read(&path).map_err(|err| err.to_string())
+            .and_then(|data| RomInfo::from_rom(&path, &data));
+        (path, info)
+    }).collect())
+}
+
+fn find_roms(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("could not read {}: {}", dir.display(), err))?;
+    for entry in entries {
+        let entry = entry.map_err(|err| format!("could not read {}: {}", dir.display(), err))?;
+        let file_type = entry.file_type().map_err(|err| format!("could not read {}: {}", dir.display(), err))?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            find_roms(&path, files)?;
+        } else if file_type.is_symlink() && path.is_dir() {
+            // Linked directories are not followed, so links back up the
+            // tree cannot loop.
+            continue;
+        } else if path.extension().and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SCAN_EXTENSIONS.iter().any(|rom| ext.eq_ignore_ascii_case(rom))) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+const COLUMNS: [&str; 14] = [
+    "path", "title", "cartridge_type", "mbc", "supported", "rom_size", "file_size", "ram_size",
+    "cgb_flag", "sgb_flag", "header_checksum_ok", "global_checksum_ok", "crc32", "error",
+];
+
+enum Field {
+    Text(String),
+    Number(String),
+    Missing,
+}
+
+// The fields of a scanned file in the order of COLUMNS. Files that could
+// not be read have only a path and an error.
+fn fields((path, info): &ScanResult) -> Vec<Field> {
+    let path = Field::Text(path.display().to_string());
+    match info {
+        Ok(info) => vec![
+            path,
+            Field::Text(info.title.clone()),
+            Field::Text(format!("0x{:02X}", info.cartridge_type)),
+            Field::Text(info.mbc.to_string()),
+            Field::Number(info.supported.to_string()),
+            Field::Number(info.rom_size.to_string()),
+            Field::Number(info.file_size.to_string()),
+            Field::Number(info.ram_size.to_string()),
+            Field::Text(format!("0x{:02X}", info.cgb_flag)),
+            Field::Text(format!("0x{:02X}", info.sgb_flag)),
+            Field::Number(info.header_checksum_ok.to_string()),
+            Field::Number(info.global_checksum_ok.to_string()),
+            Field::Text(format!("{:08X}", info.crc32)),
+            Field::Missing,
+        ],
+        Err(err) => {
+            let mut fields = vec![path];
+            fields.resize_with(COLUMNS.len() - 1, || Field::Missing);
+            fields.push(Field::Text(err.clone()));
+            fields
+        }
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Writes the scan as a JSON array with an object per file, using null for
+/// fields that do not apply.
+pub fn to_json(results: &[ScanResult]) -> String {
+    let mut out = String::from("[\n");
+    for (i, result) in results.iter().enumerate() {
+        let members: Vec<String> = COLUMNS.iter().zip(fields(result)).map(|(name, field)| {
+            let value = match field {
+                Field::Text(text) => json_string(&text),
+                Field::Number(number) => number,
+                Field::Missing => "null".to_string(),
+            };
+            format!("{}: {}", json_string(name), value)
+        }).collect();
+        let separator = if i + 1 < results.len() { "," } else { "" };
+        writeln!(out, "  {{{}}}{}", members.join(", "), separator).unwrap();
+    }
+    out.push_str("]\n");
+    out
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Writes the scan as CSV with a header row, leaving fields that do not
+/// apply empty.
+pub fn to_csv(results: &[ScanResult]) -> String {
+    let mut out = COLUMNS.join(",") + "\n";
+    for result in results {
+        let row: Vec<String> = fields(result).into_iter().map(|field| match field {
+            Field::Text(text) | Field::Number(text) => csv_field(&text),
+            Field::Missing => String::new(),
+        }).collect();
+        writeln!(out, "{}", row.join(",")).unwrap();
+    }
+    out
+}
+
+/// Writes the scan in the given format.
+pub fn write(results: &[ScanResult], format: CatalogueFormat) -> String {
+    match format {
+        CatalogueFormat::Json => to_json(results),
+        CatalogueFormat::Csv => to_csv(results),
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a cheat code does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// A Game Genie code: reads of `addr` in ROM return `value` instead,
+    /// but only while the ROM byte there equals `compare`, if given, so that
+    /// the patch only hits one bank.
+    RomPatch { addr: u16, value: u8, compare: Option<u8> },
+    /// A GameShark code: `value` is written to `addr` once a frame. For
+    /// cartridge RAM, `bank` picks the RAM bank written regardless of which
+    /// one is mapped.
+    RamWrite { addr: u16, value: u8, bank: Option<u8> },
+}
+
+/// A cheat code and whether it is switched on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub code: String,
+    pub name: String,
+    pub enabled: bool,
+    pub effect: Effect,
+}
+
+fn hex(text: &str) -> Option<u32> {
+    if text.is_empty() || !text.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(text, 16).ok()
+}
+
+impl Cheat {
+    /// Decodes a Game Genie code (`ABC-DEF` or `ABC-DEF-GHI`) or a GameShark
+    /// code (`BBVVLLHH`: bank type, value and little-endian address).
+    pub fn parse(code: &str) -> Result<Cheat, String> {
+        let invalid = || format!("invalid cheat code '{}'", code);
+        let code = code.trim().to_ascii_uppercase();
+        let groups: Vec<&str> = code.split('-').collect();
+        let effect = match groups[..] {
+            [abc, def] | [abc, def, _] if abc.len() == 3 && def.len() == 3 => {
+                let abc = hex(abc).ok_or_else(invalid)?;
+                let def = hex(def).ok_or_else(invalid)?;
+                // AB is the value; the address is CDE with F, inverted, on top.
+                let addr = ((def & 0x0F) ^ 0x0F) << 12 | (abc & 0x0F) << 8 | def >> 4;
+                let compare = match groups.get(2) {
+                    Some(ghi) if ghi.len() == 3 => {
+                        let ghi = hex(ghi).ok_or_else(invalid)?;
+                        // H is not used; GI is scrambled by a rotate and XOR.
+                        let gi = ((ghi >> 4) & 0xF0 | ghi & 0x0F) as u8;
+                        Some(gi.rotate_right(2) ^ 0xBA)
+                    }
+                    Some(_) => return Err(invalid()),
+                    None => None,
+                };
+                if addr >= 0x8000 {
+                    return Err(format!("Game Genie code '{}' does not patch ROM", code));
+                }
+                Effect::RomPatch { addr: addr as u16, value: (abc >> 4) as u8, compare }
+            }
+            [shark] if shark.len() == 8 => {
+                let shark = hex(shark).ok_or_else(invalid)?;
+                let kind = (shark >> 24) as u8;
+                let value = (shark >> 16) as u8;
+                let addr = (shark & 0xFF) << 8 | (shark >> 8) & 0xFF;
+                let addr = addr as u16;
+                let bank = match kind {
+                    // 01 writes whatever is mapped; 9X selects a CGB work RAM
+                    // bank, which the DMG does not have.
+                    0x00 | 0x01 | 0x90..=0x97 => None,
+                    0x80..=0x8F if (0xA000..0xC000).contains(&addr) => Some(kind & 0x0F),
+                    _ => return Err(format!("unsupported GameShark code type {:02X} in '{}'", kind, code)),
+                };
+                if addr < 0xA000 {
+                    return Err(format!("GameShark code '{}' does not write RAM", code));
+                }
+                Effect::RamWrite { addr, value, bank }
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(Cheat { code, name: String::new(), enabled: true, effect })
+    }
+}
+
+/// Parses a cheat file: one code per line, optionally followed by a name.
+/// Codes starting with `-` are loaded switched off, and lines starting with
+/// `#` are comments.
+pub fn parse_cheats(text: &str) -> Result<Vec<Cheat>, String> {
+    let mut cheats = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (code, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let (code, enabled) = match code.strip_prefix('-') {
+            Some(code) => (code, false),
+            None => (code, true),
+        };
+        let mut cheat = Cheat::parse(code).map_err(|err| format!("line {}: {}", number + 1, err))?;
+        cheat.name = name.trim().to_string();
+        cheat.enabled = enabled;
+        cheats.push(cheat);
+    }
+    Ok(cheats)
+}
+
+pub fn load_cheats(path: &Path) -> Result<Vec<Cheat>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+    parse_cheats(&text).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// The cheat file kept next to a ROM: the ROM path with a .cht extension.
+pub fn path_for_rom(rom: &Path) -> PathBuf {
+    rom.with_extension("cht")
+}
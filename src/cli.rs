@@ -0,0 +1,409 @@
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use gbemu::catalogue::CatalogueFormat;
+use gbemu::image::ImageFormat;
+use gbemu::symbols::parse_address;
+use gbemu::Model;
+
+#[cfg(test)]
+mod test;
+
+pub const USAGE: &str = "\
+Usage: gbemu [OPTIONS] <ROM>
+       gbemu <COMMAND> [OPTIONS] <ROM>
+
+The ROM may be zipped or gzipped.
+
+Commands:
+  disasm                   Disassemble a ROM to RGBDS source
+  scan                     Catalogue a directory of ROMs and whether they are supported
+  tracediff                Find where a ROM's trace departs from a reference log
+
+Options:
+  -m, --model <MODEL>      Hardware model: dmg, mgb, sgb, sgb2 [default: dmg]
+  -b, --boot-rom <PATH>    Run a 256-byte boot ROM before the cartridge
+  -p, --patch <PATH>       Apply an IPS, UPS or BPS patch to the ROM
+      --auto-patch         Apply the ROM path's .ips, .ups or .bps patch, if present
+      --zip-entry <NAME>   Load NAME from a zipped ROM [default: the first .gb or .gbc file]
+  -s, --save-dir <DIR>     Directory for battery-backed saves [default: the ROM's directory]
+  -f, --frames <N>         Run N frames headless as fast as possible, then exit
+      --load-state <SLOT>  Start from save state slot 0-9 (saved as <ROM>.ssN)
+      --save-state <SLOT>  Save the machine to slot 0-9 on exit
+      --record <PATH>      Record input to a movie from power-on, or from --load-state
+      --play <PATH>        Play back a movie (also .bk2, .vbm) to its end, or for --frames
+      --screenshot <PATH>  Write the last frame to a PNG or PPM image (by extension)
+      --dump-dir <DIR>     Write frames to DIR as frame_NNNNNN.<format>
+      --dump-every <N>     Only dump every Nth frame [default: 1]
+      --dump-last          Only dump the final frame (with --frames)
+      --dump-format <FMT>  Image format for dumped frames: png, ppm [default: png]
+      --audio <PATH>       Record audio output to a WAV file
+  -t, --trace              Print every executed instruction
+      --trace-log <PATH>   Log every instruction to PATH in the Gameboy Doctor format
+      --trace-pc <RANGE>   Only log instructions in a hex PC range, e.g. 0150-01FF
+      --trace-bank <BANK>  Only log instructions run from a ROM bank (hex)
+      --rewind <SECONDS>   Keep SECONDS of history for the debugger's rewind command
+      --rewind-mem <MIB>   Memory to spend on rewind history [default: 64]
+  -d, --debug              Start in the interactive debugger; Ctrl-C returns to it
+      --gdb <PORT>         Block until GDB attaches on localhost:PORT, then run under it
+      --symbols <PATH>     Load a .sym file [default: the ROM path with .sym, if present]
+      --cheats <PATH>      Load a cheat file [default: the ROM path with .cht, if present]
+      --cheat <CODE>       Enable a Game Genie or GameShark code; may be repeated
+  -i, --info               Print the cartridge header and exit
+  -h, --help               Print this help";
+
+pub const DISASM_USAGE: &str = "\
+Usage: gbemu disasm [OPTIONS] <ROM>
+
+Traces code from the entry point and interrupt vectors, following jumps and
+calls across bank switches, and writes RGBDS source that assembles back to
+the same ROM.
+
+Options:
+  -o, --output <PATH>      Write the disassembly to PATH [default: stdout]
+      --symbols <PATH>     Load a .sym file [default: the ROM path with .sym, if present]
+  -h, --help               Print this help";
+
+pub const TRACEDIFF_USAGE: &str = "\
+Usage: gbemu tracediff [OPTIONS] <ROM> <REFERENCE>
+
+Runs the ROM one instruction per line of a Gameboy Doctor trace and stops at
+the first line that differs, printing the lines leading up to it, the
+differing registers and the most recent memory writes.
+
+Options:
+  -m, --model <MODEL>      Hardware model: dmg, mgb, sgb, sgb2 [default: dmg]
+  -C, --context <N>        Lines of matching trace to print [default: 10]
+  -w, --writes <N>         Memory writes to print [default: 16]
+  -h, --help               Print this help";
+
+pub const SCAN_USAGE: &str = "\
+Usage: gbemu scan [OPTIONS] <DIR>
+
+Reads the header of every .gb, .gbc, .zip and .gz file under DIR, checks its
+checksums and cartridge type, and writes a catalogue listing which of them
+the emulator supports.
+
+Options:
+  -o, --output <PATH>      Write the catalogue to PATH [default: stdout]
+  -f, --format <FMT>       Catalogue format: json, csv [default: by extension, else json]
+  -h, --help               Print this help";
+
+pub enum Command {
+    Run(Box<Options>),
+    Disasm(DisasmOptions),
+    TraceDiff(TraceDiffOptions),
+    Scan(ScanOptions),
+    Help(&'static str),
+}
+
+pub struct Options {
+    pub rom: String,
+    pub model: Model,
+    pub boot_rom: Option<String>,
+    pub patch: Option<PathBuf>,
+    pub auto_patch: bool,
+    pub zip_entry: Option<String>,
+    pub save_dir: Option<PathBuf>,
+    pub frames: Option<u64>,
+    pub load_state: Option<u8>,
+    pub save_state: Option<u8>,
+    pub record: Option<PathBuf>,
+    pub play: Option<PathBuf>,
+    pub screenshot: Option<PathBuf>,
+    pub dump_dir: Option<PathBuf>,
+    pub dump_every: u64,
+    pub dump_last: bool,
+    pub dump_format: ImageFormat,
+    pub audio: Option<PathBuf>,
+    pub trace: bool,
+    pub trace_log: Option<PathBuf>,
+    pub trace_pc: Option<RangeInclusive<u16>>,
+    pub trace_bank: Option<u16>,
+    pub debug: bool,
+    pub rewind: Option<f64>,
+    pub rewind_budget: usize,
+    pub gdb: Option<u16>,
+    pub symbols: Option<PathBuf>,
+    pub cheats: Option<PathBuf>,
+    pub cheat_codes: Vec<String>,
+    pub info: bool,
+}
+
+pub struct DisasmOptions {
+    pub rom: String,
+    pub output: Option<PathBuf>,
+    pub symbols: Option<PathBuf>,
+}
+
+pub struct TraceDiffOptions {
+    pub rom: String,
+    pub reference: PathBuf,
+    pub model: Model,
+    pub context: usize,
+    pub writes: usize,
+}
+
+pub struct ScanOptions {
+    pub dir: PathBuf,
+    pub output: Option<PathBuf>,
+    pub format: CatalogueFormat,
+}
+
+// Splits `--name=value` into its parts; other arguments pass through whole.
+fn split_arg(arg: &str) -> (&str, Option<&str>) {
+    if arg.starts_with("--") {
+        if let Some((name, value)) = arg.split_once('=') {
+            return (name, Some(value));
+        }
+    }
+    (arg, None)
+}
+
+// Takes the value of an option, either inline (`--name=value`) or from the
+// next argument.
+fn take_value<I: Iterator<Item = String>>(name: &str, inline: Option<&str>, args: &mut I) -> Result<String, String> {
+    match inline {
+        Some(value) => Ok(value.to_string()),
+        None => args.next().ok_or(format!("{} requires a value", name)),
+    }
+}
+
+fn set_rom(rom: &mut Option<String>, arg: &str) -> Result<(), String> {
+    if rom.is_some() {
+        return Err(format!("unexpected argument '{}'", arg));
+    }
+    *rom = Some(arg.to_string());
+    Ok(())
+}
+
+fn parse_slot(slot: &str) -> Result<u8, String> {
+    slot.parse().ok().filter(|&slot| slot <= 9).ok_or(format!("invalid save state slot '{}'", slot))
+}
+
+pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.peekable();
+    match args.peek().map(String::as_str) {
+        Some("disasm") => {
+            args.next();
+            parse_disasm(args)
+        }
+        Some("tracediff") => {
+            args.next();
+            parse_tracediff(args)
+        }
+        Some("scan") => {
+            args.next();
+            parse_scan(args)
+        }
+        _ => parse_run(args),
+    }
+}
+
+fn parse_disasm<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut output = None;
+    let mut symbols = None;
+    let mut rom = None;
+    while let Some(arg) = args.next() {
+        let (name, inline) = split_arg(&arg);
+        match name {
+            "-h" | "--help" => return Ok(Command::Help(DISASM_USAGE)),
+            "-o" | "--output" => output = Some(PathBuf::from(take_value(name, inline, &mut args)?)),
+            "--symbols" => symbols = Some(PathBuf::from(take_value(name, inline, &mut args)?)),
+            _ if name.starts_with('-') && name.len() > 1 => {
+                return Err(format!("unknown option '{}'", name));
+            }
+            _ => set_rom(&mut rom, &arg)?,
+        }
+    }
+    Ok(Command::Disasm(DisasmOptions { rom: rom.ok_or("missing ROM path")?, output, symbols }))
+}
+
+fn parse_tracediff<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut model = Model::Dmg;
+    let mut context = 10;
+    let mut writes = 16;
+    let mut paths = vec![];
+    while let Some(arg) = args.next() {
+        let (name, inline) = split_arg(&arg);
+        let mut value = || take_value(name, inline, &mut args);
+        match name {
+            "-h" | "--help" => return Ok(Command::Help(TRACEDIFF_USAGE)),
+            "-m" | "--model" => {
+                let name = value()?;
+                model = Model::from_name(&name).ok_or(format!("unknown model '{}'", name))?;
+            }
+            "-C" | "--context" => {
+                let count = value()?;
+                context = count.parse().map_err(|_| format!("invalid line count '{}'", count))?;
+            }
+            "-w" | "--writes" => {
+                let count = value()?;
+                writes = count.parse().map_err(|_| format!("invalid write count '{}'", count))?;
+            }
+            _ if name.starts_with('-') && name.len() > 1 => {
+                return Err(format!("unknown option '{}'", name));
+            }
+            _ => paths.push(arg),
+        }
+    }
+    match <[String; 2]>::try_from(paths) {
+        Ok([rom, reference]) => Ok(Command::TraceDiff(TraceDiffOptions {
+            rom, reference: PathBuf::from(reference), model, context, writes,
+        })),
+        Err(paths) if paths.len() > 2 => Err(format!("unexpected argument '{}'", paths[2])),
+        Err(_) => Err("tracediff requires a ROM and a reference trace".to_string()),
+    }
+}
+
+fn parse_scan<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut output = None;
+    let mut format = None;
+    let mut dir = None;
+    while let Some(arg) = args.next() {
+        let (name, inline) = split_arg(&arg);
+        match name {
+            "-h" | "--help" => return Ok(Command::Help(SCAN_USAGE)),
+            "-o" | "--output" => output = Some(PathBuf::from(take_value(name, inline, &mut args)?)),
+            "-f" | "--format" => {
+                let name = take_value(name, inline, &mut args)?;
+                format = Some(CatalogueFormat::from_name(&name).ok_or(format!("unknown catalogue format '{}'", name))?);
+            }
+            _ if name.starts_with('-') && name.len() > 1 => {
+                return Err(format!("unknown option '{}'", name));
+            }
+            _ => set_rom(&mut dir, &arg)?,
+        }
+    }
+    let format = format.unwrap_or_else(|| output.as_deref().map_or(CatalogueFormat::Json, CatalogueFormat::from_path));
+    Ok(Command::Scan(ScanOptions { dir: PathBuf::from(dir.ok_or("missing directory")?), output, format }))
+}
+
+fn parse_run<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut options = Options {
+        rom: String::new(),
+        model: Model::Dmg,
+        boot_rom: None,
+        patch: None,
+        auto_patch: false,
+        zip_entry: None,
+        save_dir: None,
+        frames: None,
+        load_state: None,
+        save_state: None,
+        record: None,
+        play: None,
+        screenshot: None,
+        dump_dir: None,
+        dump_every: 1,
+        dump_last: false,
+        dump_format: ImageFormat::Png,
+        audio: None,
+        trace: false,
+        trace_log: None,
+        trace_pc: None,
+        trace_bank: None,
+        debug: false,
+        rewind: None,
+        rewind_budget: 64,
+        gdb: None,
+        symbols: None,
+        cheats: None,
+        cheat_codes: vec![],
+        info: false,
+    };
+    let mut rom = None;
+    while let Some(arg) = args.next() {
+        let (name, inline) = split_arg(&arg);
+        let mut value = || take_value(name, inline, &mut args);
+        match name {
+            "-h" | "--help" => return Ok(Command::Help(USAGE)),
+            "-i" | "--info" => options.info = true,
+            "-t" | "--trace" => options.trace = true,
+            "-d" | "--debug" => options.debug = true,
+            "-m" | "--model" => {
+                let model = value()?;
+                options.model = Model::from_name(&model)
+                    .ok_or(format!("unknown model '{}'", model))?;
+            }
+            "-b" | "--boot-rom" => options.boot_rom = Some(value()?),
+            "-p" | "--patch" => options.patch = Some(PathBuf::from(value()?)),
+            "--auto-patch" => options.auto_patch = true,
+            "--zip-entry" => options.zip_entry = Some(value()?),
+            "-s" | "--save-dir" => options.save_dir = Some(PathBuf::from(value()?)),
+            "-f" | "--frames" => {
+                let frames = value()?;
+                options.frames = Some(frames.parse()
+                    .map_err(|_| format!("invalid frame count '{}'", frames))?);
+            }
+            "--trace-log" => options.trace_log = Some(PathBuf::from(value()?)),
+            "--trace-pc" => {
+                let range = value()?;
+                options.trace_pc = Some(range.split_once('-')
+                    .and_then(|(start, end)| Some(parse_address(start)?..=parse_address(end)?))
+                    .filter(|range| !range.is_empty())
+                    .ok_or(format!("invalid PC range '{}'", range))?);
+            }
+            "--trace-bank" => {
+                let bank = value()?;
+                options.trace_bank = Some(parse_address(&bank).ok_or(format!("invalid bank '{}'", bank))?);
+            }
+            "--rewind" => {
+                let seconds = value()?;
+                options.rewind = Some(seconds.parse().ok().filter(|&seconds: &f64| seconds > 0.0)
+                    .ok_or(format!("invalid rewind length '{}'", seconds))?);
+            }
+            "--rewind-mem" => {
+                let budget = value()?;
+                options.rewind_budget = budget.parse().ok().filter(|&budget| budget > 0)
+                    .ok_or(format!("invalid rewind budget '{}'", budget))?;
+            }
+            "--gdb" => {
+                let port = value()?;
+                options.gdb = Some(port.parse().map_err(|_| format!("invalid port '{}'", port))?);
+            }
+            "--load-state" => options.load_state = Some(parse_slot(&value()?)?),
+            "--save-state" => options.save_state = Some(parse_slot(&value()?)?),
+            "--record" => options.record = Some(PathBuf::from(value()?)),
+            "--play" => options.play = Some(PathBuf::from(value()?)),
+            "--screenshot" => options.screenshot = Some(PathBuf::from(value()?)),
+            "--symbols" => options.symbols = Some(PathBuf::from(value()?)),
+            "--cheats" => options.cheats = Some(PathBuf::from(value()?)),
+            "--cheat" => options.cheat_codes.push(value()?),
+            "--audio" => options.audio = Some(PathBuf::from(value()?)),
+            "--dump-dir" => options.dump_dir = Some(PathBuf::from(value()?)),
+            "--dump-every" => {
+                let every = value()?;
+                options.dump_every = every.parse().ok().filter(|&n| n > 0)
+                    .ok_or(format!("invalid frame interval '{}'", every))?;
+            }
+            "--dump-last" => options.dump_last = true,
+            "--dump-format" => {
+                let format = value()?;
+                options.dump_format = ImageFormat::from_name(&format)
+                    .ok_or(format!("unknown image format '{}'", format))?;
+            }
+            _ if name.starts_with('-') && name.len() > 1 => {
+                return Err(format!("unknown option '{}'", name));
+            }
+            _ => set_rom(&mut rom, &arg)?,
+        }
+    }
+    options.rom = rom.ok_or("missing ROM path")?;
+    if (options.trace_pc.is_some() || options.trace_bank.is_some()) && options.trace_log.is_none() {
+        return Err("--trace-pc and --trace-bank require --trace-log".to_string());
+    }
+    if options.debug && options.gdb.is_some() {
+        return Err("--debug and --gdb cannot be used together".to_string());
+    }
+    if options.record.is_some() && options.play.is_some() {
+        return Err("--record and --play cannot be used together".to_string());
+    }
+    if options.play.is_some() && options.load_state.is_some() {
+        return Err("--play starts from the movie's own state; it cannot be used with --load-state".to_string());
+    }
+    if options.dump_last && options.frames.is_none() {
+        return Err("--dump-last requires --frames".to_string());
+    }
+    Ok(Command::Run(Box::new(options)))
+}
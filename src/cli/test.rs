@@ -0,0 +1,110 @@
+use super::*;
+
+fn parse_line(line: &str) -> Result<Command, String> {
+    parse(line.split_whitespace().map(str::to_string))
+}
+
+fn run(line: &str) -> Options {
+    match parse_line(line) {
+        Ok(Command::Run(options)) => *options,
+        Ok(_) => panic!("'{}' is not a run command", line),
+        Err(err) => panic!("'{}' failed: {}", line, err),
+    }
+}
+
+fn error(line: &str) -> String {
+    match parse_line(line) {
+        Ok(_) => panic!("'{}' parsed", line),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn run_options() {
+    let options = run("game.gb");
+    assert_eq!(options.rom, "game.gb");
+    assert_eq!(options.model, Model::Dmg);
+    assert_eq!(options.frames, None);
+    assert_eq!(options.rewind_budget, 64);
+    assert_eq!(options.dump_every, 1);
+
+    let options = run("-m sgb2 --frames 30 -t --trace-log=t.log --trace-pc 0150-01ff \
+        --trace-bank=2 --cheat 00A-17B --cheat 01FF25C0 --dump-format=PPM game.gb");
+    assert_eq!(options.model, Model::Sgb2);
+    assert_eq!(options.frames, Some(30));
+    assert!(options.trace);
+    assert_eq!(options.trace_log, Some(PathBuf::from("t.log")));
+    assert_eq!(options.trace_pc, Some(0x0150..=0x01FF));
+    assert_eq!(options.trace_bank, Some(2));
+    assert_eq!(options.cheat_codes, ["00A-17B", "01FF25C0"]);
+    assert_eq!(options.dump_format, ImageFormat::Ppm);
+
+    // An inline value may itself contain '='.
+    assert_eq!(run("--save-dir=a=b game.gb").save_dir, Some(PathBuf::from("a=b")));
+    assert_eq!(run("--load-state 9 game.gb").load_state, Some(9));
+    assert!(matches!(parse_line("game.gb --help"), Ok(Command::Help(USAGE))));
+}
+
+#[test]
+fn value_errors() {
+    assert_eq!(error(""), "missing ROM path");
+    assert_eq!(error("game.gb other.gb"), "unexpected argument 'other.gb'");
+    assert_eq!(error("game.gb --frames"), "--frames requires a value");
+    assert_eq!(error("--frames=ten game.gb"), "invalid frame count 'ten'");
+    assert_eq!(error("-m cgb game.gb"), "unknown model 'cgb'");
+    assert_eq!(error("--load-state 10 game.gb"), "invalid save state slot '10'");
+    assert_eq!(error("--trace-log t.log --trace-pc 0200-0100 game.gb"), "invalid PC range '0200-0100'");
+    assert_eq!(error("--rewind 0 game.gb"), "invalid rewind length '0'");
+    assert_eq!(error("--dump-every 0 game.gb"), "invalid frame interval '0'");
+    assert_eq!(error("--gdb 70000 game.gb"), "invalid port '70000'");
+    assert_eq!(error("--dump-format gif game.gb"), "unknown image format 'gif'");
+    assert_eq!(error("--bogus game.gb"), "unknown option '--bogus'");
+    // A lone '-' is taken as a path.
+    assert_eq!(run("-").rom, "-");
+}
+
+#[test]
+fn conflicting_flags() {
+    assert!(error("--trace-pc 0150-0200 game.gb").contains("require --trace-log"));
+    assert!(error("--trace-bank 1 game.gb").contains("require --trace-log"));
+    assert!(error("-d --gdb 2345 game.gb").contains("cannot be used together"));
+    assert!(error("--record a.gbm --play b.gbm game.gb").contains("cannot be used together"));
+    assert!(error("--play b.gbm --load-state 1 game.gb").contains("--load-state"));
+    assert!(error("--dump-last game.gb").contains("requires --frames"));
+    assert!(run("--dump-last -f 10 game.gb").dump_last);
+}
+
+#[test]
+fn subcommands() {
+    match parse_line("disasm -o out.asm game.gb") {
+        Ok(Command::Disasm(options)) => {
+            assert_eq!(options.rom, "game.gb");
+            assert_eq!(options.output, Some(PathBuf::from("out.asm")));
+        }
+        _ => panic!("expected disasm"),
+    }
+    match parse_line("tracediff -C 3 --writes=4 game.gb ref.log") {
+        Ok(Command::TraceDiff(options)) => {
+            assert_eq!((options.rom.as_str(), options.context, options.writes), ("game.gb", 3, 4));
+            assert_eq!(options.reference, PathBuf::from("ref.log"));
+        }
+        _ => panic!("expected tracediff"),
+    }
+    match parse_line("scan -o roms.csv roms") {
+        Ok(Command::Scan(options)) => {
+            assert_eq!(options.dir, PathBuf::from("roms"));
+            assert_eq!(options.format, CatalogueFormat::Csv);
+        }
+        _ => panic!("expected scan"),
+    }
+    match parse_line("scan -f csv -o roms.json roms") {
+        Ok(Command::Scan(options)) => assert_eq!(options.format, CatalogueFormat::Csv),
+        _ => panic!("expected scan"),
+    }
+    assert_eq!(error("tracediff game.gb"), "tracediff requires a ROM and a reference trace");
+    assert_eq!(error("tracediff a b c"), "unexpected argument 'c'");
+    assert_eq!(error("scan -f xml roms"), "unknown catalogue format 'xml'");
+    assert_eq!(error("scan"), "missing directory");
+    assert_eq!(error("disasm --model dmg game.gb"), "unknown option '--model'");
+    assert!(matches!(parse_line("scan --help"), Ok(Command::Help(SCAN_USAGE))));
+}
@@ -0,0 +1,514 @@
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use crate::bus::{Access, WatchHit, Watchpoint};
+use crate::cheats::Cheat;
+use crate::disasm::Flow;
+use crate::sharp8080::{FLAG_C, FLAG_H, FLAG_N, FLAG_Z};
+use crate::symbols::parse_address;
+use crate::GameBoy;
+
+// The interrupt flag of the debugger that interrupt() stops. A signal handler
+// cannot reach the Debugger itself, so its flag is registered here.
+static INTERRUPT_TARGET: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Makes interrupt() stop this debugger. Only the first registration in a
+/// process takes effect.
+pub fn route_interrupts(debugger: &Debugger) {
+    let _ = INTERRUPT_TARGET.set(debugger.interrupt_flag());
+}
+
+/// Asks the registered debugger to stop at the next instruction. Safe to
+/// call from a signal handler.
+pub fn interrupt() {
+    if let Some(flag) = INTERRUPT_TARGET.get() {
+        flag.store(true, Ordering::SeqCst);
+    }
+}
+
+const HELP: &str = "\
+Commands:
+  break, b <loc>          Break at an address, BB:AAAA location or symbol
+  delete, d [n]           Delete breakpoint n, or every breakpoint
+  watch, w <loc> [r|w|rw] Stop when the CPU reads and/or writes an address [default: w]
+  unwatch <n>             Delete watchpoint n
+  breakpoints, bl         List breakpoints and watchpoints
+  step, s [n]             Execute n instructions [default: 1]
+  next, n                 Step over calls and rsts
+  finish, out             Run until the current routine returns
+  continue, c             Run until something stops execution
+  rewind [n]              Go back n frames, if rewind is enabled [default: 1]
+  regs, r                 Print the registers
+  set <reg> <value>       Set a register: a f b c d e h l af bc de hl sp pc ime
+  x, mem <loc> [len]      Dump memory [default: 64 bytes]
+  poke <loc> <byte>...    Write bytes to memory
+  dis, l [loc] [n]        Disassemble n instructions around PC or from loc [default: 8]
+  cheats                  List cheats
+  cheat <code> [name]     Add a Game Genie or GameShark code
+  cheat on|off|del <n>    Switch cheat n on or off, or delete it
+  help, h                 Print this help
+  quit, q                 Exit the emulator
+An empty line repeats the previous command.";
+
+/// A breakpoint on an address, optionally only while `bank` is mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub bank: Option<u16>,
+    pub addr: u16,
+}
+
+/// Why the debugger took control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Step,
+    Interrupted,
+    Breakpoint(usize),
+    Watchpoint(WatchHit),
+    Undefined(u16),
+    Returned,
+}
+
+/// What the emulator should do when the prompt is left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Resume,
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Run,
+    Step(u32),
+    // Run until the instruction after a call at the same or a shallower
+    // stack depth.
+    Over { addr: u16, sp: u16 },
+    // Run until a return leaves SP above where it started.
+    Finish { sp: u16, returning: bool },
+}
+
+/// An interactive debugger that is consulted before every instruction.
+/// Pass [`Debugger::check`] to [`GameBoy::run_frame_until`] and run
+/// [`Debugger::prompt`] whenever it stops.
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    mode: Mode,
+    // Skips breakpoints for the first instruction after resuming, so that
+    // continuing from a breakpoint does not stop on it again.
+    resuming: bool,
+    stop: Option<Stop>,
+    last_command: String,
+    interrupt: Arc<AtomicBool>,
+}
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
+
+impl Debugger {
+    /// Creates a debugger that stops before the first instruction.
+    pub fn new() -> Debugger {
+        Debugger { breakpoints: vec![], mode: Mode::Step(0), resuming: false, stop: None,
+            last_command: String::new(), interrupt: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// A flag that stops execution at the next instruction when set.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.interrupt.clone()
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Why execution last stopped.
+    pub fn stop_reason(&self) -> Option<Stop> {
+        self.stop
+    }
+
+    /// Returns true if execution should stop before the next instruction.
+    pub fn check(&mut self, gb: &mut GameBoy) -> bool {
+        let resuming = std::mem::take(&mut self.resuming);
+        self.stop = self.stop_reason_for(gb, resuming);
+        self.stop.is_some()
+    }
+
+    fn stop_reason_for(&mut self, gb: &mut GameBoy, resuming: bool) -> Option<Stop> {
+        if self.interrupt.swap(false, Ordering::SeqCst) {
+            return Some(Stop::Interrupted);
+        }
+        if let Some(hit) = gb.bus_mut().take_watch_hit() {
+            return Some(Stop::Watchpoint(hit));
+        }
+        if let Some(opcode) = gb.cpu().undefined_opcode() {
+            return Some(Stop::Undefined(opcode));
+        }
+        let pc = gb.cpu().pc();
+        match &mut self.mode {
+            Mode::Run => {}
+            Mode::Step(0) => return Some(Stop::Step),
+            Mode::Step(steps) => *steps -= 1,
+            Mode::Over { addr, sp } => {
+                if pc == *addr && gb.cpu().sp() >= *sp {
+                    return Some(Stop::Step);
+                }
+            }
+            Mode::Finish { sp, returning } => {
+                if *returning && gb.cpu().sp() > *sp {
+                    return Some(Stop::Returned);
+                }
+                *returning = matches!(gb.decode(pc).flow(), Flow::Return | Flow::ConditionalReturn);
+            }
+        }
+        if resuming {
+            return None;
+        }
+        let bank = gb.bank_at(pc);
+        self.breakpoints.iter()
+            .position(|breakpoint| breakpoint.addr == pc && breakpoint.bank.is_none_or(|b| b == bank))
+            .map(Stop::Breakpoint)
+    }
+
+    /// Reports why execution stopped and reads commands until one resumes
+    /// execution or quits. End of input quits.
+    pub fn prompt<R: BufRead, W: Write>(&mut self, gb: &mut GameBoy, input: &mut R, output: &mut W)
+        -> io::Result<Action> {
+        self.report(gb, output)?;
+        loop {
+            write!(output, "(gbdb) ")?;
+            output.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(output)?;
+                return Ok(Action::Quit);
+            }
+            let line = line.trim();
+            let command = if line.is_empty() { self.last_command.clone() } else { line.to_string() };
+            if command.is_empty() {
+                continue;
+            }
+            self.last_command = command.clone();
+            match self.execute(gb, &command, output) {
+                Ok(Some(action)) => {
+                    if action == Action::Resume {
+                        self.resume(gb);
+                    }
+                    return Ok(action);
+                }
+                Ok(None) => {}
+                Err(err) => writeln!(output, "error: {}", err)?,
+            }
+        }
+    }
+
+    fn resume(&mut self, gb: &mut GameBoy) {
+        // A locked-up CPU stays on the undefined opcode. Unlocking it lets
+        // the user move PC elsewhere; otherwise it simply traps again.
+        gb.cpu_mut().clear_undefined_opcode();
+        self.interrupt.store(false, Ordering::SeqCst);
+        self.resuming = true;
+    }
+
+    fn report<W: Write>(&self, gb: &GameBoy, output: &mut W) -> io::Result<()> {
+        match self.stop {
+            Some(Stop::Interrupted) => writeln!(output, "Interrupted")?,
+            Some(Stop::Breakpoint(index)) => writeln!(output, "Breakpoint {}", index + 1)?,
+            Some(Stop::Watchpoint(hit)) => {
+                let access = if hit.access == Access::Read { "Read" } else { "Write" };
+                writeln!(output, "{} of {:02X} at {}", access, hit.value, self.location(gb, hit.addr))?;
+            }
+            Some(Stop::Undefined(opcode)) => writeln!(output, "Undefined opcode {:02X}; the CPU has locked up", opcode)?,
+            Some(Stop::Returned) => writeln!(output, "Returned")?,
+            Some(Stop::Step) | None => {}
+        }
+        self.print_instruction(gb, gb.cpu().pc(), true, output)
+    }
+
+    fn location(&self, gb: &GameBoy, addr: u16) -> String {
+        match gb.symbols().name_at(addr, gb.bank_at(addr)) {
+            Some(name) => format!("{:04X} ({})", addr, name),
+            None => format!("{:04X}", addr),
+        }
+    }
+
+    fn print_instruction<W: Write>(&self, gb: &GameBoy, addr: u16, current: bool, output: &mut W) -> io::Result<()> {
+        if let Some(name) = gb.symbols().name_at(addr, gb.bank_at(addr)) {
+            writeln!(output, "{}:", name)?;
+        }
+        let marker = if current { "=>" } else { "  " };
+        let bytes: Vec<String> = gb.decode(addr).bytes().iter().map(|byte| format!("{:02X}", byte)).collect();
+        writeln!(output, "{} {:02X}:{:04X}  {:<9} {}", marker, gb.bank_at(addr), addr, bytes.join(" "),
+            gb.disassemble(addr))
+    }
+
+    fn resolve(&self, gb: &GameBoy, text: &str) -> Result<(Option<u16>, u16), String> {
+        gb.symbols().resolve(text).ok_or(format!("unknown location '{}'", text))
+    }
+
+    fn address(&self, gb: &GameBoy, text: &str) -> Result<u16, String> {
+        self.resolve(gb, text).map(|(_, addr)| addr)
+    }
+
+    fn execute<W: Write>(&mut self, gb: &mut GameBoy, command: &str, output: &mut W) -> Result<Option<Action>, String> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        let write_err = |err: io::Error| err.to_string();
+        match name {
+            "help" | "h" => writeln!(output, "{}", HELP).map_err(write_err)?,
+            "quit" | "q" => return Ok(Some(Action::Quit)),
+            "continue" | "c" => {
+                self.mode = Mode::Run;
+                return Ok(Some(Action::Resume));
+            }
+            "step" | "s" => {
+                let steps = match args.first() {
+                    Some(count) => count.parse().ok().filter(|&n| n > 0)
+                        .ok_or(format!("invalid step count '{}'", count))?,
+                    None => 1,
+                };
+                self.mode = Mode::Step(steps);
+                return Ok(Some(Action::Resume));
+            }
+            "next" | "n" => {
+                let instruction = gb.decode(gb.cpu().pc());
+                self.mode = match instruction.flow() {
+                    Flow::Call(_) => Mode::Over {
+                        addr: instruction.address.wrapping_add(instruction.length),
+                        sp: gb.cpu().sp(),
+                    },
+                    _ => Mode::Step(1),
+                };
+                return Ok(Some(Action::Resume));
+            }
+            "finish" | "out" => {
+                self.mode = Mode::Finish { sp: gb.cpu().sp(), returning: false };
+                return Ok(Some(Action::Resume));
+            }
+            "break" | "b" => {
+                let location = args.first().ok_or("break requires a location")?;
+                let (bank, addr) = self.resolve(gb, location)?;
+                self.breakpoints.push(Breakpoint { bank, addr });
+                writeln!(output, "Breakpoint {} at {}", self.breakpoints.len(), describe(bank, addr))
+                    .map_err(write_err)?;
+            }
+            "delete" | "d" => match args.first() {
+                Some(number) => {
+                    let index = number.parse::<usize>().ok().filter(|&n| n >= 1 && n <= self.breakpoints.len())
+                        .ok_or(format!("no breakpoint '{}'", number))?;
+                    self.breakpoints.remove(index - 1);
+                }
+                None => self.breakpoints.clear(),
+            },
+            "watch" | "w" => {
+                let location = args.first().ok_or("watch requires a location")?;
+                let addr = self.address(gb, location)?;
+                let (read, write) = match args.get(1).copied().unwrap_or("w") {
+                    "r" => (true, false),
+                    "w" => (false, true),
+                    "rw" => (true, true),
+                    other => return Err(format!("unknown access '{}'", other)),
+                };
+                gb.bus_mut().add_watchpoint(Watchpoint { addr, read, write });
+                writeln!(output, "Watchpoint {} at {}", gb.bus().watchpoints().len(), self.location(gb, addr))
+                    .map_err(write_err)?;
+            }
+            "unwatch" => {
+                let number = args.first().ok_or("unwatch requires a watchpoint number")?;
+                number.parse::<usize>().ok().filter(|&n| n >= 1)
+                    .and_then(|n| gb.bus_mut().remove_watchpoint(n - 1))
+                    .ok_or(format!("no watchpoint '{}'", number))?;
+            }
+            "rewind" => {
+                let frames = match args.first() {
+                    Some(count) => parse_count(count)? as u64,
+                    None => 1,
+                };
+                let back = gb.rewind(frames)?;
+                writeln!(output, "Rewound {} frames", back).map_err(write_err)?;
+                self.print_instruction(gb, gb.cpu().pc(), true, output).map_err(write_err)?;
+            }
+            "breakpoints" | "bl" => self.list(gb, output).map_err(write_err)?,
+            "regs" | "r" => self.print_registers(gb, output).map_err(write_err)?,
+            "set" => {
+                let (register, value) = match args[..] {
+                    [register, value] => (register, value),
+                    _ => return Err("usage: set <reg> <value>".to_string()),
+                };
+                let value = self.address(gb, value)?;
+                set_register(gb, register, value)?;
+            }
+            "x" | "mem" => {
+                let location = args.first().ok_or("x requires a location")?;
+                let addr = self.address(gb, location)?;
+                let len = match args.get(1) {
+                    Some(len) => parse_count(len)?,
+                    None => 64,
+                };
+                self.dump(gb, addr, len, output).map_err(write_err)?;
+            }
+            "poke" | "write" => {
+                let (location, bytes) = args.split_first().ok_or("poke requires a location")?;
+                let addr = self.address(gb, location)?;
+                let bytes = bytes.iter()
+                    .map(|byte| parse_address(byte).and_then(|b| u8::try_from(b).ok())
+                        .ok_or(format!("invalid byte '{}'", byte)))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                for (i, &byte) in bytes.iter().enumerate() {
+                    gb.bus_mut().poke(addr.wrapping_add(i as u16), byte);
+                }
+            }
+            "dis" | "l" => {
+                let pc = gb.cpu().pc();
+                let (start, count) = match args[..] {
+                    [] => (self.before(gb, pc, 3), 8),
+                    [location] => (self.address(gb, location)?, 8),
+                    [location, count] => (self.address(gb, location)?, parse_count(count)?),
+                    _ => return Err("usage: dis [loc] [n]".to_string()),
+                };
+                let mut addr = start;
+                for _ in 0..count {
+                    self.print_instruction(gb, addr, addr == pc, output).map_err(write_err)?;
+                    addr = addr.wrapping_add(gb.decode(addr).length);
+                }
+            }
+            "cheats" => self.list_cheats(gb, output).map_err(write_err)?,
+            "cheat" => match args[..] {
+                [action @ ("on" | "off" | "del"), number] => {
+                    let index = number.parse::<usize>().ok().filter(|&n| n >= 1).map(|n| n - 1)
+                        .filter(|&index| index < gb.bus().cheats().len())
+                        .ok_or(format!("no cheat '{}'", number))?;
+                    match action {
+                        "del" => {
+                            gb.bus_mut().remove_cheat(index);
+                        }
+                        _ => {
+                            gb.bus_mut().set_cheat_enabled(index, action == "on");
+                        }
+                    }
+                }
+                [code, ref name @ ..] => {
+                    let mut cheat = Cheat::parse(code)?;
+                    cheat.name = name.join(" ");
+                    gb.bus_mut().add_cheat(cheat);
+                    writeln!(output, "Cheat {} added", gb.bus().cheats().len()).map_err(write_err)?;
+                }
+                [] => return Err("usage: cheat <code> [name] | cheat on|off|del <n>".to_string()),
+            },
+            _ => return Err(format!("unknown command '{}'; try help", name)),
+        }
+        Ok(None)
+    }
+
+    // Finds where to start disassembling so that up to `count`
+    // instructions before `addr` are shown. Instructions have no markers, so
+    // this tries starting points from furthest back and takes the first one
+    // whose decoding lands exactly on `addr`.
+    fn before(&self, gb: &GameBoy, addr: u16, count: usize) -> u16 {
+        for distance in (1..=count as u16 * 3).rev() {
+            let start = addr.wrapping_sub(distance);
+            let mut at = start;
+            let mut steps = 0;
+            while at != addr && steps < count && addr.wrapping_sub(at) <= distance {
+                at = at.wrapping_add(gb.decode(at).length);
+                steps += 1;
+            }
+            if at == addr {
+                return start;
+            }
+        }
+        addr
+    }
+
+    fn list<W: Write>(&self, gb: &GameBoy, output: &mut W) -> io::Result<()> {
+        if self.breakpoints.is_empty() && gb.bus().watchpoints().is_empty() {
+            return writeln!(output, "No breakpoints or watchpoints");
+        }
+        for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+            writeln!(output, "Breakpoint {} at {}", i + 1, describe(breakpoint.bank, breakpoint.addr))?;
+        }
+        for (i, watchpoint) in gb.bus().watchpoints().iter().enumerate() {
+            let access = match (watchpoint.read, watchpoint.write) {
+                (true, true) => "rw",
+                (true, false) => "r",
+                _ => "w",
+            };
+            writeln!(output, "Watchpoint {} at {} ({})", i + 1, self.location(gb, watchpoint.addr), access)?;
+        }
+        Ok(())
+    }
+
+    fn list_cheats<W: Write>(&self, gb: &GameBoy, output: &mut W) -> io::Result<()> {
+        if gb.bus().cheats().is_empty() {
+            return writeln!(output, "No cheats");
+        }
+        for (i, cheat) in gb.bus().cheats().iter().enumerate() {
+            let state = if cheat.enabled { "on" } else { "off" };
+            let line = format!("Cheat {} {} ({}) {}", i + 1, cheat.code, state, cheat.name);
+            writeln!(output, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+
+    fn print_registers<W: Write>(&self, gb: &GameBoy, output: &mut W) -> io::Result<()> {
+        let registers = gb.cpu().registers();
+        let flag = |mask: u8, name: char| if registers.f & mask != 0 { name } else { '-' };
+        writeln!(output, "AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X} PC={:04X} IME={} Flags={}{}{}{}",
+            registers.af(), registers.bc(), registers.de(), registers.hl(), registers.sp, registers.pc,
+            registers.ime as u8, flag(FLAG_Z, 'Z'), flag(FLAG_N, 'N'), flag(FLAG_H, 'H'), flag(FLAG_C, 'C'))
+    }
+
+    fn dump<W: Write>(&self, gb: &GameBoy, addr: u16, len: usize, output: &mut W) -> io::Result<()> {
+        let bytes: Vec<u8> = (0..len).map(|i| gb.bus().peek(addr.wrapping_add(i as u16))).collect();
+        for (row, chunk) in bytes.chunks(16).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+            let text: String = chunk.iter()
+                .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+                .collect();
+            writeln!(output, "{:04X}: {:<47}  {}", addr.wrapping_add(row as u16 * 16), hex.join(" "), text)?;
+        }
+        Ok(())
+    }
+}
+
+fn describe(bank: Option<u16>, addr: u16) -> String {
+    match bank {
+        Some(bank) => format!("{:02X}:{:04X}", bank, addr),
+        None => format!("{:04X}", addr),
+    }
+}
+
+fn parse_count(text: &str) -> Result<usize, String> {
+    text.parse().ok().filter(|&n| n > 0).ok_or(format!("invalid count '{}'", text))
+}
+
+fn set_register(gb: &mut GameBoy, register: &str, value: u16) -> Result<(), String> {
+    let mut registers = gb.cpu().registers();
+    let byte = || u8::try_from(value).map_err(|_| format!("{:X} does not fit in {}", value, register));
+    match register.to_ascii_lowercase().as_str() {
+        "a" => registers.a = byte()?,
+        "f" => registers.f = byte()? & 0xF0,
+        "b" => registers.b = byte()?,
+        "c" => registers.c = byte()?,
+        "d" => registers.d = byte()?,
+        "e" => registers.e = byte()?,
+        "h" => registers.h = byte()?,
+        "l" => registers.l = byte()?,
+        "af" => (registers.a, registers.f) = ((value >> 8) as u8, value as u8 & 0xF0),
+        "bc" => (registers.b, registers.c) = ((value >> 8) as u8, value as u8),
+        "de" => (registers.d, registers.e) = ((value >> 8) as u8, value as u8),
+        "hl" => (registers.h, registers.l) = ((value >> 8) as u8, value as u8),
+        "sp" => registers.sp = value,
+        "pc" => registers.pc = value,
+        "ime" => registers.ime = value != 0,
+        _ => return Err(format!("unknown register '{}'", register)),
+    }
+    gb.cpu_mut().set_registers(&registers);
+    Ok(())
+}
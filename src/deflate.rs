@@ -0,0 +1,400 @@
+// Raw DEFLATE (RFC 1951) compression plus the checksums used by the
+// container formats built on top of it.
+
+#[cfg(test)]
+mod test;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: usize = 15;
+const MAX_CHAIN: usize = 64;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+/// CRC-32 as used by PNG, gzip and zip.
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Continues a CRC-32 computed over earlier data.
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut c = !crc;
+    for &b in data {
+        c = CRC_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+/// Adler-32 as used by zlib.
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit.
+    fn write_code(&mut self, code: u32, count: u32) {
+        let reversed = code.reverse_bits() >> (32 - count);
+        self.write(reversed, count);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+fn write_literal(out: &mut BitWriter, symbol: u16) {
+    match symbol {
+        0..=143 => out.write_code(0x30 + symbol as u32, 8),
+        144..=255 => out.write_code(0x190 + (symbol - 144) as u32, 9),
+        256..=279 => out.write_code((symbol - 256) as u32, 7),
+        _ => out.write_code(0xC0 + (symbol - 280) as u32, 8),
+    }
+}
+
+fn write_match(out: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+    write_literal(out, 257 + code as u16);
+    out.write((length - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+    let code = DIST_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+    out.write_code(code as u32, 5);
+    out.write((distance - DIST_BASE[code] as usize) as u32, DIST_EXTRA[code] as u32);
+}
+
+fn hash(data: &[u8]) -> usize {
+    let v = (data[0] as usize) << 16 | (data[1] as usize) << 8 | data[2] as usize;
+    (v.wrapping_mul(2654435761) >> 8) & ((1 << HASH_BITS) - 1)
+}
+
+/// Compresses `data` into a single fixed-Huffman DEFLATE block.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter { out: vec![], bits: 0, count: 0 };
+    out.write(1, 1);
+    out.write(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; data.len()];
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(&data[pos..]);
+            let mut candidate = head[h];
+            let mut chain = 0;
+            while candidate != usize::MAX && pos - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let max = (data.len() - pos).min(MAX_MATCH);
+                let len = (0..max).take_while(|&i| data[candidate + i] == data[pos + i]).count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = pos - candidate;
+                    if len == max {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+            prev[pos] = head[h];
+            head[h] = pos;
+        }
+        if best_len >= MIN_MATCH {
+            write_match(&mut out, best_len, best_dist);
+            for p in pos + 1..pos + best_len {
+                if p + MIN_MATCH <= data.len() {
+                    let h = hash(&data[p..]);
+                    prev[p] = head[h];
+                    head[h] = p;
+                }
+            }
+            pos += best_len;
+        } else {
+            write_literal(&mut out, data[pos] as u16);
+            pos += 1;
+        }
+    }
+    write_literal(&mut out, 256);
+    out.finish()
+}
+
+/// Wraps DEFLATE output in a zlib (RFC 1950) stream.
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(compress(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+// Order in which code length code lengths are stored in a dynamic block.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos).ok_or("unexpected end of deflate stream")?;
+            value |= (((byte >> self.bit) & 0x01) as u32) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+// A canonical Huffman code stored as the number of codes of each length and
+// the symbols sorted by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, input: &mut BitReader) -> Result<u16, String> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for len in 1..16 {
+            code |= input.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_tables(input: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let hlit = input.bits(5)? as usize + 257;
+    let hdist = input.bits(5)? as usize + 1;
+    let hclen = input.bits(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &i in CODE_LENGTH_ORDER.iter().take(hclen) {
+        code_lengths[i] = input.bits(3)? as u8;
+    }
+    let code_length_table = Huffman::new(&code_lengths);
+    let mut lengths = vec![];
+    while lengths.len() < hlit + hdist {
+        let symbol = code_length_table.decode(input)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or("repeat with no previous length")?;
+                (previous, 3 + input.bits(2)?)
+            }
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > hlit + hdist {
+        return Err("code lengths overflow".to_string());
+    }
+    Ok((Huffman::new(&lengths[..hlit]), Huffman::new(&lengths[hlit..])))
+}
+
+fn too_large(limit: usize) -> String {
+    format!("decompressed data is larger than {} bytes", limit)
+}
+
+fn inflate_block(input: &mut BitReader, out: &mut Vec<u8>, limit: usize, literals: &Huffman, distances: &Huffman)
+    -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(input)? as usize;
+        match symbol {
+            0..=255 if out.len() == limit => return Err(too_large(limit)),
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let code = symbol - 257;
+                let length = LENGTH_BASE[code] as usize + input.bits(LENGTH_EXTRA[code] as u32)? as usize;
+                let code = distances.decode(input)? as usize;
+                if code >= DIST_BASE.len() {
+                    return Err("invalid distance code".to_string());
+                }
+                let distance = DIST_BASE[code] as usize + input.bits(DIST_EXTRA[code] as u32)? as usize;
+                if distance > out.len() {
+                    return Err("distance beyond start of output".to_string());
+                }
+                if length > limit - out.len() {
+                    return Err(too_large(limit));
+                }
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+            _ => return Err("invalid literal/length code".to_string()),
+        }
+    }
+}
+
+/// Decompresses a raw DEFLATE stream, failing as soon as the output grows
+/// past `limit` bytes.
+pub fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut input = BitReader { data, pos: 0, bit: 0 };
+    let mut out = vec![];
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => {
+                input.align();
+                let header = data.get(input.pos..input.pos + 4).ok_or("truncated stored block")?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let nlen = u16::from_le_bytes([header[2], header[3]]) as usize;
+                if len != !nlen & 0xFFFF {
+                    return Err("stored block length mismatch".to_string());
+                }
+                input.pos += 4;
+                let block = data.get(input.pos..input.pos + len).ok_or("truncated stored block")?;
+                if len > limit - out.len() {
+                    return Err(too_large(limit));
+                }
+                out.extend_from_slice(block);
+                input.pos += len;
+            }
+            1 => {
+                let (literals, distances) = fixed_tables();
+                inflate_block(&mut input, &mut out, limit, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut input)?;
+                inflate_block(&mut input, &mut out, limit, &literals, &distances)?;
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Decompresses a zlib (RFC 1950) stream of at most `limit` bytes and
+/// verifies its checksum.
+pub fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if data.len() < 2 {
+        return Err("invalid zlib header".to_string());
+    }
+    let header = (data[0] as u16) << 8 | data[1] as u16;
+    if data.len() < 6 || data[0] & 0x0F != 8 || !header.is_multiple_of(31) {
+        return Err("invalid zlib header".to_string());
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("zlib preset dictionaries are not supported".to_string());
+    }
+    let out = decompress(&data[2..], limit)?;
+    let expected = u32::from_be_bytes(data[data.len() - 4..].try_into().unwrap());
+    if adler32(&out) != expected {
+        return Err("zlib checksum mismatch".to_string());
+    }
+    Ok(out)
+}
@@ -0,0 +1,72 @@
+use super::*;
+
+#[test]
+fn checksum_vectors() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414FA339);
+    assert_eq!(crc32_update(crc32(b"12345"), b"6789"), crc32(b"123456789"));
+
+    assert_eq!(adler32(b""), 1);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    // Long enough for the sums to wrap modulo 65521.
+    assert_eq!(adler32(&[0xFF; 6000]), 0xA49759EA);
+}
+
+#[test]
+fn fixed_block_encoding() {
+    // A final fixed-Huffman block holding just the end-of-block code.
+    assert_eq!(compress(b""), [0x03, 0x00]);
+    assert_eq!(compress(b"a"), [0x4B, 0x04, 0x00]);
+    // "abc" as literals, then a single nine-byte match three bytes back.
+    // zlib inflates this to the same twelve bytes.
+    assert_eq!(compress(b"abcabcabcabc"), [0x4B, 0x4C, 0x4A, 0x86, 0x23, 0x00]);
+}
+
+#[test]
+fn zlib_stream_structure() {
+    let data: Vec<u8> = (0..5000).map(|i| (i % 7 * 31 + i / 500) as u8).collect();
+    let stream = zlib_compress(&data);
+    // CMF/FLG: deflate with a 32 KiB window, a valid check value and no
+    // preset dictionary.
+    assert_eq!(stream[..2], [0x78, 0x01]);
+    assert!((0x7801u16).is_multiple_of(31));
+    assert_eq!(stream[stream.len() - 4..], adler32(&data).to_be_bytes());
+    // The repeating pattern compresses to a small fraction of its size.
+    assert!(stream.len() < data.len() / 10, "{} bytes", stream.len());
+    assert_eq!(zlib_decompress(&stream, data.len()).unwrap(), data);
+    assert_eq!(zlib_compress(b""), [0x78, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01]);
+
+    let mut corrupt = stream.clone();
+    let last = corrupt.len() - 1;
+    corrupt[last] ^= 1;
+    assert!(zlib_decompress(&corrupt, data.len()).is_err());
+}
+
+#[test]
+fn round_trips() {
+    let mut noise = vec![];
+    let mut state = 1u32;
+    for _ in 0..70000 {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        noise.push((state >> 16) as u8 & 0x0F);
+    }
+    // Matches at the maximum length and past the 32 KiB window.
+    for data in [vec![], vec![0x42], vec![0; 1000], noise] {
+        assert_eq!(decompress(&compress(&data), data.len()).unwrap(), data);
+    }
+}
+
+#[test]
+fn stops_at_the_output_limit() {
+    // A megabyte of zeros deflates to a few kilobytes.
+    let bomb = compress(&vec![0; 1 << 20]);
+    assert!(bomb.len() < 8192, "{} bytes", bomb.len());
+    assert_eq!(decompress(&bomb, 1 << 20).unwrap().len(), 1 << 20);
+    for limit in [0, 1, 1000, (1 << 20) - 1] {
+        assert!(decompress(&bomb, limit).unwrap_err().contains("larger than"), "limit {}", limit);
+    }
+    // Stored blocks and literals are held to it too.
+    assert!(decompress(&[0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c'], 2).is_err());
+    assert!(decompress(&compress(b"a"), 0).is_err());
+}
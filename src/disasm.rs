@@ -0,0 +1,571 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::fmt::Write;
+
+use crate::symbols::SymbolTable;
+use crate::BusTrait;
+
+const BANK_SIZE: usize = 0x4000;
+
+const R8: [&str; 8] = ["b", "c", "d", "e", "h", "l", "[hl]", "a"];
+const R16: [&str; 4] = ["bc", "de", "hl", "sp"];
+const R16_STACK: [&str; 4] = ["bc", "de", "hl", "af"];
+const R16_MEM: [&str; 4] = ["[bc]", "[de]", "[hl+]", "[hl-]"];
+const CONDITIONS: [&str; 4] = ["nz", "z", "nc", "c"];
+const ALU: [&str; 8] = ["add", "adc", "sub", "sbc", "and", "xor", "or", "cp"];
+const ROTATES: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+const ACCUMULATOR_OPS: [&str; 8] = ["rlca", "rrca", "rla", "rra", "daa", "cpl", "scf", "ccf"];
+
+// Interrupt vectors traced as entry points alongside the cartridge entry
+// point at 0x0100. Vectors holding padding (0x00 or 0xFF) are skipped.
+const VECTORS: [(u16, &str); 6] = [
+    (0x0040, "VBlankInterrupt"),
+    (0x0048, "StatInterrupt"),
+    (0x0050, "TimerInterrupt"),
+    (0x0058, "SerialInterrupt"),
+    (0x0060, "JoypadInterrupt"),
+    (0x0100, "Entry"),
+];
+
+/// How instructions are written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// Upper case with parenthesised memory operands: `LD A,($FF44)`.
+    Classic,
+    /// RGBDS assembler syntax: `ldh a, [$FF44]`.
+    Rgbds,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// A register or register pair such as `a` or `hl`.
+    Register(&'static str),
+    /// A branch condition: `nz`, `z`, `nc` or `c`.
+    Condition(&'static str),
+    /// Memory addressed by a register, e.g. `[hl+]`; `[c]` is 0xFF00+C.
+    Indirect(&'static str),
+    Imm8(u8),
+    Imm16(u16),
+    /// Memory at an absolute address.
+    Address(u16),
+    /// Memory at 0xFF00 + n8, stored as the full address.
+    HighAddress(u16),
+    /// The destination of a jump or call.
+    Target(u16),
+    /// SP plus a signed offset, as in `LD HL,SP+e8`.
+    SpOffset(i8),
+    /// A signed offset, as in `ADD SP,e8`.
+    Offset(i8),
+    Bit(u8),
+    Vector(u8),
+}
+
+/// How control leaves an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Jump(u16),
+    ConditionalJump(u16),
+    Call(u16),
+    Return,
+    ConditionalReturn,
+    /// JP HL; the destination is not known statically.
+    IndirectJump,
+    /// An undefined opcode, which locks up the CPU.
+    Invalid,
+}
+
+/// A single decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    pub address: u16,
+    pub length: u16,
+    pub bytes: [u8; 3],
+    /// The RGBDS mnemonic, lower case; `db` for undefined opcodes.
+    pub mnemonic: &'static str,
+    pub operands: Vec<Operand>,
+}
+
+fn hex8(value: u8) -> String {
+    format!("${:02X}", value)
+}
+
+fn hex16(value: u16) -> String {
+    format!("${:04X}", value)
+}
+
+fn signed(value: i8) -> String {
+    if value < 0 {
+        format!("-${:02X}", value.unsigned_abs())
+    } else {
+        format!("${:02X}", value)
+    }
+}
+
+impl Operand {
+    fn format(&self, syntax: Syntax, name: &dyn Fn(u16) -> Option<String>) -> String {
+        let memory = |inner: String| match syntax {
+            Syntax::Classic => format!("({})", inner),
+            Syntax::Rgbds => format!("[{}]", inner),
+        };
+        let case = |text: &str| match syntax {
+            Syntax::Classic => text.to_ascii_uppercase(),
+            Syntax::Rgbds => text.to_string(),
+        };
+        let address = |addr: u16| name(addr).unwrap_or_else(|| hex16(addr));
+        match *self {
+            Operand::Register(reg) | Operand::Condition(reg) => case(reg),
+            Operand::Indirect(reg) => memory(case(reg.trim_matches(['[', ']']))),
+            Operand::Imm8(value) => hex8(value),
+            Operand::Imm16(value) => hex16(value),
+            Operand::Address(addr) | Operand::HighAddress(addr) => memory(address(addr)),
+            Operand::Target(addr) => address(addr),
+            Operand::SpOffset(offset) => match (syntax, offset < 0) {
+                (Syntax::Classic, false) => format!("SP+{}", signed(offset)),
+                (Syntax::Classic, true) => format!("SP{}", signed(offset)),
+                (Syntax::Rgbds, false) => format!("sp + {}", signed(offset)),
+                (Syntax::Rgbds, true) => format!("sp - {}", signed(offset).trim_start_matches('-')),
+            },
+            Operand::Offset(offset) => signed(offset),
+            Operand::Bit(bit) => bit.to_string(),
+            Operand::Vector(vector) => hex8(vector),
+        }
+    }
+}
+
+impl DecodedInstruction {
+    /// The instruction's encoding.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.length as usize]
+    }
+
+    pub fn flow(&self) -> Flow {
+        let conditional = matches!(self.operands.first(), Some(Operand::Condition(_)));
+        let target = self.operands.iter().find_map(|operand| match operand {
+            Operand::Target(addr) => Some(*addr),
+            Operand::Vector(vector) => Some(*vector as u16),
+            _ => None,
+        });
+        match (self.mnemonic, target) {
+            ("jp", Some(target)) | ("jr", Some(target)) if conditional => Flow::ConditionalJump(target),
+            ("jp", Some(target)) | ("jr", Some(target)) => Flow::Jump(target),
+            ("jp", None) => Flow::IndirectJump,
+            ("call", Some(target)) | ("rst", Some(target)) => Flow::Call(target),
+            ("ret", _) if conditional => Flow::ConditionalReturn,
+            ("ret", _) | ("reti", _) => Flow::Return,
+            ("db", _) => Flow::Invalid,
+            _ => Flow::Continue,
+        }
+    }
+
+    /// Formats the instruction, letting `name` replace addresses with
+    /// labels.
+    pub fn format_with(&self, syntax: Syntax, name: &dyn Fn(u16) -> Option<String>) -> String {
+        let operands: Vec<String> = self.operands.iter().map(|operand| operand.format(syntax, name)).collect();
+        match syntax {
+            Syntax::Classic => {
+                let mnemonic = if self.mnemonic == "ldh" { "LD" } else { self.mnemonic };
+                let mnemonic = mnemonic.to_ascii_uppercase();
+                if operands.is_empty() { mnemonic } else { format!("{} {}", mnemonic, operands.join(",")) }
+            }
+            Syntax::Rgbds => {
+                if operands.is_empty() {
+                    self.mnemonic.to_string()
+                } else {
+                    format!("{} {}", self.mnemonic, operands.join(", "))
+                }
+            }
+        }
+    }
+
+    pub fn format(&self, syntax: Syntax) -> String {
+        self.format_with(syntax, &|_| None)
+    }
+}
+
+impl fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.format(Syntax::Classic))
+    }
+}
+
+/// Decodes the instruction at the start of `bytes`, which was read from
+/// `address`. Missing trailing bytes read as zero.
+pub fn decode(bytes: &[u8], address: u16) -> DecodedInstruction {
+    let byte = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    let opcode = byte(0);
+    let n8 = byte(1);
+    let n16 = (byte(2) as u16) << 8 | n8 as u16;
+    let e8 = n8 as i8;
+    let relative = address.wrapping_add(2).wrapping_add(e8 as u16);
+
+    let (x, y, z) = ((opcode >> 6) as usize, (opcode >> 3 & 0x07) as usize, (opcode & 0x07) as usize);
+    let (p, q) = (y >> 1, y & 0x01);
+    let r8 = |index: usize| if index == 6 { Operand::Indirect(R8[6]) } else { Operand::Register(R8[index]) };
+    let a = Operand::Register("a");
+    let alu = |operand: Operand| match y {
+        0 | 1 | 3 => vec![a, operand],
+        _ => vec![operand],
+    };
+
+    use Operand::*;
+    let (mnemonic, operands, length): (&'static str, Vec<Operand>, u16) = match (x, z) {
+        (0, 0) => match y {
+            0 => ("nop", vec![], 1),
+            1 => ("ld", vec![Address(n16), Register("sp")], 3),
+            2 if n8 == 0 => ("stop", vec![], 2),
+            2 => ("stop", vec![Imm8(n8)], 2),
+            3 => ("jr", vec![Target(relative)], 2),
+            _ => ("jr", vec![Condition(CONDITIONS[y - 4]), Target(relative)], 2),
+        },
+        (0, 1) if q == 0 => ("ld", vec![Register(R16[p]), Imm16(n16)], 3),
+        (0, 1) => ("add", vec![Register("hl"), Register(R16[p])], 1),
+        (0, 2) if q == 0 => ("ld", vec![Indirect(R16_MEM[p]), a], 1),
+        (0, 2) => ("ld", vec![a, Indirect(R16_MEM[p])], 1),
+        (0, 3) => (if q == 0 { "inc" } else { "dec" }, vec![Register(R16[p])], 1),
+        (0, 4) => ("inc", vec![r8(y)], 1),
+        (0, 5) => ("dec", vec![r8(y)], 1),
+        (0, 6) => ("ld", vec![r8(y), Imm8(n8)], 2),
+        (0, _) => (ACCUMULATOR_OPS[y], vec![], 1),
+        (1, _) if y == 6 && z == 6 => ("halt", vec![], 1),
+        (1, _) => ("ld", vec![r8(y), r8(z)], 1),
+        (2, _) => (ALU[y], alu(r8(z)), 1),
+        (_, 0) => match y {
+            0..=3 => ("ret", vec![Condition(CONDITIONS[y])], 1),
+            4 => ("ldh", vec![HighAddress(0xFF00 | n8 as u16), a], 2),
+            5 => ("add", vec![Register("sp"), Offset(e8)], 2),
+            6 => ("ldh", vec![a, HighAddress(0xFF00 | n8 as u16)], 2),
+            _ => ("ld", vec![Register("hl"), SpOffset(e8)], 2),
+        },
+        (_, 1) if q == 0 => ("pop", vec![Register(R16_STACK[p])], 1),
+        (_, 1) => match p {
+            0 => ("ret", vec![], 1),
+            1 => ("reti", vec![], 1),
+            2 => ("jp", vec![Register("hl")], 1),
+            _ => ("ld", vec![Register("sp"), Register("hl")], 1),
+        },
+        (_, 2) => match y {
+            0..=3 => ("jp", vec![Condition(CONDITIONS[y]), Target(n16)], 3),
+            4 => ("ldh", vec![Indirect("[c]"), a], 1),
+            5 => ("ld", vec![Address(n16), a], 3),
+            6 => ("ldh", vec![a, Indirect("[c]")], 1),
+            _ => ("ld", vec![a, Address(n16)], 3),
+        },
+        (_, 3) => match y {
+            0 => ("jp", vec![Target(n16)], 3),
+            1 => return decode_cb(n8, address),
+            6 => ("di", vec![], 1),
+            7 => ("ei", vec![], 1),
+            _ => ("db", vec![Imm8(opcode)], 1),
+        },
+        (_, 4) if y < 4 => ("call", vec![Condition(CONDITIONS[y]), Target(n16)], 3),
+        (_, 5) if q == 0 => ("push", vec![Register(R16_STACK[p])], 1),
+        (_, 5) if p == 0 => ("call", vec![Target(n16)], 3),
+        (_, 6) => (ALU[y], alu(Imm8(n8)), 2),
+        (_, 7) => ("rst", vec![Vector(y as u8 * 8)], 1),
+        _ => ("db", vec![Imm8(opcode)], 1),
+    };
+    let mut encoded = [0; 3];
+    for (i, slot) in encoded.iter_mut().enumerate().take(length as usize) {
+        *slot = byte(i);
+    }
+    DecodedInstruction { address, length, bytes: encoded, mnemonic, operands }
+}
+
+fn decode_cb(opcode: u8, address: u16) -> DecodedInstruction {
+    let (x, y, z) = (opcode >> 6, (opcode >> 3 & 0x07) as usize, (opcode & 0x07) as usize);
+    let target = if z == 6 { Operand::Indirect(R8[6]) } else { Operand::Register(R8[z]) };
+    let (mnemonic, operands) = match x {
+        0 => (ROTATES[y], vec![target]),
+        1 => ("bit", vec![Operand::Bit(y as u8), target]),
+        2 => ("res", vec![Operand::Bit(y as u8), target]),
+        _ => ("set", vec![Operand::Bit(y as u8), target]),
+    };
+    DecodedInstruction { address, length: 2, bytes: [0xCB, opcode, 0], mnemonic, operands }
+}
+
+/// Decodes the instruction at `address` as seen through a bus.
+pub fn decode_bus(bus: &dyn BusTrait, address: u16) -> DecodedInstruction {
+    let bytes = [0, 1, 2].map(|i| bus.read(address.wrapping_add(i)));
+    decode(&bytes, address)
+}
+
+/// Splits a ROM file offset into its bank and CPU address.
+pub fn rom_location(offset: usize) -> (usize, u16) {
+    let bank = offset / BANK_SIZE;
+    let addr = if bank == 0 { offset } else { BANK_SIZE + offset % BANK_SIZE };
+    (bank, addr as u16)
+}
+
+/// The ROM file offset of a CPU address in 0x0000-0x7FFF when `bank` is
+/// mapped at 0x4000.
+pub fn rom_offset(bank: usize, addr: u16) -> usize {
+    if (addr as usize) < BANK_SIZE {
+        addr as usize
+    } else {
+        bank * BANK_SIZE + (addr as usize - BANK_SIZE)
+    }
+}
+
+/// The result of tracing code through a ROM image from its entry points.
+pub struct RomDisassembly<'a> {
+    rom: &'a [u8],
+    /// Decoded instructions keyed by ROM offset.
+    instructions: BTreeMap<usize, DecodedInstruction>,
+    /// For branches, the ROM offset of the destination.
+    targets: HashMap<usize, usize>,
+    /// Labels keyed by ROM offset.
+    labels: BTreeMap<usize, String>,
+    symbols: Option<&'a SymbolTable>,
+}
+
+// Register values tracked along a path, used to guess which bank a write to
+// the MBC's bank select register (0x2000-0x3FFF) switches in.
+#[derive(Clone, Copy)]
+struct TraceState {
+    bank: usize,
+    a: Option<u8>,
+    hl: Option<u16>,
+}
+
+impl<'a> RomDisassembly<'a> {
+    /// Disassembles a ROM by following control flow from the entry point
+    /// and interrupt vectors. Jumps into 0x4000-0x7FFF go to the bank most
+    /// recently selected on the path (bank 1 if none was seen), so code
+    /// reached only through computed jumps (JP HL, jump tables) is left as
+    /// data.
+    pub fn trace(rom: &'a [u8]) -> RomDisassembly<'a> {
+        let mut disassembly = RomDisassembly {
+            rom,
+            instructions: BTreeMap::new(),
+            targets: HashMap::new(),
+            labels: BTreeMap::new(),
+            symbols: None,
+        };
+        let mut queue = VecDeque::new();
+        for (addr, name) in VECTORS {
+            let padding = addr != 0x0100 && matches!(rom.get(addr as usize), Some(0x00) | Some(0xFF));
+            if (addr as usize) < rom.len() && !padding {
+                disassembly.labels.insert(addr as usize, name.to_string());
+                queue.push_back((addr as usize, TraceState { bank: 1, a: None, hl: None }));
+            }
+        }
+        while let Some((offset, state)) = queue.pop_front() {
+            disassembly.trace_block(offset, state, &mut queue);
+        }
+        disassembly
+    }
+
+    fn covered(&self, offset: usize) -> bool {
+        self.instructions.range(..=offset).next_back()
+            .is_some_and(|(&start, instruction)| offset < start + instruction.length as usize)
+    }
+
+    fn trace_block(&mut self, mut offset: usize, mut state: TraceState, queue: &mut VecDeque<(usize, TraceState)>) {
+        loop {
+            if offset >= self.rom.len() || self.covered(offset) {
+                return;
+            }
+            let (bank, addr) = rom_location(offset);
+            let instruction = decode(&self.rom[offset..], addr);
+            let end = offset + instruction.length as usize;
+            if end > self.rom.len() || (end - 1) / BANK_SIZE != bank
+                || (offset + 1..end).any(|inner| self.instructions.contains_key(&inner)) {
+                return;
+            }
+            let flow = instruction.flow();
+            Self::track_bank(&instruction, &mut state);
+            self.instructions.insert(offset, instruction);
+
+            let destination = match flow {
+                Flow::Jump(target) | Flow::ConditionalJump(target) | Flow::Call(target) if target < 0x8000 => {
+                    let target_bank = if bank != 0 { bank } else { state.bank };
+                    Some((rom_offset(target_bank, target), matches!(flow, Flow::Call(_))))
+                }
+                _ => None,
+            };
+            if let Some((target, call)) = destination {
+                if target < self.rom.len() {
+                    self.targets.insert(offset, target);
+                    let (target_bank, target_addr) = rom_location(target);
+                    let kind = if call { "Call" } else { "Jump" };
+                    let is_rst = self.rom[offset] & 0xC7 == 0xC7;
+                    if !is_rst {
+                        let label = self.labels.entry(target)
+                            .or_insert_with(|| format!("{}_{:03X}_{:04X}", kind, target_bank, target_addr));
+                        if call && label.starts_with("Jump_") {
+                            *label = label.replacen("Jump_", "Call_", 1);
+                        }
+                    }
+                    queue.push_back((target, state));
+                }
+            }
+            match flow {
+                Flow::Jump(_) | Flow::Return | Flow::IndirectJump | Flow::Invalid => return,
+                _ => offset = end,
+            }
+        }
+    }
+
+    fn track_bank(instruction: &DecodedInstruction, state: &mut TraceState) {
+        let select = |state: &mut TraceState, addr: u16, value: Option<u8>| {
+            if (0x2000..0x4000).contains(&addr) {
+                if let Some(value) = value {
+                    state.bank = (value as usize).max(1);
+                }
+            }
+        };
+        let bytes = instruction.bytes;
+        match bytes[0] {
+            0x3E => state.a = Some(bytes[1]),
+            0xAF => state.a = Some(0),
+            0x21 => state.hl = Some((bytes[2] as u16) << 8 | bytes[1] as u16),
+            0xEA => select(state, (bytes[2] as u16) << 8 | bytes[1] as u16, state.a),
+            0x77 => if let Some(hl) = state.hl { select(state, hl, state.a) },
+            0x36 => if let Some(hl) = state.hl { select(state, hl, Some(bytes[1])) },
+            // Anything else that loads A or HL loses track of its value.
+            0x0A | 0x1A | 0x2A | 0x3A | 0x3C | 0x3D | 0x2F | 0x07 | 0x0F | 0x17 | 0x1F | 0x27
+                | 0x78..=0x7F | 0x80..=0xBF | 0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6
+                | 0xF0 | 0xF1 | 0xF2 | 0xFA | 0xCB | 0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC => state.a = None,
+            _ => (),
+        }
+        if matches!(bytes[0], 0x09 | 0x19 | 0x29 | 0x39 | 0x23 | 0x2B | 0x22 | 0x2A | 0x32 | 0x3A
+            | 0x24 | 0x25 | 0x2C | 0x2D | 0x26 | 0x2E | 0x60..=0x6F | 0xE1 | 0xF8 | 0xCB | 0xCD
+            | 0xC4 | 0xCC | 0xD4 | 0xDC) {
+            state.hl = None;
+        }
+    }
+
+    /// Names labels after the ROM symbols in `symbols` instead of their
+    /// addresses, and RAM and I/O operands after the other symbols.
+    pub fn apply_symbols(&mut self, symbols: &'a SymbolTable) {
+        for (bank, addr, name) in symbols.iter() {
+            if addr >= 0x8000 || (addr < 0x4000 && bank != 0) {
+                continue;
+            }
+            let offset = rom_offset(bank as usize, addr);
+            if offset < self.rom.len() {
+                self.labels.insert(offset, rgbds_name(name));
+            }
+        }
+        self.symbols = Some(symbols);
+    }
+
+    /// The traced instructions, keyed by ROM offset.
+    pub fn instructions(&self) -> &BTreeMap<usize, DecodedInstruction> {
+        &self.instructions
+    }
+
+    /// The label at a ROM offset, if any.
+    pub fn label(&self, offset: usize) -> Option<&str> {
+        self.labels.get(&offset).map(String::as_str)
+    }
+
+    /// Writes the disassembly as RGBDS source that assembles back to the
+    /// same ROM image.
+    pub fn to_rgbds(&self) -> String {
+        let mut out = String::new();
+        // Symbols outside ROM are not defined by the listing itself.
+        if let Some(symbols) = self.symbols {
+            let mut defined = false;
+            for (_, addr, name) in symbols.iter().filter(|&(_, addr, _)| addr >= 0x8000) {
+                if symbols.name_at(addr, 0) == Some(name) {
+                    writeln!(out, "DEF {} EQU ${:04X}", rgbds_name(name), addr).unwrap();
+                    defined = true;
+                }
+            }
+            if defined {
+                out.push('\n');
+            }
+        }
+        let mut offset = 0;
+        while offset < self.rom.len() {
+            let (bank, _) = rom_location(offset);
+            if offset % BANK_SIZE == 0 {
+                if bank == 0 {
+                    writeln!(out, "SECTION \"ROM Bank $000\", ROM0[$0000]").unwrap();
+                } else {
+                    writeln!(out, "\nSECTION \"ROM Bank ${:03X}\", ROMX[$4000], BANK[${:X}]", bank, bank).unwrap();
+                }
+            }
+            if let Some(label) = self.labels.get(&offset) {
+                writeln!(out, "\n{}:", label).unwrap();
+            }
+            if let Some(instruction) = self.instructions.get(&offset) {
+                // A label that ended up inside another instruction is never
+                // written out, so branches to it keep the numeric address.
+                let target = self.targets.get(&offset)
+                    .filter(|&&target| self.instructions.contains_key(&target) || !self.covered(target))
+                    .and_then(|target| self.labels.get(target));
+                let target_addr = match instruction.flow() {
+                    Flow::Jump(addr) | Flow::ConditionalJump(addr) | Flow::Call(addr) => Some(addr),
+                    _ => None,
+                };
+                let name = |addr: u16| {
+                    if Some(addr) == target_addr {
+                        target.cloned()
+                    } else if addr >= 0x8000 {
+                        self.symbols.and_then(|symbols| symbols.name_at(addr, 0)).map(rgbds_name)
+                    } else {
+                        None
+                    }
+                };
+                writeln!(out, "    {}", instruction.format_with(Syntax::Rgbds, &name)).unwrap();
+                offset += instruction.length as usize;
+            } else {
+                offset = self.write_data(&mut out, offset);
+            }
+        }
+        out
+    }
+
+    // Writes the bytes from `start` up to the next instruction, label or
+    // bank boundary as data, and returns where it stopped.
+    fn write_data(&self, out: &mut String, start: usize) -> usize {
+        let bank_end = (start / BANK_SIZE + 1) * BANK_SIZE;
+        let mut end = start + 1;
+        while end < self.rom.len().min(bank_end)
+            && !self.instructions.contains_key(&end) && !self.labels.contains_key(&end) {
+            end += 1;
+        }
+        let mut offset = start;
+        while offset < end {
+            let value = self.rom[offset];
+            let run = self.rom[offset..end].iter().take_while(|&&byte| byte == value).count();
+            if run >= 16 {
+                writeln!(out, "    ds {}, ${:02X}", run, value).unwrap();
+                offset += run;
+                continue;
+            }
+            // Collect up to 8 bytes, stopping before the next long run.
+            let mut line_end = offset;
+            while line_end < end && line_end - offset < 8 {
+                let byte = self.rom[line_end];
+                if self.rom[line_end..end].iter().take_while(|&&b| b == byte).count() >= 16 && line_end > offset {
+                    break;
+                }
+                line_end += 1;
+            }
+            let bytes: Vec<String> = self.rom[offset..line_end].iter().map(|&byte| hex8(byte)).collect();
+            writeln!(out, "    db {}", bytes.join(", ")).unwrap();
+            offset = line_end;
+        }
+        end
+    }
+}
+
+// RGBDS only accepts a qualified local label (`Parent.local`) inside its
+// parent's scope, so dots are flattened to keep the output assembling.
+fn rgbds_name(name: &str) -> String {
+    name.replace('.', "_")
+}
+
+/// Disassembles `count` instructions linearly from `address`.
+pub fn disassemble_range(bus: &dyn BusTrait, address: u16, count: usize) -> Vec<DecodedInstruction> {
+    let mut addr = address;
+    (0..count).map(|_| {
+        let instruction = decode_bus(bus, addr);
+        addr = addr.wrapping_add(instruction.length);
+        instruction
+    }).collect()
+}
@@ -1,29 +1,514 @@
-use crate::Cartridge;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::deflate::crc32;
+use crate::disasm::{self, DecodedInstruction, Syntax};
+use crate::movie::{self, Movie, Playback, HASH_INTERVAL};
+use crate::rewind::RewindBuffer;
+use crate::savestate::{StateHeader, StateReader, StateWriter};
 use crate::Bus;
+use crate::Button;
 use crate::Sharp8080;
+use crate::SymbolTable;
+use crate::Tracer;
+
+// The DMG master clock runs at 4.194304 MHz and a frame is 154 lines of 456
+// dots, which works out to roughly 59.7275 frames per second.
+pub const CLOCK_SPEED: u32 = 4_194_304;
+pub const CYCLES_PER_FRAME: u32 = 70224;
+pub const FRAME_RATE: f64 = CLOCK_SPEED as f64 / CYCLES_PER_FRAME as f64;
+
+const BOOT_ROM_SIZE: usize = 256;
+
+/// The hardware revisions the emulator can model. They differ in the
+/// register values their boot ROMs leave behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    Dmg,
+    Mgb,
+    Sgb,
+    Sgb2,
+}
+
+impl Model {
+    pub fn from_name(name: &str) -> Option<Model> {
+        match name.to_ascii_lowercase().as_str() {
+            "dmg" => Some(Model::Dmg),
+            "mgb" => Some(Model::Mgb),
+            "sgb" => Some(Model::Sgb),
+            "sgb2" => Some(Model::Sgb2),
+            _ => None,
+        }
+    }
 
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Dmg => "dmg",
+            Model::Mgb => "mgb",
+            Model::Sgb => "sgb",
+            Model::Sgb2 => "sgb2",
+        }
+    }
+
+    fn post_boot_cpu(&self) -> Sharp8080 {
+        match self {
+            Model::Dmg => Sharp8080::post_boot(0x01, 0xB0, 0x0013, 0x00D8, 0x014D),
+            Model::Mgb => Sharp8080::post_boot(0xFF, 0xB0, 0x0013, 0x00D8, 0x014D),
+            Model::Sgb => Sharp8080::post_boot(0x01, 0x00, 0x0014, 0x0000, 0xC060),
+            Model::Sgb2 => Sharp8080::post_boot(0xFF, 0x00, 0x0014, 0x0000, 0xC060),
+        }
+    }
+}
+
+/// A complete DMG system: CPU, bus and cartridge.
 pub struct GameBoy {
     cpu: Sharp8080,
     bus: Bus,
+    model: Model,
+    realtime: bool,
+    next_frame: Option<Instant>,
+    symbols: SymbolTable,
+    trace: bool,
+    tracer: Option<Tracer>,
+    rewind: Option<RewindBuffer>,
+    boot_rom_crc: Option<u32>,
+    recording: Option<Movie>,
+    playback: Option<(Movie, Playback)>,
 }
 
 impl GameBoy {
+    /// Creates a DMG in the state the boot ROM hands over to the cartridge
+    /// at 0x0100.
     pub fn power_on() -> GameBoy {
-        GameBoy { cpu: Sharp8080::new(0x0100), bus: Bus::new() }
+        GameBoy::power_on_model(Model::Dmg)
     }
 
-    pub fn load_game(&mut self, path: String) {
-       self.bus.cartridge.load_cartridge(&path); 
+    /// Creates the given model in the state its boot ROM hands over to the
+    /// cartridge at 0x0100.
+    pub fn power_on_model(model: Model) -> GameBoy {
+        GameBoy { cpu: model.post_boot_cpu(), bus: Bus::new(), model, realtime: false,
+            next_frame: None, symbols: SymbolTable::new(), trace: false,
+            tracer: None, rewind: None, boot_rom_crc: None, recording: None, playback: None }
     }
 
-    pub fn load_buffer(&mut self, buffer: &Vec<u8>) {
-        self.bus.cartridge.load_cartridge_w_buffer(buffer);
+    pub fn model(&self) -> Model {
+        self.model
     }
 
+    /// Loads a ROM image from disk into the cartridge slot and returns its
+    /// size, or 0 on failure.
+    pub fn load_game(&mut self, path: String) -> usize {
+       self.bus.cartridge.load_cartridge(&path)
+    }
+
+    /// Loads a ROM image already held in memory into the cartridge slot and
+    /// returns its size, or 0 on failure.
+    pub fn load_buffer(&mut self, buffer: &[u8]) -> usize {
+        self.bus.cartridge.load_cartridge_w_buffer(buffer)
+    }
+
+    /// Maps a 256-byte boot ROM at 0x0000 and restarts the CPU there, so
+    /// the boot sequence runs before the cartridge.
+    pub fn load_boot_rom(&mut self, path: &String) -> bool {
+        let boot_rom = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) => {
+                println!("GameBoy::load_boot_rom - Could not read boot ROM at path {}. Error: {}", path, err);
+                return false;
+            }
+        };
+        if boot_rom.len() != BOOT_ROM_SIZE {
+            println!("GameBoy::load_boot_rom - Boot ROM must be {} bytes, got {}.", BOOT_ROM_SIZE, boot_rom.len());
+            return false;
+        }
+        self.boot_rom_crc = Some(crc32(&boot_rom));
+        self.bus.map_boot_rom(boot_rom);
+        self.bus.ppu.write(0xFF40, 0x00);
+        self.cpu = Sharp8080::new(0x0000);
+        true
+    }
+
+    pub fn cpu(&self) -> &Sharp8080 {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut Sharp8080 {
+        &mut self.cpu
+    }
+
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        &mut self.bus
+    }
+
+    /// Symbols used to name addresses in trace output and the debugger.
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = symbols;
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// The ROM bank the CPU sees at `addr`: the switchable bank for
+    /// 0x4000-0x7FFF, otherwise 0.
+    pub fn bank_at(&self, addr: u16) -> u16 {
+        if (0x4000..0x8000).contains(&addr) { self.bus.cartridge.rom_bank() as u16 } else { 0 }
+    }
+
+    /// Prints each instruction before it is executed, disassembled and
+    /// labelled with symbol names.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Decodes the instruction at `addr` without triggering watchpoints.
+    pub fn decode(&self, addr: u16) -> DecodedInstruction {
+        let bytes = [0, 1, 2].map(|i| self.bus.peek(addr.wrapping_add(i)));
+        disasm::decode(&bytes, addr)
+    }
+
+    /// Disassembles the instruction at `addr`, naming addresses with the
+    /// loaded symbols.
+    pub fn disassemble(&self, addr: u16) -> String {
+        let rom_bank = self.bus.cartridge.rom_bank() as u16;
+        let name = |target: u16| self.symbols.name_at(target, rom_bank).map(str::to_string);
+        self.decode(addr).format_with(Syntax::Classic, &name)
+    }
+
+    /// Logs every instruction in the Gameboy Doctor format. If writing the
+    /// log fails, tracing stops.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    pub fn tracer_mut(&mut self) -> Option<&mut Tracer> {
+        self.tracer.as_mut()
+    }
+
+    fn log_instruction(&mut self) {
+        let pc = self.cpu.pc();
+        let bank = self.bank_at(pc);
+        let tracer = match self.tracer.as_mut() {
+            Some(tracer) if tracer.wants(pc, bank) => tracer,
+            _ => return,
+        };
+        let pcmem = [0, 1, 2, 3].map(|i| self.bus.peek(pc.wrapping_add(i)));
+        if let Err(err) = tracer.trace(&self.cpu.registers(), pcmem) {
+            println!("GameBoy::log_instruction - Could not write trace. Error: {}", err);
+            self.tracer = None;
+        }
+    }
+
+    fn trace_instruction(&self) {
+        let pc = self.cpu.pc();
+        let bank = self.bank_at(pc);
+        if let Some(name) = self.symbols.name_at(pc, bank) {
+            println!("{}:", name);
+        }
+        println!("  {:02X}:{:04X}  {}", bank, pc, self.disassemble(pc));
+    }
+
+    /// Snapshots the whole machine: CPU, memory, cartridge banking and RAM,
+    /// PPU, APU and I/O. The state records the ROM it belongs to.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        StateHeader::new(self.bus.cartridge.rom_crc()).write(&mut state);
+        state.section(b"CPU ", |state| {
+            let registers = self.cpu.registers();
+            for register in [registers.a, registers.f, registers.b, registers.c, registers.d, registers.e,
+                registers.h, registers.l] {
+                state.u8(register);
+            }
+            state.u16(registers.sp);
+            state.u16(registers.pc);
+            state.bool(registers.ime);
+        });
+        self.bus.save_state(&mut state);
+        state.finish()
+    }
+
+    /// Restores a state made by [`GameBoy::save_state`] for the loaded ROM.
+    /// On error the machine is left as it was.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut state = StateReader::new(data);
+        let header = StateHeader::read(&mut state)?;
+        if header.rom_crc != self.bus.cartridge.rom_crc() {
+            return Err("save state was made with a different ROM".to_string());
+        }
+        let backup = self.save_state();
+        let result = self.load_sections(&mut state);
+        if result.is_err() {
+            let mut backup = StateReader::new(&backup);
+            StateHeader::read(&mut backup).and_then(|_| self.load_sections(&mut backup))
+                .expect("restoring a state just saved");
+        }
+        result
+    }
+
+    fn load_sections(&mut self, state: &mut StateReader) -> Result<(), String> {
+        let mut cpu = false;
+        while !state.is_empty() {
+            let (tag, mut section) = state.section()?;
+            if &tag == b"CPU " {
+                let mut registers = self.cpu.registers();
+                for register in [&mut registers.a, &mut registers.f, &mut registers.b, &mut registers.c,
+                    &mut registers.d, &mut registers.e, &mut registers.h, &mut registers.l] {
+                    *register = section.u8()?;
+                }
+                registers.sp = section.u16()?;
+                registers.pc = section.u16()?;
+                registers.ime = section.bool()?;
+                self.cpu.set_registers(&registers);
+                cpu = true;
+            } else {
+                // Sections from newer versions are skipped.
+                self.bus.load_section(&tag, &mut section)?;
+            }
+        }
+        if !cpu {
+            return Err("save state has no CPU state".to_string());
+        }
+        Ok(())
+    }
+
+    /// Keeps a snapshot of every frame for the last `seconds` seconds, using
+    /// at most about `budget` bytes, so that [`GameBoy::rewind`] can step
+    /// back.
+    pub fn enable_rewind(&mut self, seconds: f64, budget: usize) {
+        self.rewind = Some(RewindBuffer::new((seconds * FRAME_RATE).ceil() as u64, budget));
+    }
+
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    pub fn rewind_buffer(&self) -> Option<&RewindBuffer> {
+        self.rewind.as_ref()
+    }
+
+    /// Steps back `frames` frames, or as far as the rewind buffer reaches,
+    /// to the state at the end of a frame. Returns how many frames back it
+    /// went.
+    pub fn rewind(&mut self, frames: u64) -> Result<u64, String> {
+        let buffer = self.rewind.as_mut().ok_or("rewind is not enabled")?;
+        let (state, back) = buffer.rewind(frames).ok_or("no frames to rewind to")?;
+        self.load_state(&state)?;
+        // Rewinding while recording takes the rewound frames back out of the
+        // movie, so a run can be retried from an earlier point.
+        if let Some(recording) = self.recording.as_mut() {
+            let frames = (recording.inputs.len() as u64).saturating_sub(back);
+            recording.inputs.truncate(frames as usize);
+            recording.hashes.retain(|&(frame, _)| frame <= frames);
+        }
+        if let Some((movie, playback)) = self.playback.as_mut() {
+            playback.frame = playback.frame.saturating_sub(back);
+            if let Some(&pressed) = movie.inputs.get(playback.frame as usize) {
+                self.bus.set_buttons(pressed);
+            }
+        }
+        Ok(back)
+    }
+
+    /// Starts recording the buttons held on every frame into a movie. With
+    /// `from_state` the movie starts from the machine's current state;
+    /// otherwise it is expected to be played back from power-on.
+    pub fn start_recording(&mut self, from_state: bool) {
+        let state = self.save_state();
+        let mut movie = Movie::new(self.bus.cartridge.rom_crc(), self.model, self.boot_rom_crc,
+            from_state.then(|| state.clone()));
+        movie.hashes.push((0, movie::state_hash(&state)));
+        self.recording = Some(movie);
+    }
+
+    pub fn recording(&self) -> Option<&Movie> {
+        self.recording.as_ref()
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        self.recording.take()
+    }
+
+    /// Starts replaying `movie`, whose inputs then replace the buttons set
+    /// through the API until it ends. A movie without a start state must be
+    /// played on a machine that has only just been powered on. Fails if the
+    /// ROM, model or boot ROM differ from those it was recorded with.
+    pub fn start_playback(&mut self, movie: Movie) -> Result<(), String> {
+        if movie.rom_crc != self.bus.cartridge.rom_crc() {
+            return Err("movie was recorded with a different ROM".to_string());
+        }
+        if movie.model != self.model {
+            return Err(format!("movie was recorded on the {} model", movie.model.name()));
+        }
+        if movie.boot_rom_crc != self.boot_rom_crc {
+            return Err(match movie.boot_rom_crc {
+                Some(_) if self.boot_rom_crc.is_some() => "movie was recorded with a different boot ROM",
+                Some(_) => "movie was recorded with a boot ROM",
+                None => "movie was recorded without a boot ROM",
+            }.to_string());
+        }
+        if let Some(state) = &movie.start_state {
+            self.load_state(state)?;
+        }
+        let mut playback = Playback { frame: 0, frames: movie.inputs.len() as u64, desync: None };
+        if movie.hash_at(0).is_some_and(|hash| hash != movie::state_hash(&self.save_state())) {
+            playback.desync = Some(0);
+        }
+        if let Some(&pressed) = movie.inputs.first() {
+            self.bus.set_buttons(pressed);
+        }
+        self.playback = Some((movie, playback));
+        Ok(())
+    }
+
+    /// Progress through the movie being played back, if any.
+    pub fn playback(&self) -> Option<Playback> {
+        self.playback.as_ref().map(|&(_, playback)| playback)
+    }
+
+    pub fn stop_playback(&mut self) -> Option<Playback> {
+        self.playback.take().map(|(_, playback)| playback)
+    }
+
+    // Records or replays the frame that has just finished.
+    fn movie_frame(&mut self) {
+        if let Some(mut recording) = self.recording.take() {
+            recording.inputs.push(self.buttons());
+            let frame = recording.inputs.len() as u64;
+            if frame.is_multiple_of(HASH_INTERVAL) {
+                recording.hashes.push((frame, movie::state_hash(&self.save_state())));
+            }
+            self.recording = Some(recording);
+        }
+        if let Some((movie, mut playback)) = self.playback.take() {
+            if !playback.finished() {
+                playback.frame += 1;
+                let desynced = movie.hash_at(playback.frame)
+                    .is_some_and(|hash| hash != movie::state_hash(&self.save_state()));
+                if desynced && playback.desync.is_none() {
+                    playback.desync = Some(playback.frame);
+                }
+                if let Some(&pressed) = movie.inputs.get(playback.frame as usize) {
+                    self.bus.set_buttons(pressed);
+                }
+            }
+            self.playback = Some((movie, playback));
+        }
+    }
+
+    pub fn press(&mut self, button: Button) {
+        let pressed = self.bus.joypad.buttons() | button as u8;
+        self.bus.set_buttons(pressed);
+    }
+
+    pub fn release(&mut self, button: Button) {
+        let pressed = self.bus.joypad.buttons() & !(button as u8);
+        self.bus.set_buttons(pressed);
+    }
+
+    /// Sets every button at once from a mask of [`Button`] values.
+    pub fn set_buttons(&mut self, pressed: u8) {
+        self.bus.set_buttons(pressed);
+    }
+
+    pub fn buttons(&self) -> u8 {
+        self.bus.joypad.buttons()
+    }
+
+    /// When enabled, [`GameBoy::run_frame`] sleeps so that frames are
+    /// produced at [`FRAME_RATE`] instead of as fast as the host allows.
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.realtime = realtime;
+        self.next_frame = None;
+    }
+
+    /// Executes a single instruction and returns the T-cycles it took.
+    pub fn step_instruction(&mut self) -> u32 {
+        if self.trace {
+            self.trace_instruction();
+        }
+        if self.tracer.is_some() {
+            self.log_instruction();
+        }
+        let opcode = self.cpu.fetch_opcode(&self.bus);
+        let cycles = self.cpu.execute(&mut self.bus, opcode) as u32;
+        self.bus.tick(cycles);
+        cycles
+    }
+
+    /// Executes whole instructions until at least `cycles` T-cycles have
+    /// elapsed and returns the number actually run.
+    pub fn run_for_cycles(&mut self, cycles: u32) -> u32 {
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed += self.step_instruction();
+        }
+        elapsed
+    }
+
+    /// Executes instructions until the PPU enters VBlank. With the LCD
+    /// switched off there is no VBlank, so a frame's worth of cycles is run
+    /// instead. Returns the number of T-cycles run.
+    pub fn run_frame(&mut self) -> u32 {
+        self.run_frame_until(|_| false).0
+    }
+
+    /// Like [`GameBoy::run_frame`], but calls `stop` before each
+    /// instruction and returns early, before executing it, once `stop`
+    /// returns true. Returns the T-cycles run and whether it stopped early;
+    /// calling it again carries on with the same frame.
+    pub fn run_frame_until<F: FnMut(&mut GameBoy) -> bool>(&mut self, mut stop: F) -> (u32, bool) {
+        let frame = self.bus.ppu.frame_count();
+        let mut elapsed = 0;
+        while self.bus.ppu.frame_count() == frame {
+            if !self.bus.ppu.lcd_enabled() && elapsed >= CYCLES_PER_FRAME {
+                break;
+            }
+            if stop(self) {
+                return (elapsed, true);
+            }
+            elapsed += self.step_instruction();
+        }
+        self.bus.apply_cheats();
+        self.movie_frame();
+        if let Some(mut rewind) = self.rewind.take() {
+            rewind.push(self.save_state());
+            self.rewind = Some(rewind);
+        }
+        if self.realtime {
+            self.pace();
+        }
+        (elapsed, false)
+    }
+
+    /// Runs frames in real time forever.
     pub fn run(&mut self) {
+        self.set_realtime(true);
         loop {
-            let opcode = self.cpu.fetch_opcode(&self.bus);
-            self.cpu.execute(&mut self.bus, opcode);
+            self.run_frame();
+        }
+    }
+
+    fn pace(&mut self) {
+        let frame_time = Duration::from_secs_f64(1.0 / FRAME_RATE);
+        let now = Instant::now();
+        let deadline = match self.next_frame {
+            Some(deadline) => deadline,
+            None => now,
+        };
+        if deadline > now {
+            thread::sleep(deadline - now);
+            self.next_frame = Some(deadline + frame_time);
+        } else if now - deadline > frame_time {
+            // Too far behind (e.g. the host was suspended); resynchronise
+            // rather than running a burst of frames to catch up.
+            self.next_frame = Some(now + frame_time);
+        } else {
+            self.next_frame = Some(deadline + frame_time);
         }
     }
 }
@@ -0,0 +1,509 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::bus::{Access, WatchHit, Watchpoint};
+use crate::{GameBoy, Registers};
+
+/// The register layout reported to GDB: the four register pairs, SP and
+/// PC, each 16 bits and sent little-endian, in the same order as GDB's own
+/// z80 target.
+pub const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.z80.cpu">
+    <reg name="af" bitsize="16" type="int" regnum="0"/>
+    <reg name="bc" bitsize="16" type="data_ptr"/>
+    <reg name="de" bitsize="16" type="data_ptr"/>
+    <reg name="hl" bitsize="16" type="data_ptr"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+const REGISTER_COUNT: usize = 6;
+const SP: usize = 4;
+const PC: usize = 5;
+const PACKET_SIZE: usize = 0x4000;
+// The byte GDB sends to interrupt a running target.
+const INTERRUPT: u8 = 0x03;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BreakpointKind {
+    Software,
+    Hardware,
+}
+
+// Why the target stopped, as reported in a stop reply packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    Step,
+    Interrupted,
+    Breakpoint(BreakpointKind),
+    Watchpoint(WatchHit),
+    Undefined,
+}
+
+/// How a debugging session ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    /// GDB detached, leaving the target to run on.
+    Detached,
+    /// GDB killed the target.
+    Killed,
+    /// The connection closed.
+    Disconnected,
+}
+
+enum Session {
+    Continue,
+    End(End),
+}
+
+/// A GDB remote serial protocol server for one connected debugger.
+///
+/// Software breakpoints are not patched into memory, since most code runs
+/// from ROM; they behave exactly like hardware breakpoints. Watchpoints are
+/// set on the bus.
+pub struct GdbStub {
+    stream: TcpStream,
+    breakpoints: Vec<(u16, BreakpointKind)>,
+    no_ack: bool,
+}
+
+/// Waits for GDB to connect on `addr` and serves it until the session
+/// ends. This blocks, so the emulator does not run until GDB attaches.
+pub fn serve<A: ToSocketAddrs>(gb: &mut GameBoy, addr: A) -> io::Result<End> {
+    let listener = TcpListener::bind(addr)?;
+    println!("gdb: waiting for a connection on {}", listener.local_addr()?);
+    let (stream, peer) = listener.accept()?;
+    println!("gdb: connected to {}", peer);
+    GdbStub::new(stream)?.run(gb)
+}
+
+impl GdbStub {
+    pub fn new(stream: TcpStream) -> io::Result<GdbStub> {
+        stream.set_nodelay(true)?;
+        Ok(GdbStub { stream, breakpoints: vec![], no_ack: false })
+    }
+
+    /// Handles packets until the debugger detaches, kills the target or
+    /// disconnects. The target is halted while no continue is in progress.
+    pub fn run(&mut self, gb: &mut GameBoy) -> io::Result<End> {
+        gb.cpu_mut().set_trap_undefined(true);
+        let result = self.session(gb);
+        self.breakpoints.clear();
+        gb.cpu_mut().set_trap_undefined(false);
+        result
+    }
+
+    fn session(&mut self, gb: &mut GameBoy) -> io::Result<End> {
+        while let Some(packet) = self.read_packet()? {
+            if let Session::End(end) = self.handle(gb, &packet)? {
+                return Ok(end);
+            }
+        }
+        Ok(End::Disconnected)
+    }
+
+    fn handle(&mut self, gb: &mut GameBoy, packet: &[u8]) -> io::Result<Session> {
+        let text = String::from_utf8_lossy(packet).into_owned();
+        let reply = match packet.first() {
+            Some(b'?') => stop_reply(gb, Stop::Step),
+            Some(b'g') => read_registers(gb),
+            Some(b'G') => {
+                match decode_hex(&text[1..]) {
+                    Some(bytes) if bytes.len() >= REGISTER_COUNT * 2 => {
+                        for register in 0..REGISTER_COUNT {
+                            let value = u16::from_le_bytes([bytes[register * 2], bytes[register * 2 + 1]]);
+                            set_register(gb, register, value);
+                        }
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            Some(b'p') => match usize::from_str_radix(&text[1..], 16) {
+                Ok(register) if register < REGISTER_COUNT => encode_hex(&register_value(gb, register).to_le_bytes()),
+                _ => "E01".to_string(),
+            },
+            Some(b'P') => {
+                let value = text[1..].split_once('=')
+                    .and_then(|(register, value)| Some((usize::from_str_radix(register, 16).ok()?, decode_hex(value)?)));
+                match value {
+                    Some((register, bytes)) if register < REGISTER_COUNT && bytes.len() == 2 => {
+                        set_register(gb, register, u16::from_le_bytes([bytes[0], bytes[1]]));
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            // The hex reply has to fit in one packet.
+            Some(b'm') => match parse_range(&text[1..]) {
+                Some((addr, len)) if len <= PACKET_SIZE / 2 => {
+                    let bytes: Vec<u8> = (0..len).map(|i| gb.bus().peek(addr.wrapping_add(i as u16))).collect();
+                    encode_hex(&bytes)
+                }
+                _ => "E01".to_string(),
+            },
+            Some(b'M') => {
+                let write = text[1..].split_once(':')
+                    .and_then(|(range, data)| Some((parse_range(range)?, decode_hex(data)?)));
+                match write {
+                    Some(((addr, len), bytes)) if bytes.len() == len => {
+                        write_memory(gb, addr, &bytes);
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            Some(b'X') => {
+                // Binary write; the data is everything after the colon.
+                let colon = packet.iter().position(|&byte| byte == b':');
+                let range = colon.and_then(|colon| parse_range(&String::from_utf8_lossy(&packet[1..colon])));
+                match (colon, range) {
+                    (Some(colon), Some((addr, len))) if packet.len() - colon - 1 == len => {
+                        write_memory(gb, addr, &packet[colon + 1..]);
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            Some(b'Z') | Some(b'z') => self.breakpoint(gb, &text),
+            Some(b's') => {
+                if let Some(addr) = resume_address(&text[1..]) {
+                    set_register(gb, PC, addr);
+                }
+                let stop = self.step(gb);
+                stop_reply(gb, stop)
+            }
+            Some(b'c') => {
+                if let Some(addr) = resume_address(&text[1..]) {
+                    set_register(gb, PC, addr);
+                }
+                match self.resume(gb)? {
+                    Some(stop) => stop_reply(gb, stop),
+                    None => return Ok(Session::End(End::Disconnected)),
+                }
+            }
+            Some(b'D') => {
+                self.send_packet(b"OK")?;
+                return Ok(Session::End(End::Detached));
+            }
+            Some(b'k') => return Ok(Session::End(End::Killed)),
+            Some(b'H') => "OK".to_string(),
+            _ => match text.as_str() {
+                "vCont?" => "vCont;c;C;s;S".to_string(),
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                "QStartNoAckMode" => {
+                    self.send_packet(b"OK")?;
+                    self.no_ack = true;
+                    return Ok(Session::Continue);
+                }
+                _ if text.starts_with("qSupported") => format!(
+                    "PacketSize={:x};qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+", PACKET_SIZE),
+                _ if text.starts_with("qXfer:features:read:target.xml:") => {
+                    features_chunk(&text["qXfer:features:read:target.xml:".len()..])
+                }
+                _ if text.starts_with("vCont;") => {
+                    // There is a single thread, so only the first action
+                    // matters.
+                    let action = text["vCont;".len()..].split(';').next().unwrap_or("");
+                    match action.as_bytes().first() {
+                        Some(b's') | Some(b'S') => {
+                            let stop = self.step(gb);
+                            stop_reply(gb, stop)
+                        }
+                        Some(b'c') | Some(b'C') => match self.resume(gb)? {
+                            Some(stop) => stop_reply(gb, stop),
+                            None => return Ok(Session::End(End::Disconnected)),
+                        },
+                        _ => "E01".to_string(),
+                    }
+                }
+                // An empty reply tells GDB the packet is not supported.
+                _ => String::new(),
+            },
+        };
+        self.send_packet(reply.as_bytes())?;
+        Ok(Session::Continue)
+    }
+
+    fn breakpoint(&mut self, gb: &mut GameBoy, text: &str) -> String {
+        let insert = text.starts_with('Z');
+        let mut fields = text[1..].split(',');
+        let kind = fields.next().unwrap_or("");
+        let addr = fields.next().and_then(|addr| u32::from_str_radix(addr, 16).ok());
+        let len = fields.next().and_then(|len| usize::from_str_radix(len, 16).ok()).unwrap_or(1);
+        // Watchpoints are set a byte at a time, and one per byte of the
+        // address space is as many as can differ.
+        let addr = match addr {
+            Some(addr) if len <= 0x10000 => addr as u16,
+            _ => return "E01".to_string(),
+        };
+        let (read, write) = match kind {
+            "0" | "1" => {
+                let kind = if kind == "0" { BreakpointKind::Software } else { BreakpointKind::Hardware };
+                if insert {
+                    self.breakpoints.push((addr, kind));
+                } else if let Some(index) = self.breakpoints.iter().position(|&breakpoint| breakpoint == (addr, kind)) {
+                    self.breakpoints.remove(index);
+                }
+                return "OK".to_string();
+            }
+            "2" => (false, true),
+            "3" => (true, false),
+            "4" => (true, true),
+            _ => return String::new(),
+        };
+        for i in 0..len.max(1) {
+            let watchpoint = Watchpoint { addr: addr.wrapping_add(i as u16), read, write };
+            if insert {
+                gb.bus_mut().add_watchpoint(watchpoint);
+            } else if let Some(index) = gb.bus().watchpoints().iter().position(|&other| other == watchpoint) {
+                gb.bus_mut().remove_watchpoint(index);
+            }
+        }
+        "OK".to_string()
+    }
+
+    fn step(&mut self, gb: &mut GameBoy) -> Stop {
+        gb.cpu_mut().clear_undefined_opcode();
+        gb.step_instruction();
+        if let Some(hit) = gb.bus_mut().take_watch_hit() {
+            return Stop::Watchpoint(hit);
+        }
+        if gb.cpu().undefined_opcode().is_some() {
+            return Stop::Undefined;
+        }
+        Stop::Step
+    }
+
+    // Runs until something stops the target, checking for an interrupt
+    // from GDB after every frame. Returns None if GDB disconnected.
+    fn resume(&mut self, gb: &mut GameBoy) -> io::Result<Option<Stop>> {
+        gb.cpu_mut().clear_undefined_opcode();
+        gb.bus_mut().take_watch_hit();
+        let mut resuming = true;
+        loop {
+            let mut stop = None;
+            let breakpoints = &self.breakpoints;
+            gb.run_frame_until(|gb| {
+                stop = check(gb, breakpoints, std::mem::take(&mut resuming));
+                stop.is_some()
+            });
+            if stop.is_some() {
+                return Ok(stop);
+            }
+            match self.poll_interrupt()? {
+                Some(true) => return Ok(Some(Stop::Interrupted)),
+                Some(false) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    // Returns whether GDB sent an interrupt, or None if it disconnected.
+    fn poll_interrupt(&mut self) -> io::Result<Option<bool>> {
+        self.stream.set_nonblocking(true)?;
+        let mut buffer = [0; 64];
+        let result = self.stream.read(&mut buffer);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Ok(None),
+            Ok(len) => Ok(Some(buffer[..len].contains(&INTERRUPT))),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(Some(false)),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        loop {
+            match self.stream.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Reads the next packet, skipping acknowledgements and stray interrupts.
+    // Returns None once the connection closes.
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                Some(_) => continue,
+            }
+            let mut packet = vec![];
+            let mut sum: u8 = 0;
+            loop {
+                let byte = match self.read_byte()? {
+                    Some(byte) => byte,
+                    None => return Ok(None),
+                };
+                if byte == b'#' {
+                    break;
+                }
+                sum = sum.wrapping_add(byte);
+                if byte == b'}' {
+                    let escaped = match self.read_byte()? {
+                        Some(byte) => byte,
+                        None => return Ok(None),
+                    };
+                    sum = sum.wrapping_add(escaped);
+                    packet.push(escaped ^ 0x20);
+                } else {
+                    packet.push(byte);
+                }
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum)?;
+            let valid = decode_hex(&String::from_utf8_lossy(&checksum)).is_some_and(|checksum| checksum[0] == sum);
+            if !self.no_ack {
+                self.stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid || self.no_ack {
+                return Ok(Some(packet));
+            }
+        }
+    }
+
+    fn send_packet(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        for &byte in data {
+            if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+                packet.extend_from_slice(&[b'}', byte ^ 0x20]);
+            } else {
+                packet.push(byte);
+            }
+        }
+        let sum = packet[1..].iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        packet.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+        loop {
+            self.stream.write_all(&packet)?;
+            if self.no_ack {
+                return Ok(());
+            }
+            match self.read_byte()? {
+                Some(b'-') => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+fn check(gb: &mut GameBoy, breakpoints: &[(u16, BreakpointKind)], resuming: bool) -> Option<Stop> {
+    if let Some(hit) = gb.bus_mut().take_watch_hit() {
+        return Some(Stop::Watchpoint(hit));
+    }
+    if gb.cpu().undefined_opcode().is_some() {
+        return Some(Stop::Undefined);
+    }
+    if resuming {
+        return None;
+    }
+    let pc = gb.cpu().pc();
+    breakpoints.iter().find(|&&(addr, _)| addr == pc).map(|&(_, kind)| Stop::Breakpoint(kind))
+}
+
+fn stop_reply(gb: &GameBoy, stop: Stop) -> String {
+    match stop {
+        Stop::Step => format!("S{:02x}", SIGTRAP),
+        Stop::Interrupted => format!("S{:02x}", SIGINT),
+        Stop::Undefined => format!("S{:02x}", SIGILL),
+        Stop::Breakpoint(BreakpointKind::Software) => format!("T{:02x}swbreak:;", SIGTRAP),
+        Stop::Breakpoint(BreakpointKind::Hardware) => format!("T{:02x}hwbreak:;", SIGTRAP),
+        Stop::Watchpoint(hit) => {
+            let access = gb.bus().watchpoints().iter().any(|watchpoint| watchpoint.addr == hit.addr
+                && watchpoint.read && watchpoint.write);
+            let kind = match hit.access {
+                _ if access => "awatch",
+                Access::Read => "rwatch",
+                Access::Write => "watch",
+            };
+            format!("T{:02x}{}:{:04x};", SIGTRAP, kind, hit.addr)
+        }
+    }
+}
+
+fn register_value(gb: &GameBoy, register: usize) -> u16 {
+    let cpu = gb.cpu();
+    match register {
+        0 => cpu.af(),
+        1 => cpu.bc(),
+        2 => cpu.de(),
+        3 => cpu.hl(),
+        SP => cpu.sp(),
+        _ => cpu.pc(),
+    }
+}
+
+fn set_register(gb: &mut GameBoy, register: usize, value: u16) {
+    let cpu = gb.cpu_mut();
+    match register {
+        0 => cpu.set_af(value),
+        1 => cpu.set_bc(value),
+        2 => cpu.set_de(value),
+        3 => cpu.set_hl(value),
+        SP => cpu.set_registers(&Registers { sp: value, ..cpu.registers() }),
+        _ => cpu.set_registers(&Registers { pc: value, ..cpu.registers() }),
+    }
+}
+
+fn read_registers(gb: &GameBoy) -> String {
+    let bytes: Vec<u8> = (0..REGISTER_COUNT).flat_map(|register| register_value(gb, register).to_le_bytes()).collect();
+    encode_hex(&bytes)
+}
+
+fn write_memory(gb: &mut GameBoy, addr: u16, bytes: &[u8]) {
+    for (i, &byte) in bytes.iter().enumerate() {
+        gb.bus_mut().poke(addr.wrapping_add(i as u16), byte);
+    }
+}
+
+// GDB may send addresses wider than 16 bits; only the low 16 are used.
+fn parse_range(text: &str) -> Option<(u16, usize)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((u32::from_str_radix(addr, 16).ok()? as u16, usize::from_str_radix(len, 16).ok()?))
+}
+
+fn resume_address(text: &str) -> Option<u16> {
+    u32::from_str_radix(text, 16).ok().map(|addr| addr as u16)
+}
+
+// Answers a qXfer read of `offset,length` from the target description.
+fn features_chunk(range: &str) -> String {
+    let (offset, len) = match range.split_once(',')
+        .and_then(|(offset, len)| Some((usize::from_str_radix(offset, 16).ok()?, usize::from_str_radix(len, 16).ok()?))) {
+        Some(range) => range,
+        None => return "E01".to_string(),
+    };
+    let xml = TARGET_XML.as_bytes();
+    let start = offset.min(xml.len());
+    let end = start.saturating_add(len).min(xml.len());
+    let marker = if end == xml.len() { 'l' } else { 'm' };
+    format!("{}{}", marker, String::from_utf8_lossy(&xml[start..end]))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
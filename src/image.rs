@@ -0,0 +1,214 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::deflate::{crc32, zlib_compress, zlib_decompress};
+use crate::ppu::{LCD_HEIGHT, LCD_WIDTH};
+
+#[cfg(test)]
+mod test;
+
+// RGB values for the four DMG shades, lightest first.
+pub const DMG_PALETTE: [[u8; 3]; 4] = [
+    [0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55],
+    [0x00, 0x00, 0x00],
+];
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    /// Picks the format from a file extension, defaulting to PNG.
+    pub fn from_path(path: &Path) -> ImageFormat {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(ImageFormat::from_name)
+            .unwrap_or(ImageFormat::Png)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// Converts a PPU framebuffer of shades into packed RGB bytes.
+pub fn framebuffer_to_rgb(framebuffer: &[u8]) -> Vec<u8> {
+    framebuffer.iter().flat_map(|&shade| DMG_PALETTE[(shade & 0x03) as usize]).collect()
+}
+
+/// Encodes packed RGB pixels as a binary (P6) PPM image.
+pub fn encode_ppm(width: usize, height: usize, rgb: &[u8]) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    out.extend_from_slice(rgb);
+    out
+}
+
+fn write_png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+/// Encodes packed RGB pixels as an 8-bit truecolour PNG image.
+pub fn encode_png(width: usize, height: usize, rgb: &[u8]) -> Vec<u8> {
+    let mut out = PNG_SIGNATURE.to_vec();
+    let mut ihdr = vec![];
+    ihdr.extend((width as u32).to_be_bytes());
+    ihdr.extend((height as u32).to_be_bytes());
+    // Bit depth 8, colour type 2 (RGB), default compression, filter and
+    // no interlacing.
+    ihdr.extend([8, 2, 0, 0, 0]);
+    write_png_chunk(&mut out, b"IHDR", &ihdr);
+
+    // Every scanline is prefixed with filter type 0 (None).
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    for row in rgb.chunks(width * 3).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    write_png_chunk(&mut out, b"IDAT", &zlib_compress(&raw));
+    write_png_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Writes packed RGB pixels to an image file of the given format.
+pub fn write_rgb(path: &Path, format: ImageFormat, width: usize, height: usize, rgb: &[u8]) -> std::io::Result<()> {
+    let data = match format {
+        ImageFormat::Ppm => encode_ppm(width, height, rgb),
+        ImageFormat::Png => encode_png(width, height, rgb),
+    };
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&data)?;
+    out.flush()
+}
+
+/// Writes a PPU framebuffer to an image file of the given format.
+pub fn write_frame(path: &Path, format: ImageFormat, framebuffer: &[u8]) -> std::io::Result<()> {
+    write_rgb(path, format, LCD_WIDTH, LCD_HEIGHT, &framebuffer_to_rgb(framebuffer))
+}
+
+/// A decoded image as packed RGB pixels.
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+}
+
+/// Decodes a non-interlaced PNG image to packed RGB. Alpha is discarded.
+pub fn decode_png(data: &[u8]) -> Result<RgbImage, String> {
+    if data.len() < 8 || data[..8] != PNG_SIGNATURE {
+        return Err("not a PNG file".to_string());
+    }
+    let mut pos = 8;
+    let mut ihdr = None;
+    let mut palette: Vec<u8> = vec![];
+    let mut idat = vec![];
+    while pos + 12 <= data.len() {
+        let len = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let kind = &data[pos + 4..pos + 8];
+        let body = data.get(pos + 8..pos + 8 + len).ok_or("truncated PNG chunk")?;
+        match kind {
+            b"IHDR" => ihdr = Some(body.to_vec()),
+            b"PLTE" => palette = body.to_vec(),
+            b"IDAT" => idat.extend_from_slice(body),
+            b"IEND" => break,
+            _ => (),
+        }
+        pos += 12 + len;
+    }
+    let ihdr = ihdr.ok_or("missing IHDR chunk")?;
+    if ihdr.len() < 13 {
+        return Err("truncated IHDR chunk".to_string());
+    }
+    let width = u32::from_be_bytes(ihdr[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(ihdr[4..8].try_into().unwrap()) as usize;
+    let (depth, color_type, interlace) = (ihdr[8], ihdr[9], ihdr[12]);
+    if interlace != 0 {
+        return Err("interlaced PNGs are not supported".to_string());
+    }
+    let channels = match (color_type, depth) {
+        (0, 8) => 1,
+        (2, 8) => 3,
+        (3, 1) | (3, 2) | (3, 4) | (3, 8) => 1,
+        (4, 8) => 2,
+        (6, 8) => 4,
+        _ => return Err(format!("unsupported PNG colour type {} at depth {}", color_type, depth)),
+    };
+    let bits_per_pixel = channels * depth as usize;
+    let stride = (width * bits_per_pixel).div_ceil(8);
+    let bpp = bits_per_pixel.div_ceil(8);
+    let size = height.checked_mul(stride + 1).ok_or("PNG image is too large")?;
+    let raw = zlib_decompress(&idat, size)?;
+    if raw.len() < size {
+        return Err("not enough image data".to_string());
+    }
+
+    let mut pixels = vec![0u8; height * stride];
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        for x in 0..stride {
+            let a = if x >= bpp { pixels[y * stride + x - bpp] } else { 0 };
+            let b = if y > 0 { pixels[(y - 1) * stride + x] } else { 0 };
+            let c = if x >= bpp && y > 0 { pixels[(y - 1) * stride + x - bpp] } else { 0 };
+            pixels[y * stride + x] = line[x].wrapping_add(match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("invalid PNG filter {}", filter)),
+            });
+        }
+    }
+
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        let row = &pixels[y * stride..(y + 1) * stride];
+        for x in 0..width {
+            match color_type {
+                0 => rgb.extend([row[x]; 3]),
+                2 => rgb.extend_from_slice(&row[x * 3..x * 3 + 3]),
+                3 => {
+                    let per_byte = 8 / depth as usize;
+                    let shift = 8 - depth as usize * (x % per_byte + 1);
+                    let index = (row[x / per_byte] >> shift) as usize & ((1 << depth) - 1);
+                    let entry = palette.get(index * 3..index * 3 + 3).ok_or("palette index out of range")?;
+                    rgb.extend_from_slice(entry);
+                }
+                4 => rgb.extend([row[x * 2]; 3]),
+                _ => rgb.extend_from_slice(&row[x * 4..x * 4 + 3]),
+            }
+        }
+    }
+    Ok(RgbImage { width, height, rgb })
+}
@@ -0,0 +1,39 @@
+use super::*;
+
+fn chunk_at(png: &[u8], pos: usize) -> (&[u8], &[u8], u32) {
+    let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+    let crc = u32::from_be_bytes(png[pos + 8 + len..pos + 12 + len].try_into().unwrap());
+    (&png[pos + 4..pos + 8], &png[pos + 8..pos + 8 + len], crc)
+}
+
+#[test]
+fn png_structure() {
+    let rgb = [0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x10, 0x20, 0x30];
+    let png = encode_png(2, 2, &rgb);
+    assert_eq!(png[..8], PNG_SIGNATURE);
+
+    let (kind, ihdr, crc) = chunk_at(&png, 8);
+    assert_eq!(kind, b"IHDR");
+    assert_eq!(ihdr, [0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+    assert_eq!(crc, crc32(&png[12..12 + 4 + 13]));
+
+    let (kind, idat, crc) = chunk_at(&png, 8 + 25);
+    assert_eq!(kind, b"IDAT");
+    assert_eq!(crc, crc32(&png[8 + 25 + 4..8 + 25 + 8 + idat.len()]));
+    // Each row is filter type 0 followed by its pixels.
+    assert_eq!(zlib_decompress(idat, 14).unwrap(), [
+        0, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00,
+        0, 0x00, 0x00, 0xFF, 0x10, 0x20, 0x30,
+    ]);
+
+    assert_eq!(png[png.len() - 12..], [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+
+    let image = decode_png(&png).unwrap();
+    assert_eq!((image.width, image.height, image.rgb), (2, 2, rgb.to_vec()));
+}
+
+#[test]
+fn ppm_and_framebuffer() {
+    assert_eq!(encode_ppm(1, 1, &[1, 2, 3]), b"P6\n1 1\n255\n\x01\x02\x03");
+    assert_eq!(framebuffer_to_rgb(&[0, 3, 1]), [0xFF, 0xFF, 0xFF, 0, 0, 0, 0xAA, 0xAA, 0xAA]);
+}
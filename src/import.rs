@@ -0,0 +1,147 @@
+// Importers for movies recorded by other emulators: BizHawk's .bk2 and
+// VisualBoyAdvance's .vbm. Only the per-frame input is carried over, so an
+// imported movie has no state hashes to detect desync with, and it only
+// stays in sync as far as this emulator's timing matches the original's.
+
+use std::fs;
+use std::path::Path;
+
+use crate::archive::ZipArchive;
+use crate::deflate::crc32;
+use crate::gameboy::Model;
+use crate::movie::Movie;
+use crate::Button;
+
+/// Loads a movie in any supported format, picked by file extension, for
+/// playback with `rom`.
+pub fn load(path: &Path, rom: &[u8]) -> Result<Movie, String> {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_ascii_lowercase();
+    let import = match extension.as_str() {
+        "bk2" => import_bk2,
+        "vbm" => import_vbm,
+        _ => return Movie::load(path),
+    };
+    let data = fs::read(path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+    import(&data, rom).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+// Maps a BizHawk input column name, with or without a player prefix, to a
+// button. Columns such as Power are not buttons.
+fn bk2_button(name: &str) -> Option<Button> {
+    let name = name.strip_prefix("P1 ").unwrap_or(name);
+    match name {
+        "Up" | "Down" | "Left" | "Right" | "Start" | "Select" | "A" | "B" => Button::from_name(name),
+        _ => None,
+    }
+}
+
+/// Converts a BizHawk .bk2 movie, a zip archive holding a text header and
+/// an input log with one line of button columns per frame. The ROM's hash
+/// in the header is not checked; the movie is tied to `rom`.
+pub fn import_bk2(data: &[u8], rom: &[u8]) -> Result<Movie, String> {
+    let archive = ZipArchive::parse(data)?;
+    let text = |name: &str| -> Result<String, String> {
+        let entry = archive.find(name).ok_or(format!("movie has no {}", name))?;
+        Ok(String::from_utf8_lossy(&archive.read(entry)?).into_owned())
+    };
+
+    let mut model = Model::Dmg;
+    for line in text("Header.txt")?.lines() {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match (key, value.trim()) {
+            ("StartsFromSavestate", "True") => {
+                return Err("movies that start from a save state are not supported".to_string());
+            }
+            ("StartsFromSaveRam", "True") => {
+                return Err("movies that start from save RAM are not supported".to_string());
+            }
+            ("Platform", "GBC") | ("IsCGBMode", "True") => {
+                return Err("Game Boy Color movies are not supported".to_string());
+            }
+            ("Platform", "SGB") => model = Model::Sgb,
+            ("Platform", "GB" | "") => {}
+            ("Platform", platform) => return Err(format!("not a Game Boy movie (platform {})", platform)),
+            _ => {}
+        }
+    }
+
+    let log = text("Input Log.txt")?;
+    let mut columns = None;
+    let mut inputs = vec![];
+    for line in log.lines().map(str::trim) {
+        if let Some(key) = line.strip_prefix("LogKey:") {
+            // Groups are separated by '#' and columns by '|'.
+            let names = key.split(['#', '|']).filter(|name| !name.is_empty());
+            columns = Some(names.map(bk2_button).collect::<Vec<_>>());
+        } else if line.starts_with('|') {
+            let columns = columns.as_ref().ok_or("input log has no LogKey")?;
+            let mut pressed = 0;
+            for (button, state) in columns.iter().zip(line.chars().filter(|&c| c != '|')) {
+                if let Some(button) = button.filter(|_| state != '.' && state != ' ') {
+                    pressed |= button as u8;
+                }
+            }
+            inputs.push(pressed);
+        }
+    }
+    if columns.is_none() {
+        return Err("input log has no LogKey".to_string());
+    }
+    let mut movie = Movie::new(crc32(rom), model, None, None);
+    movie.inputs = inputs;
+    Ok(movie)
+}
+
+// The VBM controller bits, lowest first.
+const VBM_BUTTONS: [Button; 8] = [
+    Button::A, Button::B, Button::Select, Button::Start,
+    Button::Right, Button::Left, Button::Up, Button::Down,
+];
+
+/// Converts a VisualBoyAdvance .vbm movie: a 64-byte header followed by a
+/// little-endian u16 of buttons per enabled controller per frame. Only
+/// movies from power-on without embedded save RAM are supported, and the
+/// ROM's header checksums must match those recorded in the movie.
+pub fn import_vbm(data: &[u8], rom: &[u8]) -> Result<Movie, String> {
+    if data.len() < 0x40 || &data[0..4] != b"VBM\x1A" {
+        return Err("not a VBM movie".to_string());
+    }
+    let u32_at = |pos: usize| u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+    if u32_at(0x04) != 1 {
+        return Err(format!("unsupported VBM version {}", u32_at(0x04)));
+    }
+    let frames = u32_at(0x0C) as usize;
+    match data[0x14] & 0x03 {
+        0 => {}
+        0x01 => return Err("movies that start from a save state are not supported".to_string()),
+        _ => return Err("movies that start from save RAM are not supported".to_string()),
+    }
+    let controllers = (data[0x15] & 0x0F).count_ones() as usize;
+    if data[0x15] & 0x01 == 0 {
+        return Err("movie does not use controller 1".to_string());
+    }
+    let model = match data[0x16] & 0x07 {
+        0 => Model::Dmg,
+        0x04 => Model::Sgb,
+        flags if flags & 0x01 != 0 => return Err("Game Boy Advance movies are not supported".to_string()),
+        _ => return Err("Game Boy Color movies are not supported".to_string()),
+    };
+    // VBA records the header checksum byte and the two global checksum
+    // bytes as they appear in the ROM.
+    if rom.len() < 0x150 || data[0x31] != rom[0x14D] || data[0x32..0x34] != rom[0x14E..0x150] {
+        return Err("movie was recorded with a different ROM".to_string());
+    }
+
+    let start = u32_at(0x3C) as usize;
+    let stride = controllers * 2;
+    let input = start.checked_add(frames * stride).and_then(|end| data.get(start..end))
+        .ok_or("movie input is truncated")?;
+    let inputs = input.chunks(stride).map(|frame| {
+        let bits = u16::from_le_bytes([frame[0], frame[1]]);
+        VBM_BUTTONS.iter().enumerate().filter(|&(bit, _)| bits & 1 << bit != 0)
+            .fold(0, |pressed, (_, &button)| pressed | button as u8)
+    }).collect();
+    let mut movie = Movie::new(crc32(rom), model, None, None);
+    movie.inputs = inputs;
+    Ok(movie)
+}
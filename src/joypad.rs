@@ -0,0 +1,105 @@
+use crate::savestate::{StateReader, StateWriter};
+
+pub const INT_JOYPAD: u8 = 0x10;
+
+/// The eight DMG buttons. The value of each is its bit in the state passed
+/// to Joypad::set_buttons: the d-pad in the low nibble, the action buttons
+/// in the high nibble.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Right = 0x01,
+    Left = 0x02,
+    Up = 0x04,
+    Down = 0x08,
+    A = 0x10,
+    B = 0x20,
+    Select = 0x40,
+    Start = 0x80,
+}
+
+pub const BUTTONS: [Button; 8] = [
+    Button::Right, Button::Left, Button::Up, Button::Down,
+    Button::A, Button::B, Button::Select, Button::Start,
+];
+
+impl Button {
+    pub fn from_name(name: &str) -> Option<Button> {
+        BUTTONS.iter().copied().find(|button| button.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Button::Right => "right",
+            Button::Left => "left",
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::A => "a",
+            Button::B => "b",
+            Button::Select => "select",
+            Button::Start => "start",
+        }
+    }
+}
+
+/// The P1/JOYP register at 0xFF00.
+pub struct Joypad {
+    select: u8,
+    pressed: u8,
+}
+
+impl Default for Joypad {
+    fn default() -> Joypad {
+        Joypad::new()
+    }
+}
+
+impl Joypad {
+    pub fn new() -> Joypad {
+        Joypad { select: 0x30, pressed: 0 }
+    }
+
+    /// Currently pressed buttons as a mask of Button values.
+    pub fn buttons(&self) -> u8 {
+        self.pressed
+    }
+
+    /// Replaces the pressed buttons and returns the joypad interrupt flag if
+    /// a selected line went from high to low.
+    pub fn set_buttons(&mut self, pressed: u8) -> u8 {
+        let before = self.lines();
+        self.pressed = pressed;
+        let after = self.lines();
+        if before & !after != 0 { INT_JOYPAD } else { 0 }
+    }
+
+    // Low nibble of P1, active low.
+    fn lines(&self) -> u8 {
+        let mut lines = 0x0F;
+        if self.select & 0x10 == 0 {
+            lines &= !(self.pressed & 0x0F);
+        }
+        if self.select & 0x20 == 0 {
+            lines &= !(self.pressed >> 4);
+        }
+        lines
+    }
+
+    pub fn read(&self) -> u8 {
+        0xC0 | self.select | self.lines()
+    }
+
+    pub fn write(&mut self, data: u8) {
+        self.select = data & 0x30;
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.select);
+        state.u8(self.pressed);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.select = state.u8()?;
+        self.pressed = state.u8()?;
+        Ok(())
+    }
+}
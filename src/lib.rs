@@ -0,0 +1,43 @@
+//! A Game Boy (DMG) emulator.
+//!
+//! [`GameBoy`] ties the [`Sharp8080`] CPU to a [`Bus`] holding the
+//! [`Cartridge`] and the rest of the memory-mapped hardware. Embedders
+//! drive it with [`GameBoy::run_frame`], [`GameBoy::run_for_cycles`] or
+//! [`GameBoy::step_instruction`]; anything implementing [`BusTrait`] can be
+//! used to run the CPU against a custom memory map.
+pub mod apu;
+pub mod archive;
+pub mod bus;
+pub mod cartridge;
+pub mod catalogue;
+pub mod cheats;
+pub mod debugger;
+pub mod deflate;
+pub mod disasm;
+pub mod gameboy;
+pub mod gdb;
+pub mod image;
+pub mod import;
+pub mod joypad;
+pub mod movie;
+pub mod patch;
+pub mod ppu;
+pub mod rewind;
+pub mod savestate;
+pub mod serial;
+pub mod sharp8080;
+pub mod symbols;
+pub mod tracediff;
+pub mod tracer;
+pub mod wav;
+
+pub use apu::Apu;
+pub use bus::{Bus, BusTrait};
+pub use cartridge::{Cartridge, CartridgeType, Header};
+pub use gameboy::{GameBoy, Model};
+pub use joypad::{Button, Joypad};
+pub use ppu::Ppu;
+pub use serial::Serial;
+pub use sharp8080::{Registers, Sharp8080};
+pub use symbols::SymbolTable;
+pub use tracer::Tracer;
@@ -1,14 +1,429 @@
-mod bus;
-mod cartridge;
-mod sharp8080;
-mod gameboy;
-use bus::*;
-use cartridge::*;
-use sharp8080::*;
-use gameboy::*;
+mod cli;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use gbemu::apu::SAMPLE_RATE;
+use gbemu::archive;
+use gbemu::cartridge::cartridge_type_name;
+use gbemu::catalogue;
+use gbemu::cheats::{self, Cheat};
+use gbemu::debugger::{self, Action, Debugger};
+use gbemu::disasm::RomDisassembly;
+use gbemu::gdb;
+use gbemu::import;
+use gbemu::tracediff::{self, Comparison};
+use gbemu::image::{write_frame, ImageFormat};
+use gbemu::patch::{apply_patch, PATCH_EXTENSIONS};
+use gbemu::wav::WavWriter;
+use gbemu::{GameBoy, Header, SymbolTable, Tracer};
+
+// How often, in frames, save files, screenshots and audio are flushed to
+// disk while running.
+const FLUSH_INTERVAL: u64 = 60;
+
+// Prints the header of the ROM as it would be loaded: unpacked from its
+// archive and patched.
+fn print_info(path: &str, entry: Option<&str>, patch: Option<&Path>) -> Result<(), String> {
+    let data = fs::read(path).map_err(|err| format!("could not read {}: {}", path, err))?;
+    let mut rom = archive::unpack_rom(&data, entry).map_err(|err| format!("{}: {}", path, err))?;
+    if let Some(patch) = patch {
+        let data = fs::read(patch).map_err(|err| format!("could not read {}: {}", patch.display(), err))?;
+        rom = apply_patch(&rom, &data).map_err(|err| format!("{}: {}", patch.display(), err))?.into();
+        println!("Patch:           {}", patch.display());
+    }
+    let header = Header::parse(&rom).ok_or(format!("{} is too small to be a ROM", path))?;
+    let check = |ok: bool| if ok { "ok" } else { "MISMATCH" };
+    let header_checksum = Header::compute_header_checksum(&rom);
+    let global_checksum = Header::compute_global_checksum(&rom);
+    println!("Title:           {}", header.title);
+    println!("Cartridge type:  {:#04x} ({})", header.cartridge_type, cartridge_type_name(header.cartridge_type));
+    println!("ROM size:        {} KiB (header {:#04x}, file {} KiB)",
+        header.rom_bytes() / 1024, header.rom_size, rom.len() / 1024);
+    println!("RAM size:        {} KiB (header {:#04x})", header.ram_bytes() / 1024, header.ram_size);
+    println!("CGB flag:        {:#04x}", header.cgb_flag);
+    println!("SGB flag:        {:#04x}", header.sgb_flag);
+    println!("Destination:     {}", if header.destination == 0 { "Japan" } else { "Overseas" });
+    if header.old_licensee == 0x33 {
+        println!("Licensee:        {}", String::from_utf8_lossy(&header.new_licensee));
+    } else {
+        println!("Licensee:        {:#04x}", header.old_licensee);
+    }
+    println!("Version:         {}", header.version);
+    println!("Header checksum: {:#04x} ({})", header.header_checksum,
+        check(header.header_checksum == header_checksum));
+    println!("Global checksum: {:#06x} ({})", header.global_checksum,
+        check(header.global_checksum == global_checksum));
+    Ok(())
+}
+
+// Loads the symbol file given on the command line, or the one next to the
+// ROM if there is one.
+fn load_symbols(rom: &str, path: &Option<PathBuf>) -> Result<SymbolTable, String> {
+    match path {
+        Some(path) => SymbolTable::load(path),
+        None => {
+            let path = SymbolTable::path_for_rom(Path::new(rom));
+            if path.exists() { SymbolTable::load(&path) } else { Ok(SymbolTable::new()) }
+        }
+    }
+}
+
+// Loads the cheat file given on the command line, or the one next to the
+// ROM if there is one, followed by any codes given individually.
+fn load_cheats(gb: &mut GameBoy, options: &cli::Options) -> Result<(), String> {
+    let mut loaded = match &options.cheats {
+        Some(path) => cheats::load_cheats(path)?,
+        None => {
+            let path = cheats::path_for_rom(Path::new(&options.rom));
+            if path.exists() { cheats::load_cheats(&path)? } else { vec![] }
+        }
+    };
+    for code in &options.cheat_codes {
+        loaded.push(Cheat::parse(code)?);
+    }
+    for cheat in loaded {
+        gb.bus_mut().add_cheat(cheat);
+    }
+    Ok(())
+}
+
+// The patch given on the command line or, with --auto-patch, the first
+// patch found next to the ROM.
+fn patch_path(options: &cli::Options) -> Option<PathBuf> {
+    if options.patch.is_some() || !options.auto_patch {
+        return options.patch.clone();
+    }
+    PATCH_EXTENSIONS.iter().map(|extension| Path::new(&options.rom).with_extension(extension))
+        .find(|path| path.exists())
+}
+
+fn disassemble(options: cli::DisasmOptions) -> Result<(), String> {
+    let data = fs::read(&options.rom).map_err(|err| format!("could not read {}: {}", options.rom, err))?;
+    let rom = archive::unpack_rom(&data, None).map_err(|err| format!("{}: {}", options.rom, err))?;
+    let symbols = load_symbols(&options.rom, &options.symbols)?;
+    let mut disassembly = RomDisassembly::trace(&rom);
+    disassembly.apply_symbols(&symbols);
+    let source = disassembly.to_rgbds();
+    match &options.output {
+        Some(path) => fs::write(path, source)
+            .map_err(|err| format!("could not write {}: {}", path.display(), err)),
+        None => io::stdout().write_all(source.as_bytes())
+            .map_err(|err| format!("could not write disassembly: {}", err)),
+    }
+}
+
+fn scan(options: cli::ScanOptions) -> Result<(), String> {
+    let results = catalogue::scan(&options.dir)?;
+    let text = catalogue::write(&results, options.format);
+    match &options.output {
+        Some(path) => fs::write(path, text).map_err(|err| format!("could not write {}: {}", path.display(), err))?,
+        None => io::stdout().write_all(text.as_bytes()).map_err(|err| format!("could not write catalogue: {}", err))?,
+    }
+    // The summary goes to stderr so it stays out of a catalogue on stdout.
+    let roms: Vec<_> = results.iter().filter_map(|(_, info)| info.as_ref().ok()).collect();
+    let supported = roms.iter().filter(|info| info.supported).count();
+    eprintln!("Scanned {} files: {} ROMs, {} supported", results.len(), roms.len(), supported);
+    Ok(())
+}
+
+// Makes Ctrl-C break into the debugger instead of killing the process.
+#[cfg(unix)]
+fn catch_interrupt() {
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+    extern "C" fn on_interrupt(_: i32) {
+        debugger::interrupt();
+    }
+    const SIGINT: i32 = 2;
+    unsafe {
+        signal(SIGINT, on_interrupt);
+    }
+}
+
+#[cfg(not(unix))]
+fn catch_interrupt() {}
+
+fn trace_diff(options: cli::TraceDiffOptions) -> Result<(), String> {
+    let mut gb = GameBoy::power_on_model(options.model);
+    if gb.load_game(options.rom.clone()) == 0 {
+        return Err(format!("could not load ROM {}", options.rom));
+    }
+    let file = fs::File::open(&options.reference)
+        .map_err(|err| format!("could not read {}: {}", options.reference.display(), err))?;
+    let divergence = match tracediff::compare(&mut gb, io::BufReader::new(file), options.context, options.writes)? {
+        Comparison::Matched(lines) => {
+            println!("All {} lines match", lines);
+            return Ok(());
+        }
+        Comparison::Diverged(divergence) => divergence,
+    };
+    println!("Diverged at line {}:", divergence.line);
+    let first = divergence.line - divergence.context.len();
+    for (i, line) in divergence.context.iter().enumerate() {
+        println!("  {:>8}  {}", first + i, line);
+    }
+    println!("- {:>8}  {}", divergence.line, divergence.expected);
+    println!("+ {:>8}  {}", divergence.line, divergence.actual);
+    println!();
+    for (name, expected, actual) in &divergence.fields {
+        println!("{:<6} expected {:<12} got {}", name, expected, actual);
+    }
+    if let Some(opcode) = gb.cpu().undefined_opcode() {
+        println!("The CPU locked up on undefined opcode {:02X}", opcode);
+    }
+    if !divergence.writes.is_empty() {
+        println!();
+        println!("Recent memory writes:");
+        for write in &divergence.writes {
+            println!("  {:04X}  {}  [{:04X}] <- {:02X}", write.pc, gb.disassemble(write.pc), write.addr, write.value);
+        }
+    }
+    Err(format!("trace diverged at line {}", divergence.line))
+}
+
+// The file next to the ROM, or in `save_dir`, with the ROM's name and the
+// given extension.
+fn save_path(rom: &str, save_dir: &Option<PathBuf>, extension: &str) -> PathBuf {
+    let rom = Path::new(rom);
+    let dir = match save_dir {
+        Some(dir) => dir.clone(),
+        None => rom.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    dir.join(rom.file_stem().unwrap_or_default()).with_extension(extension)
+}
+
+struct FrameDump {
+    dir: PathBuf,
+    every: u64,
+    last: Option<u64>,
+    format: ImageFormat,
+}
+
+impl FrameDump {
+    // `frame` counts from 1.
+    fn wants(&self, frame: u64) -> bool {
+        match self.last {
+            Some(last) => frame == last,
+            None => frame.is_multiple_of(self.every),
+        }
+    }
+
+    fn dump(&self, gb: &GameBoy, frame: u64) -> Result<(), String> {
+        let path = self.dir.join(format!("frame_{:06}.{}", frame, self.format.extension()));
+        write_frame(&path, self.format, gb.bus().ppu.framebuffer())
+            .map_err(|err| format!("could not write frame {}: {}", path.display(), err))
+    }
+}
+
+struct Outputs {
+    save: Option<PathBuf>,
+    screenshot: Option<PathBuf>,
+    audio: Option<WavWriter>,
+    dump: Option<FrameDump>,
+}
+
+impl Outputs {
+    fn collect_audio(&mut self, gb: &mut GameBoy) {
+        if let Some(wav) = self.audio.as_mut() {
+            if let Err(err) = wav.write_samples(&gb.bus_mut().apu.take_samples()) {
+                eprintln!("error: could not write audio: {}", err);
+                self.audio = None;
+            }
+        }
+    }
+
+    fn flush(&mut self, gb: &mut GameBoy) {
+        if let Some(save) = &self.save {
+            gb.bus_mut().cartridge.save_ram(save);
+        }
+        if let Some(path) = &self.screenshot {
+            let format = ImageFormat::from_path(path);
+            if let Err(err) = write_frame(path, format, gb.bus().ppu.framebuffer()) {
+                eprintln!("error: could not write screenshot {}: {}", path.display(), err);
+            }
+        }
+        if let Some(wav) = self.audio.as_mut() {
+            if let Err(err) = wav.flush() {
+                eprintln!("error: could not write audio: {}", err);
+            }
+        }
+        if let Some(tracer) = gb.tracer_mut() {
+            if let Err(err) = tracer.flush() {
+                eprintln!("error: could not write trace: {}", err);
+            }
+        }
+    }
+}
+
+fn run(options: cli::Options) -> Result<(), String> {
+    if options.info {
+        return print_info(&options.rom, options.zip_entry.as_deref(), patch_path(&options).as_deref());
+    }
+    let mut gb = GameBoy::power_on_model(options.model);
+    gb.bus_mut().cartridge.set_archive_entry(options.zip_entry.clone());
+    if let Some(path) = patch_path(&options) {
+        let patch = fs::read(&path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        gb.bus_mut().cartridge.set_patch(Some(patch));
+    }
+    if gb.load_game(options.rom.clone()) == 0 {
+        return Err(format!("could not load ROM {}", options.rom));
+    }
+    if let Some(boot_rom) = &options.boot_rom {
+        if !gb.load_boot_rom(boot_rom) {
+            return Err(format!("could not load boot ROM {}", boot_rom));
+        }
+    }
+    gb.set_symbols(load_symbols(&options.rom, &options.symbols)?);
+    load_cheats(&mut gb, &options)?;
+    gb.set_trace(options.trace);
+    if let Some(path) = &options.trace_log {
+        let mut tracer = Tracer::create(path)
+            .map_err(|err| format!("could not create {}: {}", path.display(), err))?;
+        tracer.set_pc_range(options.trace_pc.clone());
+        tracer.set_bank(options.trace_bank);
+        gb.set_tracer(Some(tracer));
+    }
+
+    let movie = match &options.play {
+        Some(path) => Some(import::load(path, gb.bus().cartridge.rom())?),
+        None => None,
+    };
+    // A movie from power-on must start with the same cartridge RAM on every
+    // run, so the battery save is left alone while recording or playing one.
+    let from_power_on = match &movie {
+        Some(movie) => movie.start_state.is_none(),
+        None => options.record.is_some() && options.load_state.is_none(),
+    };
+    let save = if from_power_on {
+        None
+    } else {
+        let save = save_path(&options.rom, &options.save_dir, "sav");
+        gb.bus_mut().cartridge.load_ram(&save);
+        Some(save)
+    };
+    if let Some(slot) = options.load_state {
+        let path = save_path(&options.rom, &options.save_dir, &format!("ss{}", slot));
+        let state = fs::read(&path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        gb.load_state(&state).map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+    if let Some(movie) = movie {
+        let path = options.play.as_ref().unwrap();
+        gb.start_playback(movie).map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+    if options.record.is_some() {
+        gb.start_recording(options.load_state.is_some());
+    }
+    let audio = match &options.audio {
+        Some(path) => {
+            gb.bus_mut().apu.set_capture(true);
+            Some(WavWriter::create(path, SAMPLE_RATE, 2)
+                .map_err(|err| format!("could not create {}: {}", path.display(), err))?)
+        }
+        None => None,
+    };
+    let dump = match &options.dump_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("could not create {}: {}", dir.display(), err))?;
+            let last = if options.dump_last { options.frames } else { None };
+            Some(FrameDump { dir: dir.clone(), every: options.dump_every, last, format: options.dump_format })
+        }
+        None => None,
+    };
+    let mut outputs = Outputs { save, screenshot: options.screenshot.clone(), audio, dump };
+
+    if let Some(seconds) = options.rewind {
+        gb.enable_rewind(seconds, options.rewind_budget * 1024 * 1024);
+    }
+    let mut debugger = if options.debug {
+        let debugger = Debugger::new();
+        debugger::route_interrupts(&debugger);
+        catch_interrupt();
+        gb.cpu_mut().set_trap_undefined(true);
+        Some(debugger)
+    } else {
+        None
+    };
+
+    gb.set_realtime(options.frames.is_none());
+    if let Some(port) = options.gdb {
+        let end = gdb::serve(&mut gb, ("127.0.0.1", port)).map_err(|err| format!("gdb: {}", err))?;
+        if end != gdb::End::Detached {
+            outputs.flush(&mut gb);
+            return Ok(());
+        }
+    }
+    let mut frame = 0;
+    let mut warned = false;
+    while options.frames.is_none_or(|frames| frame < frames) {
+        if let Some(playback) = gb.playback() {
+            if let Some(desync) = playback.desync.filter(|_| !warned) {
+                warned = true;
+                eprintln!("warning: movie desynced at frame {}", desync);
+            }
+            if playback.finished() && options.frames.is_none() {
+                break;
+            }
+        }
+        if let Some(debugger) = debugger.as_mut() {
+            let (_, stopped) = gb.run_frame_until(|gb| debugger.check(gb));
+            if stopped {
+                let action = debugger.prompt(&mut gb, &mut io::stdin().lock(), &mut io::stdout())
+                    .map_err(|err| format!("debugger: {}", err))?;
+                if action == Action::Quit {
+                    break;
+                }
+                // Don't try to catch up on the time spent at the prompt.
+                gb.set_realtime(options.frames.is_none());
+                continue;
+            }
+        } else {
+            gb.run_frame();
+        }
+        frame += 1;
+        outputs.collect_audio(&mut gb);
+        if let Some(dump) = outputs.dump.as_ref().filter(|dump| dump.wants(frame)) {
+            dump.dump(&gb, frame)?;
+        }
+        if frame.is_multiple_of(FLUSH_INTERVAL) {
+            outputs.flush(&mut gb);
+        }
+    }
+    outputs.flush(&mut gb);
+    if let (Some(path), Some(movie)) = (&options.record, gb.stop_recording()) {
+        movie.save(path)?;
+    }
+    if let Some(slot) = options.save_state {
+        let path = save_path(&options.rom, &options.save_dir, &format!("ss{}", slot));
+        fs::write(&path, gb.save_state()).map_err(|err| format!("could not write {}: {}", path.display(), err))?;
+    }
+    Ok(())
+}
 
 fn main() {
-    let mut gb = GameBoy::power_on();
-    gb.load_game("roms/hello.gb".to_string());
-    gb.run();
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    let result = match command {
+        cli::Command::Run(options) => run(*options),
+        cli::Command::Disasm(options) => disassemble(options),
+        cli::Command::TraceDiff(options) => trace_diff(options),
+        cli::Command::Scan(options) => scan(options),
+        cli::Command::Help(usage) => {
+            println!("{}", usage);
+            Ok(())
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
 }
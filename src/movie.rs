@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::Path;
+
+use crate::deflate::crc32;
+use crate::gameboy::Model;
+use crate::savestate::{StateReader, StateWriter};
+
+pub const MAGIC: &[u8; 8] = b"GBEMUMOV";
+pub const VERSION: u16 = 1;
+
+/// How often, in frames, a recording stores a hash of the machine state.
+pub const HASH_INTERVAL: u64 = 60;
+
+/// A recording of the buttons held on every frame, along with what is
+/// needed to replay it: the ROM, the model and boot ROM it ran with, and
+/// optionally the save state it started from instead of power-on.
+///
+/// The file uses the save state layout: a header followed by tagged
+/// sections, so later additions stay readable by older builds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_crc: u32,
+    pub model: Model,
+    pub boot_rom_crc: Option<u32>,
+    pub start_state: Option<Vec<u8>>,
+    /// The buttons held during each frame, as a mask of Button values.
+    pub inputs: Vec<u8>,
+    /// CRC-32s of the save state after the given number of frames, used to
+    /// detect when playback no longer matches the recording.
+    pub hashes: Vec<(u64, u32)>,
+}
+
+/// Progress through a movie being played back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Playback {
+    /// Frames played so far.
+    pub frame: u64,
+    pub frames: u64,
+    /// The first frame whose state hash did not match the recording.
+    pub desync: Option<u64>,
+}
+
+impl Playback {
+    pub fn finished(&self) -> bool {
+        self.frame >= self.frames
+    }
+}
+
+/// The hash stored in movies to check that playback matches.
+pub fn state_hash(state: &[u8]) -> u32 {
+    crc32(state)
+}
+
+impl Movie {
+    pub fn new(rom_crc: u32, model: Model, boot_rom_crc: Option<u32>, start_state: Option<Vec<u8>>) -> Movie {
+        Movie { rom_crc, model, boot_rom_crc, start_state, inputs: vec![], hashes: vec![] }
+    }
+
+    /// The state hash recorded after `frame` frames, if there is one.
+    pub fn hash_at(&self, frame: u64) -> Option<u32> {
+        self.hashes.binary_search_by_key(&frame, |&(frame, _)| frame).ok().map(|i| self.hashes[i].1)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut movie = StateWriter::new();
+        movie.bytes(MAGIC);
+        movie.u16(VERSION);
+        movie.section(b"INFO", |info| {
+            info.u32(self.rom_crc);
+            info.blob(self.model.name().as_bytes());
+            info.bool(self.boot_rom_crc.is_some());
+            info.u32(self.boot_rom_crc.unwrap_or(0));
+        });
+        if let Some(state) = &self.start_state {
+            movie.section(b"STAT", |section| section.bytes(state));
+        }
+        movie.section(b"INPT", |section| section.bytes(&self.inputs));
+        movie.section(b"HASH", |section| {
+            for &(frame, hash) in &self.hashes {
+                section.u64(frame);
+                section.u32(hash);
+            }
+        });
+        movie.finish()
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Movie, String> {
+        let mut reader = StateReader::new(data);
+        if reader.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err("not a movie".to_string());
+        }
+        reader.u16()?;
+        let mut movie = None;
+        while !reader.is_empty() {
+            let (tag, mut section) = reader.section()?;
+            if &tag == b"INFO" {
+                let rom_crc = section.u32()?;
+                let model = String::from_utf8_lossy(section.blob()?).into_owned();
+                let model = Model::from_name(&model).ok_or(format!("unknown model '{}'", model))?;
+                let boot_rom = section.bool()?;
+                let boot_rom_crc = section.u32()?;
+                movie = Some(Movie::new(rom_crc, model, boot_rom.then_some(boot_rom_crc), None));
+                continue;
+            }
+            let movie = movie.as_mut().ok_or("movie is missing its INFO section")?;
+            match &tag {
+                b"STAT" => movie.start_state = Some(section.rest().to_vec()),
+                b"INPT" => movie.inputs = section.rest().to_vec(),
+                b"HASH" => {
+                    while !section.is_empty() {
+                        movie.hashes.push((section.u64()?, section.u32()?));
+                    }
+                }
+                _ => {}
+            }
+        }
+        movie.ok_or("movie is missing its INFO section".to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Movie, String> {
+        let data = fs::read(path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        Movie::parse(&data).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_bytes()).map_err(|err| format!("could not write {}: {}", path.display(), err))
+    }
+}
@@ -0,0 +1,219 @@
+// ROM patch formats used by translations and hacks: IPS, UPS and BPS.
+// UPS and BPS patches carry CRC-32s of the ROM they apply to, the result
+// and the patch itself, which are all checked.
+
+use crate::cartridge::MAX_ROM_SIZE;
+use crate::deflate::crc32;
+
+/// The file extensions of the supported patch formats.
+pub const PATCH_EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+/// Applies a patch of any supported format, recognised by its magic bytes.
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.starts_with(b"PATCH") {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(b"UPS1") {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(b"BPS1") {
+        apply_bps(rom, patch)
+    } else {
+        Err("unknown patch format".to_string())
+    }
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl PatchReader<'_> {
+    fn bytes(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len()).ok_or("patch is truncated")?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn big_endian(&mut self, len: usize) -> Result<usize, String> {
+        Ok(self.bytes(len)?.iter().fold(0, |value, &byte| value << 8 | byte as usize))
+    }
+
+    // The variable-length integers of UPS and BPS: seven bits per byte, low
+    // bits first, with the top bit marking the last byte. Each continuation
+    // also adds one to the value so every number has a single encoding.
+    fn varint(&mut self) -> Result<usize, String> {
+        let mut value = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let byte = self.u8()?;
+            value = (byte as usize & 0x7F).checked_mul(shift).and_then(|bits| value.checked_add(bits))
+                .ok_or("patch number is too large")?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_shl(7).filter(|&shift| shift != 0).ok_or("patch number is too large")?;
+            value += shift;
+        }
+    }
+}
+
+/// Applies an IPS patch: records of a 24-bit offset and a 16-bit length
+/// followed by that many bytes, or by a run length and fill byte when the
+/// length is zero, ending with `EOF` and an optional size to truncate to.
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = PatchReader { data: patch, pos: 5 };
+    let mut out = rom.to_vec();
+    loop {
+        if reader.data.get(reader.pos..reader.pos + 3) == Some(b"EOF") {
+            reader.pos += 3;
+            if let Ok(size) = reader.big_endian(3) {
+                out.truncate(size);
+            }
+            return Ok(out);
+        }
+        let offset = reader.big_endian(3)?;
+        let len = reader.big_endian(2)?;
+        let (len, fill) = if len == 0 { (reader.big_endian(2)?, Some(reader.u8()?)) } else { (len, None) };
+        if out.len() < offset + len {
+            out.resize(offset + len, 0);
+        }
+        match fill {
+            Some(byte) => out[offset..offset + len].fill(byte),
+            None => out[offset..offset + len].copy_from_slice(reader.bytes(len)?),
+        }
+    }
+}
+
+// Moves a BPS copy offset by the next number in the patch, whose low bit
+// is the sign, and returns the new offset.
+fn relative(offset: &mut usize, reader: &mut PatchReader) -> Result<usize, String> {
+    let delta = reader.varint()?;
+    let moved = if delta & 1 != 0 { offset.checked_sub(delta >> 1) } else { offset.checked_add(delta >> 1) };
+    *offset = moved.ok_or("patch copies from outside the ROM")?;
+    Ok(*offset)
+}
+
+// Checks the CRC-32 footer shared by UPS and BPS against the patch and the
+// ROM, and returns the CRC-32 the result should have.
+fn checked_footer(rom: &[u8], patch: &[u8]) -> Result<u32, String> {
+    if patch.len() < 16 {
+        return Err("patch is truncated".to_string());
+    }
+    let crc = |pos: usize| u32::from_le_bytes(patch[pos..pos + 4].try_into().unwrap());
+    let footer = patch.len() - 12;
+    if crc(footer + 8) != crc32(&patch[..footer + 8]) {
+        return Err("patch is corrupt: checksum mismatch".to_string());
+    }
+    if crc(footer) != crc32(rom) {
+        return Err("patch is for a different ROM".to_string());
+    }
+    Ok(crc(footer + 4))
+}
+
+fn check_target_size(target_size: usize) -> Result<usize, String> {
+    if target_size > MAX_ROM_SIZE {
+        return Err(format!("patched ROM would be {} bytes, more than the largest Game Boy ROM", target_size));
+    }
+    Ok(target_size)
+}
+
+// The `len` bytes of `rom` from `start`, if they are all there.
+fn source_span(rom: &[u8], start: usize, len: usize) -> Result<&[u8], String> {
+    start.checked_add(len).and_then(|end| rom.get(start..end))
+        .ok_or_else(|| "patch copies from outside the ROM".to_string())
+}
+
+fn check_result(out: Vec<u8>, target_crc: u32) -> Result<Vec<u8>, String> {
+    if crc32(&out) != target_crc {
+        return Err("patched ROM does not match the patch's checksum".to_string());
+    }
+    Ok(out)
+}
+
+/// Applies a UPS patch: runs of bytes XORed into the ROM, each preceded by
+/// how far past the previous run it starts.
+pub fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let target_crc = checked_footer(rom, patch)?;
+    let end = patch.len() - 12;
+    let mut reader = PatchReader { data: &patch[..end], pos: 4 };
+    let source_size = reader.varint()?;
+    let target_size = check_target_size(reader.varint()?)?;
+    if source_size != rom.len() {
+        return Err("patch is for a different ROM".to_string());
+    }
+    let mut out = rom.to_vec();
+    out.resize(target_size, 0);
+    let mut pos = 0usize;
+    while reader.pos < end {
+        pos = pos.saturating_add(reader.varint()?);
+        // Each run ends at a zero byte, which changes nothing but still
+        // moves on a byte.
+        loop {
+            let byte = reader.u8()?;
+            if let Some(out) = out.get_mut(pos) {
+                *out ^= byte;
+            }
+            pos = pos.saturating_add(1);
+            if byte == 0 {
+                break;
+            }
+        }
+    }
+    check_result(out, target_crc)
+}
+
+/// Applies a BPS patch, which builds the result from copies out of the ROM,
+/// the patch and the result so far.
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let target_crc = checked_footer(rom, patch)?;
+    let end = patch.len() - 12;
+    let mut reader = PatchReader { data: &patch[..end], pos: 4 };
+    let source_size = reader.varint()?;
+    let target_size = check_target_size(reader.varint()?)?;
+    let metadata = reader.varint()?;
+    reader.bytes(metadata)?;
+    if source_size != rom.len() {
+        return Err("patch is for a different ROM".to_string());
+    }
+
+    let mut out = Vec::with_capacity(target_size);
+    let mut source_offset = 0usize;
+    let mut target_offset = 0usize;
+    while reader.pos < end {
+        let action = reader.varint()?;
+        let len = (action >> 2) + 1;
+        if out.len() + len > target_size {
+            return Err("patch writes past the end of the ROM".to_string());
+        }
+        match action & 0x03 {
+            0 => {
+                out.extend_from_slice(source_span(rom, out.len(), len)?);
+            }
+            1 => out.extend_from_slice(reader.bytes(len)?),
+            2 => {
+                let start = relative(&mut source_offset, &mut reader)?;
+                out.extend_from_slice(source_span(rom, start, len)?);
+                source_offset += len;
+            }
+            _ => {
+                // The copy may overlap what it produces, so it goes byte by
+                // byte.
+                let start = relative(&mut target_offset, &mut reader)?;
+                let end = start.checked_add(len).ok_or("patch copies from outside the ROM")?;
+                for i in start..end {
+                    let byte = *out.get(i).ok_or("patch copies from outside the ROM")?;
+                    out.push(byte);
+                }
+                target_offset += len;
+            }
+        }
+    }
+    if out.len() != target_size {
+        return Err("patch does not produce a whole ROM".to_string());
+    }
+    check_result(out, target_crc)
+}
@@ -0,0 +1,357 @@
+use crate::savestate::{StateReader, StateWriter};
+
+pub const LCD_WIDTH: usize = 160;
+pub const LCD_HEIGHT: usize = 144;
+
+const DOTS_PER_LINE: u32 = 456;
+const LINES_PER_FRAME: u8 = 154;
+const OAM_SCAN_DOTS: u32 = 80;
+const DRAWING_DOTS: u32 = 172;
+const OAM_SPRITES: usize = 40;
+const SPRITES_PER_LINE: usize = 10;
+
+pub const INT_VBLANK: u8 = 0x01;
+pub const INT_STAT: u8 = 0x02;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+// The mode the PPU is in at a dot of a line while the LCD is on.
+fn mode_at(ly: u8, dot: u32) -> Mode {
+    if ly as usize >= LCD_HEIGHT {
+        Mode::VBlank
+    } else if dot < OAM_SCAN_DOTS {
+        Mode::OamScan
+    } else if dot < OAM_SCAN_DOTS + DRAWING_DOTS {
+        Mode::Drawing
+    } else {
+        Mode::HBlank
+    }
+}
+
+/// The picture processing unit's registers and LCD timing.
+pub struct Ppu {
+    lcdc: u8,
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    dot: u32,
+    mode: Mode,
+    frames: u64,
+    window_line: u8,
+    vram: [u8; 0x2000],
+    oam: [u8; 0xA0],
+    framebuffer: [u8; LCD_WIDTH * LCD_HEIGHT],
+}
+
+impl Default for Ppu {
+    fn default() -> Ppu {
+        Ppu::new()
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Ppu {
+        // Register values as left behind by the DMG boot ROM.
+        Ppu {
+            lcdc: 0x91,
+            stat: 0x00,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0xFC,
+            obp0: 0xFF,
+            obp1: 0xFF,
+            wy: 0,
+            wx: 0,
+            dot: 0,
+            mode: Mode::OamScan,
+            frames: 0,
+            window_line: 0,
+            vram: [0; 0x2000],
+            oam: [0; 0xA0],
+            framebuffer: [0; LCD_WIDTH * LCD_HEIGHT],
+        }
+    }
+
+    pub fn lcd_enabled(&self) -> bool {
+        self.lcdc & 0x80 != 0
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Number of times the PPU has entered VBlank since power on.
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    /// The last rendered frame as shades 0 (lightest) to 3 (darkest), one
+    /// byte per pixel in row-major order.
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0x9FFF => self.vram[(addr - 0x8000) as usize],
+            0xFE00..=0xFE9F => self.oam[(addr - 0xFE00) as usize],
+            0xFF40 => self.lcdc,
+            0xFF41 => 0x80 | (self.stat & 0x78) | self.coincidence() | self.mode as u8,
+            0xFF42 => self.scy,
+            0xFF43 => self.scx,
+            0xFF44 => self.ly,
+            0xFF45 => self.lyc,
+            0xFF47 => self.bgp,
+            0xFF48 => self.obp0,
+            0xFF49 => self.obp1,
+            0xFF4A => self.wy,
+            0xFF4B => self.wx,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.vram[(addr - 0x8000) as usize] = data,
+            0xFE00..=0xFE9F => self.oam[(addr - 0xFE00) as usize] = data,
+            0xFF40 => {
+                let was_enabled = self.lcd_enabled();
+                self.lcdc = data;
+                if was_enabled && !self.lcd_enabled() {
+                    self.ly = 0;
+                    self.dot = 0;
+                    self.window_line = 0;
+                    self.mode = Mode::HBlank;
+                    self.framebuffer.fill(0);
+                } else if !was_enabled && self.lcd_enabled() {
+                    self.mode = Mode::OamScan;
+                }
+            }
+            0xFF41 => self.stat = data & 0x78,
+            0xFF42 => self.scy = data,
+            0xFF43 => self.scx = data,
+            0xFF44 => (),
+            0xFF45 => self.lyc = data,
+            0xFF47 => self.bgp = data,
+            0xFF48 => self.obp0 = data,
+            0xFF49 => self.obp1 = data,
+            0xFF4A => self.wy = data,
+            0xFF4B => self.wx = data,
+            _ => (),
+        }
+    }
+
+    /// Advances the PPU by the given number of T-cycles and returns the
+    /// interrupt flags (IF bits) raised along the way.
+    pub fn tick(&mut self, cycles: u32) -> u8 {
+        if !self.lcd_enabled() {
+            return 0;
+        }
+        let mut interrupts = 0;
+        for _ in 0..cycles {
+            self.dot += 1;
+            if self.dot == DOTS_PER_LINE {
+                self.dot = 0;
+                self.ly = (self.ly + 1) % LINES_PER_FRAME;
+                if self.ly == 0 {
+                    self.window_line = 0;
+                }
+                if self.ly == self.lyc && self.stat & 0x40 != 0 {
+                    interrupts |= INT_STAT;
+                }
+            }
+            let mode = mode_at(self.ly, self.dot);
+            if mode != self.mode {
+                interrupts |= self.enter_mode(mode);
+            }
+        }
+        interrupts
+    }
+
+    fn enter_mode(&mut self, mode: Mode) -> u8 {
+        if self.mode == Mode::Drawing && mode == Mode::HBlank {
+            self.render_line();
+        }
+        self.mode = mode;
+        let mut interrupts = 0;
+        let stat_source = match mode {
+            Mode::HBlank => 0x08,
+            Mode::VBlank => 0x10,
+            Mode::OamScan => 0x20,
+            Mode::Drawing => 0x00,
+        };
+        if self.stat & stat_source != 0 {
+            interrupts |= INT_STAT;
+        }
+        if mode == Mode::VBlank {
+            self.frames += 1;
+            interrupts |= INT_VBLANK;
+        }
+        interrupts
+    }
+
+    fn render_line(&mut self) {
+        let ly = self.ly;
+        let row = ly as usize * LCD_WIDTH;
+        // Colour indices before palette lookup; sprites need them to resolve
+        // BG-over-OBJ priority.
+        let mut bg_color = [0u8; LCD_WIDTH];
+
+        if self.lcdc & 0x01 != 0 {
+            let map = if self.lcdc & 0x08 != 0 { 0x1C00 } else { 0x1800 };
+            let y = ly.wrapping_add(self.scy);
+            for (x, color) in bg_color.iter_mut().enumerate() {
+                let map_x = (x as u8).wrapping_add(self.scx);
+                *color = self.tile_pixel(map, map_x, y);
+            }
+            let window_visible = self.lcdc & 0x20 != 0 && ly >= self.wy && self.wx <= 166;
+            if window_visible {
+                let map = if self.lcdc & 0x40 != 0 { 0x1C00 } else { 0x1800 };
+                let start = self.wx as i16 - 7;
+                for (x, color) in bg_color.iter_mut().enumerate() {
+                    let window_x = x as i16 - start;
+                    if window_x >= 0 {
+                        *color = self.tile_pixel(map, window_x as u8, self.window_line);
+                    }
+                }
+                self.window_line += 1;
+            }
+        }
+        for (x, &color) in bg_color.iter().enumerate() {
+            self.framebuffer[row + x] = (self.bgp >> (color * 2)) & 0x03;
+        }
+
+        if self.lcdc & 0x02 != 0 {
+            self.render_sprites(&bg_color);
+        }
+    }
+
+    // Colour index of the pixel at (x, y) of the 256x256 background map
+    // starting at `map` in VRAM.
+    fn tile_pixel(&self, map: usize, x: u8, y: u8) -> u8 {
+        let tile = self.vram[map + (y as usize / 8) * 32 + x as usize / 8];
+        let tile_addr = if self.lcdc & 0x10 != 0 {
+            tile as usize * 16
+        } else {
+            (0x1000 + (tile as i8 as isize) * 16) as usize
+        };
+        let line = tile_addr + (y as usize % 8) * 2;
+        let bit = 7 - (x % 8);
+        let lo = (self.vram[line] >> bit) & 0x01;
+        let hi = (self.vram[line + 1] >> bit) & 0x01;
+        hi << 1 | lo
+    }
+
+    fn render_sprites(&mut self, bg_color: &[u8; LCD_WIDTH]) {
+        let ly = self.ly as i16;
+        let height = if self.lcdc & 0x04 != 0 { 16 } else { 8 };
+        let mut sprites: Vec<usize> = (0..OAM_SPRITES)
+            .filter(|&i| {
+                let y = self.oam[i * 4] as i16 - 16;
+                ly >= y && ly < y + height
+            })
+            .take(SPRITES_PER_LINE)
+            .collect();
+        // Lower X wins, ties go to the earlier OAM entry. Drawing in reverse
+        // priority order lets the winner overwrite the others.
+        sprites.sort_by_key(|&i| (self.oam[i * 4 + 1], i));
+        let row = self.ly as usize * LCD_WIDTH;
+        for &i in sprites.iter().rev() {
+            let y = self.oam[i * 4] as i16 - 16;
+            let x = self.oam[i * 4 + 1] as i16 - 8;
+            let mut tile = self.oam[i * 4 + 2];
+            let attr = self.oam[i * 4 + 3];
+            if height == 16 {
+                tile &= 0xFE;
+            }
+            let mut line = ly - y;
+            if attr & 0x40 != 0 {
+                line = height - 1 - line;
+            }
+            let addr = tile as usize * 16 + line as usize * 2;
+            let palette = if attr & 0x10 != 0 { self.obp1 } else { self.obp0 };
+            for px in 0..8 {
+                let sx = x + px;
+                if !(0..LCD_WIDTH as i16).contains(&sx) {
+                    continue;
+                }
+                let bit = if attr & 0x20 != 0 { px } else { 7 - px };
+                let lo = (self.vram[addr] >> bit) & 0x01;
+                let hi = (self.vram[addr + 1] >> bit) & 0x01;
+                let color = hi << 1 | lo;
+                if color == 0 {
+                    continue;
+                }
+                if attr & 0x80 != 0 && bg_color[sx as usize] != 0 {
+                    continue;
+                }
+                self.framebuffer[row + sx as usize] = (palette >> (color * 2)) & 0x03;
+            }
+        }
+    }
+
+    fn coincidence(&self) -> u8 {
+        if self.ly == self.lyc { 0x04 } else { 0x00 }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        for register in [self.lcdc, self.stat, self.scy, self.scx, self.ly, self.lyc, self.bgp, self.obp0,
+            self.obp1, self.wy, self.wx] {
+            state.u8(register);
+        }
+        state.u32(self.dot);
+        state.u8(self.mode as u8);
+        state.u64(self.frames);
+        state.u8(self.window_line);
+        state.bytes(&self.vram);
+        state.bytes(&self.oam);
+        state.bytes(&self.framebuffer);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        for register in [&mut self.lcdc, &mut self.stat, &mut self.scy, &mut self.scx, &mut self.ly, &mut self.lyc,
+            &mut self.bgp, &mut self.obp0, &mut self.obp1, &mut self.wy, &mut self.wx] {
+            *register = state.u8()?;
+        }
+        self.dot = state.u32()?;
+        self.mode = match state.u8()? {
+            0 => Mode::HBlank,
+            1 => Mode::VBlank,
+            2 => Mode::OamScan,
+            3 => Mode::Drawing,
+            mode => return Err(format!("invalid PPU mode {}", mode)),
+        };
+        self.frames = state.u64()?;
+        self.window_line = state.u8()?;
+        if self.ly >= LINES_PER_FRAME || self.dot >= DOTS_PER_LINE {
+            return Err(format!("invalid PPU position: line {} dot {}", self.ly, self.dot));
+        }
+        // The mode follows from the position while the LCD is on, and is
+        // HBlank while it is off.
+        let mode = if self.lcd_enabled() { mode_at(self.ly, self.dot) } else { Mode::HBlank };
+        if self.mode != mode {
+            return Err(format!("PPU mode {:?} does not match line {} dot {}", self.mode, self.ly, self.dot));
+        }
+        if self.window_line as usize > LCD_HEIGHT {
+            return Err(format!("invalid PPU window line {}", self.window_line));
+        }
+        state.fill(&mut self.vram)?;
+        state.fill(&mut self.oam)?;
+        state.fill(&mut self.framebuffer)
+    }
+}
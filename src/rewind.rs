@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+
+// How many snapshots share a keyframe. Each delta is taken against the
+// keyframe rather than the previous snapshot, so restoring any snapshot
+// costs a single decode.
+const KEYFRAME_INTERVAL: usize = 60;
+
+// Snapshots stored as XOR deltas against the keyframe that starts the
+// group. They are evicted together, since the deltas are useless without
+// the keyframe.
+struct Group {
+    frame: u64,
+    keyframe: Vec<u8>,
+    deltas: Vec<(u64, Vec<u8>)>,
+}
+
+impl Group {
+    fn size(&self) -> usize {
+        self.keyframe.len() + self.deltas.iter().map(|(_, delta)| delta.len()).sum::<usize>()
+    }
+
+    fn last_frame(&self) -> u64 {
+        self.deltas.last().map_or(self.frame, |&(frame, _)| frame)
+    }
+}
+
+/// A ring buffer of save states, one per frame, limited both in how many
+/// frames it reaches back and in how much memory it uses. Only every
+/// KEYFRAME_INTERVAL-th state is kept whole; the rest are stored as
+/// run-length encoded XOR deltas, which are small because little of the
+/// machine changes from frame to frame.
+pub struct RewindBuffer {
+    groups: VecDeque<Group>,
+    depth: u64,
+    budget: usize,
+    size: usize,
+    frame: u64,
+}
+
+impl RewindBuffer {
+    /// Keeps up to `depth` frames of history in at most roughly `budget`
+    /// bytes. The newest keyframe group is always kept, even if it alone is
+    /// over budget.
+    pub fn new(depth: u64, budget: usize) -> RewindBuffer {
+        RewindBuffer { groups: VecDeque::new(), depth: depth.max(1), budget, size: 0, frame: 0 }
+    }
+
+    /// The number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(|group| group.deltas.len() + 1).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// The memory used by the stored snapshots, in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// How many frames back the oldest snapshot is.
+    pub fn frames(&self) -> u64 {
+        self.groups.front().map_or(0, |group| self.frame - group.frame)
+    }
+
+    /// Adds the state at the end of a frame.
+    pub fn push(&mut self, state: Vec<u8>) {
+        self.frame += 1;
+        let frame = self.frame;
+        let delta = match self.groups.back() {
+            Some(group) if group.deltas.len() + 1 < KEYFRAME_INTERVAL && group.keyframe.len() == state.len() => {
+                Some(encode_delta(&group.keyframe, &state))
+            }
+            _ => None,
+        };
+        match delta {
+            Some(delta) => {
+                self.size += delta.len();
+                self.groups.back_mut().unwrap().deltas.push((frame, delta));
+            }
+            None => {
+                self.size += state.len();
+                self.groups.push_back(Group { frame, keyframe: state, deltas: vec![] });
+            }
+        }
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.groups.len() > 1 {
+            let oldest = &self.groups[1];
+            // Dropping the oldest group must still leave `depth` frames.
+            let too_deep = self.frame - oldest.frame >= self.depth;
+            if !too_deep && self.size <= self.budget {
+                break;
+            }
+            let group = self.groups.pop_front().unwrap();
+            self.size -= group.size();
+        }
+    }
+
+    /// Removes and returns the newest state at least `frames` frames old, or
+    /// the oldest one held if the buffer does not reach back that far. It
+    /// stays in the buffer as the new present; everything after it is
+    /// dropped. Returns the state and how many frames back it was.
+    pub fn rewind(&mut self, frames: u64) -> Option<(Vec<u8>, u64)> {
+        let oldest = self.groups.front()?.frame;
+        let target = self.frame.saturating_sub(frames).max(oldest);
+        while self.groups.back().is_some_and(|group| group.frame > target) {
+            let group = self.groups.pop_back().unwrap();
+            self.size -= group.size();
+        }
+        let group = self.groups.back_mut()?;
+        while group.deltas.last().is_some_and(|&(frame, _)| frame > target) {
+            let (_, delta) = group.deltas.pop().unwrap();
+            self.size -= delta.len();
+        }
+        let frame = group.last_frame();
+        let state = match group.deltas.last() {
+            Some((_, delta)) => decode_delta(&group.keyframe, delta),
+            None => group.keyframe.clone(),
+        };
+        let back = self.frame - frame;
+        self.frame = frame;
+        Some((state, back))
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.size = 0;
+    }
+}
+
+// Encodes `state` XOR `base` as pairs of a run of unchanged bytes and a run
+// of changed bytes, each length written as a LEB128 varint followed, for
+// changed runs, by the XORed bytes.
+fn encode_delta(base: &[u8], state: &[u8]) -> Vec<u8> {
+    let mut delta = vec![];
+    let mut i = 0;
+    while i < state.len() {
+        let same = base[i..].iter().zip(&state[i..]).take_while(|(a, b)| a == b).count();
+        i += same;
+        let changed = base[i..].iter().zip(&state[i..]).take_while(|(a, b)| a != b).count();
+        write_varint(&mut delta, same);
+        write_varint(&mut delta, changed);
+        delta.extend(base[i..i + changed].iter().zip(&state[i..i + changed]).map(|(a, b)| a ^ b));
+        i += changed;
+    }
+    delta
+}
+
+fn decode_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut state = base.to_vec();
+    let mut i = 0;
+    let mut pos = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos);
+        let changed = read_varint(delta, &mut pos);
+        for (byte, xor) in state[i..i + changed].iter_mut().zip(&delta[pos..pos + changed]) {
+            *byte ^= xor;
+        }
+        i += changed;
+        pos += changed;
+    }
+    state
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,167 @@
+// The save state format.
+//
+// A state starts with the magic bytes, the format version and the CRC-32
+// of the ROM it was taken from. Sections follow, each a four-byte tag, a
+// little-endian u32 length and that many bytes of data. Readers skip
+// sections they do not know and ignore bytes left over at the end of a
+// section, so new state is added either as a new section or as fields
+// appended to an existing one and older builds can still load the result.
+// The version records which layout wrote a state; a change that older
+// builds could not read would need a new section tag.
+
+pub const MAGIC: &[u8; 8] = b"GBEMUSST";
+pub const VERSION: u16 = 1;
+
+/// Serialises fields in little-endian order.
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter::default()
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    /// Writes a fixed-size block whose length the reader knows.
+    pub fn bytes(&mut self, data: &[u8]) {
+        self.data.extend_from_slice(data);
+    }
+
+    /// Writes a block preceded by its length.
+    pub fn blob(&mut self, data: &[u8]) {
+        self.u32(data.len() as u32);
+        self.bytes(data);
+    }
+
+    /// Writes a tagged section filled in by `write`.
+    pub fn section<F: FnOnce(&mut StateWriter)>(&mut self, tag: &[u8; 4], write: F) {
+        let mut section = StateWriter::new();
+        write(&mut section);
+        self.bytes(tag);
+        self.blob(&section.data);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Reads fields written by [`StateWriter`].
+#[derive(Debug)]
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateReader<'a> {
+        StateReader { data, pos: 0 }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len())
+            .ok_or("save state is truncated")?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn blob(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    /// Reads a block into `buffer`, which must be exactly as long.
+    pub fn fill(&mut self, buffer: &mut [u8]) -> Result<(), String> {
+        buffer.copy_from_slice(self.bytes(buffer.len())?);
+        Ok(())
+    }
+
+    /// Reads everything left, for a block that runs to the end.
+    pub fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.pos..];
+        self.pos = self.data.len();
+        rest
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    /// Reads the next section's tag and a reader over its data.
+    pub fn section(&mut self) -> Result<([u8; 4], StateReader<'a>), String> {
+        let tag = self.array()?;
+        Ok((tag, StateReader::new(self.blob()?)))
+    }
+}
+
+/// The header at the start of a save state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateHeader {
+    pub version: u16,
+    pub rom_crc: u32,
+}
+
+impl StateHeader {
+    pub fn new(rom_crc: u32) -> StateHeader {
+        StateHeader { version: VERSION, rom_crc }
+    }
+
+    pub fn write(&self, writer: &mut StateWriter) {
+        writer.bytes(MAGIC);
+        writer.u16(self.version);
+        writer.u32(self.rom_crc);
+    }
+
+    pub fn read(reader: &mut StateReader) -> Result<StateHeader, String> {
+        if reader.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err("not a save state".to_string());
+        }
+        Ok(StateHeader { version: reader.u16()?, rom_crc: reader.u32()? })
+    }
+}
@@ -0,0 +1,88 @@
+use crate::savestate::{StateReader, StateWriter};
+
+pub const INT_SERIAL: u8 = 0x08;
+
+// An internally clocked transfer shifts 8 bits at 8192 Hz.
+const TRANSFER_CYCLES: u32 = 8 * 512;
+
+/// The serial port (SB at 0xFF01, SC at 0xFF02). No link partner is
+/// attached, so incoming bits are all ones; every byte sent is kept so test
+/// ROMs that report over serial can be read back.
+pub struct Serial {
+    data: u8,
+    control: u8,
+    timer: u32,
+    output: Vec<u8>,
+}
+
+impl Default for Serial {
+    fn default() -> Serial {
+        Serial::new()
+    }
+}
+
+impl Serial {
+    pub fn new() -> Serial {
+        Serial { data: 0, control: 0, timer: 0, output: vec![] }
+    }
+
+    /// Every byte transferred out since power on or the last take_output.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF01 => self.data,
+            0xFF02 => 0x7E | self.control,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0xFF01 => self.data = data,
+            0xFF02 => {
+                self.control = data & 0x81;
+                if self.control == 0x81 {
+                    self.output.push(self.data);
+                    self.timer = TRANSFER_CYCLES;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Advances a transfer in progress and returns the serial interrupt
+    /// flag when it completes.
+    pub fn tick(&mut self, cycles: u32) -> u8 {
+        if self.timer == 0 {
+            return 0;
+        }
+        self.timer = self.timer.saturating_sub(cycles);
+        if self.timer > 0 {
+            return 0;
+        }
+        self.data = 0xFF;
+        self.control &= 0x7F;
+        INT_SERIAL
+    }
+
+    // Bytes already sent are not part of the state.
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.data);
+        state.u8(self.control);
+        state.u32(self.timer);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.data = state.u8()?;
+        self.control = state.u8()?;
+        self.timer = state.u32()?;
+        Ok(())
+    }
+}
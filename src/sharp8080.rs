@@ -1,11 +1,14 @@
 use crate::BusTrait;
 mod instructions;
+mod registers;
 #[cfg(test)]
 mod test;
 pub use instructions::INSTRUCTION_TABLE;
 pub use instructions::INSTRUCTION_TABLE_CB;
 pub use instructions::Instruction;
 pub use instructions::Type;
+pub use registers::Registers;
+pub use registers::{FLAG_C, FLAG_H, FLAG_N, FLAG_Z};
 
 macro_rules! reg_map_get {
     ($self: expr, $bus: expr, $op: expr) => {{
@@ -16,7 +19,7 @@ macro_rules! reg_map_get {
             0x3 => $self.e,
             0x4 => $self.h,
             0x5 => $self.l,
-            0x6 => $bus.read(($self.h as u16) << 8 | $self.l as u16),
+            0x6 => $bus.read($self.hl()),
             0x7 => $self.a,
             _   => 0
         }
@@ -32,7 +35,7 @@ macro_rules! reg_map_set {
             0x3 => $self.e = $value,
             0x4 => $self.h = $value,
             0x5 => $self.l = $value,
-            0x6 => $bus.write(($self.h as u16) << 8 | $self.l as u16, $value),
+            0x6 => $bus.write($self.hl(), $value),
             0x7 => $self.a = $value,
             _   => ()
         }
@@ -45,94 +48,76 @@ macro_rules! ld_reg {
     }}
 }
 
-macro_rules! add_reg {
+macro_rules! alu_reg {
     ($self: expr, $bus: expr, $op: expr) => {{
-        let reg = reg_map_get!($self, $bus, $op);
-        $self.hf = if ($self.a & 0x0f) + (reg & 0x0f) > 0x0f { 1 } else { 0 };
-        $self.cf = if ($self.a + reg) as u16 > 0xff { 1 } else { 0 };
-        $self.a += reg;
-        $self.zf = if $self.a == 0 { 1 } else { 0 };
-        $self.nf = 0;
+        let val = reg_map_get!($self, $bus, $op);
+        $self.alu($op, val);
+    }}
+}
+
+// Stores the result of a CB rotate or shift and sets the flags from it and
+// the bit shifted out.
+macro_rules! cb_store {
+    ($self: expr, $bus: expr, $op: expr, $result: expr, $carry: expr) => {{
+        let result: u8 = $result;
+        reg_map_set!($self, $bus, $op, result);
+        $self.f = if result == 0 { FLAG_Z } else { 0 } | if $carry { FLAG_C } else { 0 };
     }}
 }
 
 macro_rules! cb_rlc {
     ($self: expr, $bus: expr, $op: expr) => {{
         let val = reg_map_get!($self, $bus, $op);
-        cb_rl!($self, $bus, $op);
-        $self.cf = if val & 0x80 != 0 { 1 } else { 0 };
+        cb_store!($self, $bus, $op, val.rotate_left(1), val & 0x80 != 0);
     }}
 }
 
 macro_rules! cb_rrc {
     ($self: expr, $bus: expr, $op: expr) => {{
         let val = reg_map_get!($self, $bus, $op);
-        cb_rr!($self, $bus, $op);
-        $self.cf = if val & 0x01 != 0 { 1 } else { 0 };
+        cb_store!($self, $bus, $op, val.rotate_right(1), val & 0x01 != 0);
     }}
 }
 
 macro_rules! cb_rl {
     ($self: expr, $bus: expr, $op: expr) => {{
         let val = reg_map_get!($self, $bus, $op);
-        reg_map_set!($self, $bus, $op, (val << 1) | ((val & 0x80) >> 7));
-        $self.zf = if (val << 1) | ((val & 0x80) >> 7) == 0 { 1 } else { 0 };
-        $self.nf = 0;
-        $self.hf = 0;
+        cb_store!($self, $bus, $op, val << 1 | $self.carry(), val & 0x80 != 0);
     }}
 }
 
 macro_rules! cb_rr {
     ($self: expr, $bus: expr, $op: expr) => {{
         let val = reg_map_get!($self, $bus, $op);
-        reg_map_set!($self, $bus, $op, (val >> 1) | ((val & 0x01) << 7));
-        $self.zf = if (val >> 1) | ((val & 0x01) << 7) == 0 { 1 } else { 0 };
-        $self.nf = 0;
-        $self.hf = 0;
+        cb_store!($self, $bus, $op, val >> 1 | $self.carry() << 7, val & 0x01 != 0);
     }}
 }
 
 macro_rules! cb_sla {
     ($self: expr, $bus: expr, $op: expr) => {{
         let val = reg_map_get!($self, $bus, $op);
-        reg_map_set!($self, $bus, $op, val << 1 | val & 0x01);
-        $self.zf = if val << 1 | val & 0x01 == 0 { 1 } else { 0 };
-        $self.nf = 0;
-        $self.hf = 0;
-        $self.cf = if val & 0x80 != 0 { 1 } else { 0 };
+        cb_store!($self, $bus, $op, val << 1, val & 0x80 != 0);
     }}
 }
 
 macro_rules! cb_sra {
     ($self: expr, $bus: expr, $op: expr) => {{
         let val = reg_map_get!($self, $bus, $op);
-        reg_map_set!($self, $bus, $op, val >> 1 | val & 0x80);
-        $self.zf = if val >> 1 | val & 0x80 == 0 { 1 } else { 0 };
-        $self.nf = 0;
-        $self.hf = 0;
-        $self.cf = if val & 0x01 == 1 { 1 } else { 0 };
+        cb_store!($self, $bus, $op, val >> 1 | val & 0x80, val & 0x01 != 0);
     }}
 }
 
 macro_rules! cb_swap {
     ($self: expr, $bus: expr, $op: expr) => {{
         let val = reg_map_get!($self, $bus, $op);
-        reg_map_set!($self, $bus, $op, (val << 4 | val >> 4));
-        $self.zf = if val << 4 | val >> 4 == 0 { 1 } else { 0 };
-        $self.nf = 0;
-        $self.hf = 0;
-        $self.cf = 0;
+        cb_store!($self, $bus, $op, val.rotate_left(4), false);
     }}
 }
 
 macro_rules! cb_srl {
     ($self: expr, $bus: expr, $op: expr) => {{
         let val = reg_map_get!($self, $bus, $op);
-        reg_map_set!($self, $bus, $op, val >> 1);
-        $self.zf = if val >> 1 == 0 { 1 } else { 0 };
-        $self.nf = 0;
-        $self.hf = 0;
-        $self.cf = if val & 0x01 != 0 { 1 } else { 0 };
+        cb_store!($self, $bus, $op, val >> 1, val & 0x01 != 0);
     }}
 }
 
@@ -150,14 +135,15 @@ macro_rules! cb_set_bit {
     }}
 }
 
+// Z is set when the tested bit is clear; C is left alone.
 macro_rules! cb_bit {
     ($self: expr, $bus: expr, $op: expr, $bit: literal) => {{
-        $self.zf = reg_map_get!($self, $bus, $op) & (0b1 << $bit);
-        $self.nf = 0;
-        $self.hf = 1;
+        let zero = reg_map_get!($self, $bus, $op) & (0b1 << $bit) == 0;
+        $self.f = $self.f & FLAG_C | FLAG_H | if zero { FLAG_Z } else { 0 };
     }}
 }
 
+/// The DMG's LR35902 CPU core.
 #[derive(Debug)]
 pub struct Sharp8080 {
     a: u8,
@@ -169,48 +155,217 @@ pub struct Sharp8080 {
     l: u8,
     sp: u16,
     pc: u16,
-    zf: u8,
-    nf: u8,
-    hf: u8,
-    cf: u8,
+    f: u8,
     ime: bool,
+    ld_b_b_breakpoint: bool,
+    breakpoint_hit: bool,
+    trap_undefined: bool,
+    undefined_opcode: Option<u16>,
 }
 
 impl Sharp8080 {
     pub fn new(pc: u16) -> Sharp8080 {
         Sharp8080 { a: 0, b: 0, c: 0, d: 0, e: 0, h: 0, l: 0, sp: 0, 
-            pc: pc, zf: 0, nf: 0, hf: 0, cf: 0, ime: true }
+            pc, f: 0, ime: true,
+            ld_b_b_breakpoint: false, breakpoint_hit: false, trap_undefined: false,
+            undefined_opcode: None }
+    }
+
+    /// Creates a CPU in the state a boot ROM leaves it in when it jumps to
+    /// the cartridge entry point.
+    pub fn post_boot(a: u8, f: u8, bc: u16, de: u16, hl: u16) -> Sharp8080 {
+        let mut cpu = Sharp8080::new(0x0100);
+        cpu.set_af((a as u16) << 8 | f as u16);
+        cpu.set_bc(bc);
+        cpu.set_de(de);
+        cpu.set_hl(hl);
+        cpu.sp = 0xFFFE;
+        cpu
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    pub fn a(&self) -> u8 {
+        self.a
+    }
+
+    pub fn b(&self) -> u8 {
+        self.b
+    }
+
+    pub fn c(&self) -> u8 {
+        self.c
+    }
+
+    pub fn d(&self) -> u8 {
+        self.d
+    }
+
+    pub fn e(&self) -> u8 {
+        self.e
+    }
+
+    pub fn h(&self) -> u8 {
+        self.h
+    }
+
+    pub fn l(&self) -> u8 {
+        self.l
     }
 
-    fn apply_flags(&self) {
+    /// Treats LD B,B (0x40) as a software breakpoint, the convention used by
+    /// the mooneye test suite to signal the end of a test. Hits are latched
+    /// until collected with take_breakpoint.
+    pub fn set_ld_b_b_breakpoint(&mut self, enabled: bool) {
+        self.ld_b_b_breakpoint = enabled;
+    }
+
+    /// Returns whether a breakpoint instruction was executed since the last
+    /// call.
+    pub fn take_breakpoint(&mut self) -> bool {
+        std::mem::take(&mut self.breakpoint_hit)
+    }
 
+    /// Instead of panicking on an undefined opcode, lock up like the
+    /// hardware does and report the opcode through undefined_opcode.
+    pub fn set_trap_undefined(&mut self, trap: bool) {
+        self.trap_undefined = trap;
     }
 
+    /// The undefined opcode the CPU locked up on, if any. A locked CPU does
+    /// not execute anything until clear_undefined_opcode is called.
+    pub fn undefined_opcode(&self) -> Option<u16> {
+        self.undefined_opcode
+    }
+
+    pub fn clear_undefined_opcode(&mut self) {
+        self.undefined_opcode = None;
+    }
+
+    /// The flags register; the low nibble is always zero.
+    pub fn f(&self) -> u8 {
+        self.f
+    }
+
+    pub fn set_f(&mut self, f: u8) {
+        self.f = f & 0xF0;
+    }
+
+    fn flag(&self, flag: u8) -> bool {
+        self.f & flag != 0
+    }
+
+    fn set_flag(&mut self, flag: u8, on: bool) {
+        if on {
+            self.f |= flag;
+        } else {
+            self.f &= !flag;
+        }
+    }
+
+    // The carry flag as 0 or 1, for ADC, SBC and the rotates through carry.
+    fn carry(&self) -> u8 {
+        (self.f & FLAG_C) >> 4
+    }
+
+    pub fn af(&self) -> u16 {
+        (self.a as u16) << 8 | self.f() as u16
+    }
+
+    pub fn set_af(&mut self, af: u16) {
+        self.a = (af >> 8) as u8;
+        self.set_f(af as u8);
+    }
+
+    pub fn bc(&self) -> u16 {
+        (self.b as u16) << 8 | self.c as u16
+    }
+
+    pub fn set_bc(&mut self, bc: u16) {
+        self.b = (bc >> 8) as u8;
+        self.c = bc as u8;
+    }
+
+    pub fn de(&self) -> u16 {
+        (self.d as u16) << 8 | self.e as u16
+    }
+
+    pub fn set_de(&mut self, de: u16) {
+        self.d = (de >> 8) as u8;
+        self.e = de as u8;
+    }
+
+    pub fn hl(&self) -> u16 {
+        (self.h as u16) << 8 | self.l as u16
+    }
+
+    pub fn set_hl(&mut self, hl: u16) {
+        self.h = (hl >> 8) as u8;
+        self.l = hl as u8;
+    }
+
+    /// Returns a copy of the register file.
+    pub fn registers(&self) -> Registers {
+        Registers {
+            a: self.a, f: self.f, b: self.b, c: self.c, d: self.d, e: self.e,
+            h: self.h, l: self.l, sp: self.sp, pc: self.pc, ime: self.ime,
+        }
+    }
+
+    /// Overwrites the register file; the low nibble of F is discarded.
+    pub fn set_registers(&mut self, registers: &Registers) {
+        self.a = registers.a;
+        self.set_f(registers.f);
+        self.b = registers.b;
+        self.c = registers.c;
+        self.d = registers.d;
+        self.e = registers.e;
+        self.h = registers.h;
+        self.l = registers.l;
+        self.sp = registers.sp;
+        self.pc = registers.pc;
+        self.ime = registers.ime;
+    }
+
+    /// Reads the opcode at PC; CB-prefixed opcodes are returned as 0xCBxx.
     pub fn fetch_opcode(&self, bus: &dyn BusTrait) -> u16 {
         match bus.read(self.pc) {
             0xCB => {
-                (0xCB as u16) << 8 |
-                  bus.read(self.pc+1) as u16
+                0xCB_u16 << 8 |
+                  bus.read(self.pc.wrapping_add(1)) as u16
             } 
-            _ => { bus.read(self.pc) as u16}
+            opcode => opcode as u16
         }
     }
 
-    pub fn execute(&mut self, bus: &mut dyn BusTrait, opcode: u16) {
+    /// Executes the given opcode and returns the T-cycles it took.
+    pub fn execute(&mut self, bus: &mut dyn BusTrait, opcode: u16) -> u8 {
+        if self.undefined_opcode.is_some() {
+            return 4;
+        }
+        let start_pc = self.pc;
         // pc_base points to the first param or next opcode.
         let (instruction, pc_base) = if opcode & 0xFF00 == 0xCB00 {
-            (&INSTRUCTION_TABLE_CB[((opcode & 0x00FF) as usize)], self.pc+2)
+            (&INSTRUCTION_TABLE_CB[(opcode & 0x00FF) as usize], self.pc.wrapping_add(2))
         } else {
-            (&INSTRUCTION_TABLE[(opcode & 0x00FF) as usize], self.pc+1)
+            (&INSTRUCTION_TABLE[(opcode & 0x00FF) as usize], self.pc.wrapping_add(1))
         };
         match instruction.encoding {
             Type::N => {
                 match opcode {
                     0x0000          => (),
-                    0x0002          => self.ld_bc_a(),
+                    0x0002          => self.ld_bc_a(bus),
                     0x0003          => self.inc_bc(),
+                    0x0027          => self.daa(),
 
-                    0x0040..=0x0047 => ld_reg!(self, bus, self.b, opcode),
+                    0x0040          => self.breakpoint_hit |= self.ld_b_b_breakpoint,
+                    0x0041..=0x0047 => ld_reg!(self, bus, self.b, opcode),
                     0x0048..=0x004f => ld_reg!(self, bus, self.c, opcode),
                     0x0050..=0x0057 => ld_reg!(self, bus, self.d, opcode),
                     0x0058..=0x005f => ld_reg!(self, bus, self.e, opcode),
@@ -219,30 +374,47 @@ impl Sharp8080 {
 
                     0x0078..=0x007f => ld_reg!(self, bus, self.a, opcode),
 
-                    0x0080..=0x0087 => add_reg!(self, bus, opcode),
+                    0x0080..=0x00BF => alu_reg!(self, bus, opcode),
+
+                    0x00C1          => { let bc = self.pop(bus); self.set_bc(bc) }
+                    0x00D1          => { let de = self.pop(bus); self.set_de(de) }
+                    0x00E1          => { let hl = self.pop(bus); self.set_hl(hl) }
+                    0x00F1          => { let af = self.pop(bus); self.set_af(af) }
+                    0x00C5          => self.push(bus, self.bc()),
+                    0x00D5          => self.push(bus, self.de()),
+                    0x00E5          => self.push(bus, self.hl()),
+                    0x00F5          => self.push(bus, self.af()),
 
                     0x00F3          => self.ime = false,
-                    _               => self.undefined_instruction(),
+                    _               => self.undefined_instruction(opcode),
+                }
+                self.pc = self.pc.wrapping_add(instruction.length);
+            }
+            Type::D8 => {
+                let b0 = bus.read(pc_base);
+                match opcode {
+                    0x00C6 | 0x00CE | 0x00D6 | 0x00DE |
+                    0x00E6 | 0x00EE | 0x00F6 | 0x00FE => self.alu(opcode, b0),
+                    _      => self.undefined_instruction(opcode),
                 }
-                self.pc += instruction.length;
-                self.decode_type_n(instruction);
+                self.pc = self.pc.wrapping_add(instruction.length);
             }
             Type::D16 => {
                 let b0 = bus.read(pc_base);
-                let b1 = bus.read(pc_base+1);
+                let b1 = bus.read(pc_base.wrapping_add(1));
                 match opcode {
                     0x0001 => self.ld_bc_d16(b0, b1),
-                    _      => self.undefined_instruction(), 
+                    _      => self.undefined_instruction(opcode), 
                 }
+                self.pc = self.pc.wrapping_add(instruction.length);
             }
             Type::A16 => {
-                let address = (bus.read(pc_base+1) as u16) << 8 | 
+                let address = (bus.read(pc_base.wrapping_add(1)) as u16) << 8 | 
                                bus.read(pc_base) as u16;
                 match opcode {
                     0x00C3 => self.pc = address,
-                    _      => self.undefined_instruction()
+                    _      => self.undefined_instruction(opcode)
                 }
-                self.decode_type_a16(instruction, address);
             }
             Type::CB => {
                 match opcode {
@@ -278,43 +450,117 @@ impl Sharp8080 {
                     0xCBE8..=0xCBEF => cb_set_bit!(self, bus, opcode, 5),
                     0xCBF0..=0xCBF7 => cb_set_bit!(self, bus, opcode, 6),
                     0xCBF8..=0xCBFF => cb_set_bit!(self, bus, opcode, 7),
-                    _      => self.undefined_instruction()
+                    _      => self.undefined_instruction(opcode)
                 }
-                self.pc += instruction.length;
+                self.pc = self.pc.wrapping_add(instruction.length);
             }
             _ => {
                 self.undefined_type();
             }
         }
-        self.apply_flags();
-        self.wait(instruction.cycles);
+        if self.undefined_opcode.is_some() {
+            self.pc = start_pc;
+        }
+        instruction.cycles
     }
 
-    fn wait(&self, cycles: u8) {
+    // Applies the 8-bit ALU operation selected by bits 3-5 of the opcode,
+    // which is shared by the register (0x80-0xBF) and immediate (0xC6-0xFE)
+    // forms: ADD, ADC, SUB, SBC, AND, XOR, OR, CP.
+    fn alu(&mut self, opcode: u16, val: u8) {
+        match (opcode >> 3) & 0x07 {
+            0x0 => self.a = self.add8(val, 0),
+            0x1 => self.a = self.add8(val, self.carry()),
+            0x2 => self.a = self.sub8(val, 0),
+            0x3 => self.a = self.sub8(val, self.carry()),
+            0x4 => self.logic8(self.a & val, FLAG_H),
+            0x5 => self.logic8(self.a ^ val, 0),
+            0x6 => self.logic8(self.a | val, 0),
+            _   => { self.sub8(val, 0); }
+        }
+    }
 
+    fn add8(&mut self, val: u8, carry: u8) -> u8 {
+        let sum = self.a as u16 + val as u16 + carry as u16;
+        let result = sum as u8;
+        self.f = 0;
+        self.set_flag(FLAG_Z, result == 0);
+        self.set_flag(FLAG_H, (self.a & 0x0f) + (val & 0x0f) + carry > 0x0f);
+        self.set_flag(FLAG_C, sum > 0xff);
+        result
     }
 
-    fn decode_type_n(&self, instruction: &Instruction) {
-        println!("{}", instruction.mnemonic);
+    // Sets the flags for A - val - carry and returns the result; CP discards it.
+    fn sub8(&mut self, val: u8, carry: u8) -> u8 {
+        let result = self.a.wrapping_sub(val).wrapping_sub(carry);
+        self.f = FLAG_N;
+        self.set_flag(FLAG_Z, result == 0);
+        self.set_flag(FLAG_H, (self.a & 0x0f) < (val & 0x0f) + carry);
+        self.set_flag(FLAG_C, (self.a as u16) < val as u16 + carry as u16);
+        result
     }
 
-    fn decode_type_a16(&self, instruction: &Instruction, address: u16) {
-        println!("{} - Address: {:#06x}", instruction.mnemonic, address)
+    // Stores the result of AND, XOR or OR; only AND sets H.
+    fn logic8(&mut self, result: u8, h: u8) {
+        self.a = result;
+        self.f = if result == 0 { FLAG_Z } else { 0 } | h;
     }
 
-    fn ld_bc_a(&self) {
+    // Adjusts A to packed BCD after an ADD/ADC (N clear) or SUB/SBC (N set).
+    fn daa(&mut self) {
+        if !self.flag(FLAG_N) {
+            if self.flag(FLAG_C) || self.a > 0x99 {
+                self.a = self.a.wrapping_add(0x60);
+                self.set_flag(FLAG_C, true);
+            }
+            if self.flag(FLAG_H) || self.a & 0x0f > 0x09 {
+                self.a = self.a.wrapping_add(0x06);
+            }
+        } else {
+            if self.flag(FLAG_C) {
+                self.a = self.a.wrapping_sub(0x60);
+            }
+            if self.flag(FLAG_H) {
+                self.a = self.a.wrapping_sub(0x06);
+            }
+        }
+        self.set_flag(FLAG_Z, self.a == 0);
+        self.set_flag(FLAG_H, false);
+    }
 
+    fn push(&mut self, bus: &mut dyn BusTrait, value: u16) {
+        self.sp = self.sp.wrapping_sub(1);
+        bus.write(self.sp, (value >> 8) as u8);
+        self.sp = self.sp.wrapping_sub(1);
+        bus.write(self.sp, value as u8);
     }
 
-    fn inc_bc(&self) {
+    fn pop(&mut self, bus: &mut dyn BusTrait) -> u16 {
+        let low = bus.read(self.sp);
+        self.sp = self.sp.wrapping_add(1);
+        let high = bus.read(self.sp);
+        self.sp = self.sp.wrapping_add(1);
+        (high as u16) << 8 | low as u16
+    }
 
+    fn ld_bc_a(&self, bus: &mut dyn BusTrait) {
+        bus.write(self.bc(), self.a);
     }
 
-    fn ld_bc_d16(&self, b0: u8, b1: u8) {
+    fn inc_bc(&mut self) {
+        self.set_bc(self.bc().wrapping_add(1));
+    }
 
+    fn ld_bc_d16(&mut self, b0: u8, b1: u8) {
+        self.b = b1;
+        self.c = b0;
     }
 
-    fn undefined_instruction(&self) {
+    fn undefined_instruction(&mut self, opcode: u16) {
+        if self.trap_undefined {
+            self.undefined_opcode = Some(opcode);
+            return;
+        }
         println!("{:?}", self);
         panic!("Undefined Instruction!\n");
     }
@@ -21,8 +21,8 @@ pub struct Instruction {
 pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0x00 */ Instruction{encoding:Type::N,mnemonic: "NOP",cycles:4,length:1},
 /* 0x01 */ Instruction{encoding:Type::D16,mnemonic:"LDBCD16",cycles:12,length:3},
-/* 0x02 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x03 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0x02 */ Instruction{encoding:Type::N, mnemonic:"LD_BC_A",cycles:8,length:1},
+/* 0x03 */ Instruction{encoding:Type::N, mnemonic:"INC_BC",cycles:8,length:1},
 /* 0x04 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x05 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x06 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -58,7 +58,7 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0x24 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x25 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x26 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x27 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0x27 */ Instruction{encoding:Type::N, mnemonic:"DAA",cycles:4,length:1},
 /* 0x28 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x29 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x2a */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -89,7 +89,7 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0x43 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x44 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x45 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x46 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0x46 */ Instruction{encoding:Type::N, mnemonic:"LD_B_HL",cycles:8,length:1},
 /* 0x47 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x48 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x49 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -97,7 +97,7 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0x4b */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x4c */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x4d */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x4e */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0x4e */ Instruction{encoding:Type::N, mnemonic:"LD_C_HL",cycles:8,length:1},
 /* 0x4f */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x50 */ Instruction{encoding:Type::N, mnemonic:"LD_D_B",cycles:4,length:1},
 /* 0x51 */ Instruction{encoding:Type::N, mnemonic:"LD_D_C",cycles:4,length:1},
@@ -105,7 +105,7 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0x53 */ Instruction{encoding:Type::N, mnemonic:"LD_D_E",cycles:4,length:1},
 /* 0x54 */ Instruction{encoding:Type::N, mnemonic:"LD_D_H",cycles:4,length:1},
 /* 0x55 */ Instruction{encoding:Type::N, mnemonic:"LD_D_L",cycles:4,length:1},
-/* 0x56 */ Instruction{encoding:Type::N, mnemonic:"LD_D_HL",cycles:4,length:1},
+/* 0x56 */ Instruction{encoding:Type::N, mnemonic:"LD_D_HL",cycles:8,length:1},
 /* 0x57 */ Instruction{encoding:Type::N, mnemonic:"LD_D_A",cycles:4,length:1},
 /* 0x58 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x59 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -113,7 +113,7 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0x5b */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x5c */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x5d */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x5e */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0x5e */ Instruction{encoding:Type::N, mnemonic:"LD_E_HL",cycles:8,length:1},
 /* 0x5f */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x60 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x61 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -121,7 +121,7 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0x63 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x64 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x65 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x66 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0x66 */ Instruction{encoding:Type::N, mnemonic:"LD_H_HL",cycles:8,length:1},
 /* 0x67 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x68 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x69 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -129,7 +129,7 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0x6b */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x6c */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x6d */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x6e */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0x6e */ Instruction{encoding:Type::N, mnemonic:"LD_L_HL",cycles:8,length:1},
 /* 0x6f */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x70 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x71 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -145,79 +145,79 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0x7b */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x7c */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0x7d */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x7e */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0x7e */ Instruction{encoding:Type::N, mnemonic:"LD_A_HL",cycles:8,length:1},
 /* 0x7f */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x80 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x81 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x82 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x83 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x84 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x85 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x86 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x87 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x88 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x89 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x8a */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x8b */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x8c */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x8d */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x8e */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x8f */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x90 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x91 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x92 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x93 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x94 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x95 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x96 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x97 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x98 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x99 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x9a */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x9b */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x9c */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x9d */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x9e */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0x9f */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xa0 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xa1 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xa2 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xa3 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xa4 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xa5 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xa6 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xa7 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xa8 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xa9 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xaa */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xab */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xac */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xad */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xae */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xaf */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xb0 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xb1 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xb2 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xb3 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xb4 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xb5 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xb6 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xb7 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xb8 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xb9 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xba */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xbb */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xbc */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xbd */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xbe */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xbf */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0x80 */ Instruction{encoding:Type::N, mnemonic:"ADD_A_B",cycles:4,length:1},
+/* 0x81 */ Instruction{encoding:Type::N, mnemonic:"ADD_A_C",cycles:4,length:1},
+/* 0x82 */ Instruction{encoding:Type::N, mnemonic:"ADD_A_D",cycles:4,length:1},
+/* 0x83 */ Instruction{encoding:Type::N, mnemonic:"ADD_A_E",cycles:4,length:1},
+/* 0x84 */ Instruction{encoding:Type::N, mnemonic:"ADD_A_H",cycles:4,length:1},
+/* 0x85 */ Instruction{encoding:Type::N, mnemonic:"ADD_A_L",cycles:4,length:1},
+/* 0x86 */ Instruction{encoding:Type::N, mnemonic:"ADD_A_HL",cycles:8,length:1},
+/* 0x87 */ Instruction{encoding:Type::N, mnemonic:"ADD_A_A",cycles:4,length:1},
+/* 0x88 */ Instruction{encoding:Type::N, mnemonic:"ADC_A_B",cycles:4,length:1},
+/* 0x89 */ Instruction{encoding:Type::N, mnemonic:"ADC_A_C",cycles:4,length:1},
+/* 0x8a */ Instruction{encoding:Type::N, mnemonic:"ADC_A_D",cycles:4,length:1},
+/* 0x8b */ Instruction{encoding:Type::N, mnemonic:"ADC_A_E",cycles:4,length:1},
+/* 0x8c */ Instruction{encoding:Type::N, mnemonic:"ADC_A_H",cycles:4,length:1},
+/* 0x8d */ Instruction{encoding:Type::N, mnemonic:"ADC_A_L",cycles:4,length:1},
+/* 0x8e */ Instruction{encoding:Type::N, mnemonic:"ADC_A_HL",cycles:8,length:1},
+/* 0x8f */ Instruction{encoding:Type::N, mnemonic:"ADC_A_A",cycles:4,length:1},
+/* 0x90 */ Instruction{encoding:Type::N, mnemonic:"SUB_B",cycles:4,length:1},
+/* 0x91 */ Instruction{encoding:Type::N, mnemonic:"SUB_C",cycles:4,length:1},
+/* 0x92 */ Instruction{encoding:Type::N, mnemonic:"SUB_D",cycles:4,length:1},
+/* 0x93 */ Instruction{encoding:Type::N, mnemonic:"SUB_E",cycles:4,length:1},
+/* 0x94 */ Instruction{encoding:Type::N, mnemonic:"SUB_H",cycles:4,length:1},
+/* 0x95 */ Instruction{encoding:Type::N, mnemonic:"SUB_L",cycles:4,length:1},
+/* 0x96 */ Instruction{encoding:Type::N, mnemonic:"SUB_HL",cycles:8,length:1},
+/* 0x97 */ Instruction{encoding:Type::N, mnemonic:"SUB_A",cycles:4,length:1},
+/* 0x98 */ Instruction{encoding:Type::N, mnemonic:"SBC_A_B",cycles:4,length:1},
+/* 0x99 */ Instruction{encoding:Type::N, mnemonic:"SBC_A_C",cycles:4,length:1},
+/* 0x9a */ Instruction{encoding:Type::N, mnemonic:"SBC_A_D",cycles:4,length:1},
+/* 0x9b */ Instruction{encoding:Type::N, mnemonic:"SBC_A_E",cycles:4,length:1},
+/* 0x9c */ Instruction{encoding:Type::N, mnemonic:"SBC_A_H",cycles:4,length:1},
+/* 0x9d */ Instruction{encoding:Type::N, mnemonic:"SBC_A_L",cycles:4,length:1},
+/* 0x9e */ Instruction{encoding:Type::N, mnemonic:"SBC_A_HL",cycles:8,length:1},
+/* 0x9f */ Instruction{encoding:Type::N, mnemonic:"SBC_A_A",cycles:4,length:1},
+/* 0xa0 */ Instruction{encoding:Type::N, mnemonic:"AND_B",cycles:4,length:1},
+/* 0xa1 */ Instruction{encoding:Type::N, mnemonic:"AND_C",cycles:4,length:1},
+/* 0xa2 */ Instruction{encoding:Type::N, mnemonic:"AND_D",cycles:4,length:1},
+/* 0xa3 */ Instruction{encoding:Type::N, mnemonic:"AND_E",cycles:4,length:1},
+/* 0xa4 */ Instruction{encoding:Type::N, mnemonic:"AND_H",cycles:4,length:1},
+/* 0xa5 */ Instruction{encoding:Type::N, mnemonic:"AND_L",cycles:4,length:1},
+/* 0xa6 */ Instruction{encoding:Type::N, mnemonic:"AND_HL",cycles:8,length:1},
+/* 0xa7 */ Instruction{encoding:Type::N, mnemonic:"AND_A",cycles:4,length:1},
+/* 0xa8 */ Instruction{encoding:Type::N, mnemonic:"XOR_B",cycles:4,length:1},
+/* 0xa9 */ Instruction{encoding:Type::N, mnemonic:"XOR_C",cycles:4,length:1},
+/* 0xaa */ Instruction{encoding:Type::N, mnemonic:"XOR_D",cycles:4,length:1},
+/* 0xab */ Instruction{encoding:Type::N, mnemonic:"XOR_E",cycles:4,length:1},
+/* 0xac */ Instruction{encoding:Type::N, mnemonic:"XOR_H",cycles:4,length:1},
+/* 0xad */ Instruction{encoding:Type::N, mnemonic:"XOR_L",cycles:4,length:1},
+/* 0xae */ Instruction{encoding:Type::N, mnemonic:"XOR_HL",cycles:8,length:1},
+/* 0xaf */ Instruction{encoding:Type::N, mnemonic:"XOR_A",cycles:4,length:1},
+/* 0xb0 */ Instruction{encoding:Type::N, mnemonic:"OR_B",cycles:4,length:1},
+/* 0xb1 */ Instruction{encoding:Type::N, mnemonic:"OR_C",cycles:4,length:1},
+/* 0xb2 */ Instruction{encoding:Type::N, mnemonic:"OR_D",cycles:4,length:1},
+/* 0xb3 */ Instruction{encoding:Type::N, mnemonic:"OR_E",cycles:4,length:1},
+/* 0xb4 */ Instruction{encoding:Type::N, mnemonic:"OR_H",cycles:4,length:1},
+/* 0xb5 */ Instruction{encoding:Type::N, mnemonic:"OR_L",cycles:4,length:1},
+/* 0xb6 */ Instruction{encoding:Type::N, mnemonic:"OR_HL",cycles:8,length:1},
+/* 0xb7 */ Instruction{encoding:Type::N, mnemonic:"OR_A",cycles:4,length:1},
+/* 0xb8 */ Instruction{encoding:Type::N, mnemonic:"CP_B",cycles:4,length:1},
+/* 0xb9 */ Instruction{encoding:Type::N, mnemonic:"CP_C",cycles:4,length:1},
+/* 0xba */ Instruction{encoding:Type::N, mnemonic:"CP_D",cycles:4,length:1},
+/* 0xbb */ Instruction{encoding:Type::N, mnemonic:"CP_E",cycles:4,length:1},
+/* 0xbc */ Instruction{encoding:Type::N, mnemonic:"CP_H",cycles:4,length:1},
+/* 0xbd */ Instruction{encoding:Type::N, mnemonic:"CP_L",cycles:4,length:1},
+/* 0xbe */ Instruction{encoding:Type::N, mnemonic:"CP_HL",cycles:8,length:1},
+/* 0xbf */ Instruction{encoding:Type::N, mnemonic:"CP_A",cycles:4,length:1},
 /* 0xc0 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xc1 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xc1 */ Instruction{encoding:Type::N, mnemonic:"POP_BC",cycles:12,length:1},
 /* 0xc2 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xc3 */ Instruction{encoding:Type::A16, mnemonic:"JP",cycles:16,length:3},
 /* 0xc4 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xc5 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xc6 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xc5 */ Instruction{encoding:Type::N, mnemonic:"PUSH_BC",cycles:16,length:1},
+/* 0xc6 */ Instruction{encoding:Type::D8, mnemonic:"ADD_A_D8",cycles:8,length:2},
 /* 0xc7 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xc8 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xc9 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -225,15 +225,15 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0xcb */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xcc */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xcd */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xce */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xce */ Instruction{encoding:Type::D8, mnemonic:"ADC_A_D8",cycles:8,length:2},
 /* 0xcf */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xd0 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xd1 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xd1 */ Instruction{encoding:Type::N, mnemonic:"POP_DE",cycles:12,length:1},
 /* 0xd2 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xd3 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xd4 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xd5 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xd6 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xd5 */ Instruction{encoding:Type::N, mnemonic:"PUSH_DE",cycles:16,length:1},
+/* 0xd6 */ Instruction{encoding:Type::D8, mnemonic:"SUB_D8",cycles:8,length:2},
 /* 0xd7 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xd8 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xd9 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -241,15 +241,15 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0xdb */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xdc */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xdd */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xde */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xde */ Instruction{encoding:Type::D8, mnemonic:"SBC_A_D8",cycles:8,length:2},
 /* 0xdf */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xe0 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xe1 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xe1 */ Instruction{encoding:Type::N, mnemonic:"POP_HL",cycles:12,length:1},
 /* 0xe2 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xe3 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xe4 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xe5 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xe6 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xe5 */ Instruction{encoding:Type::N, mnemonic:"PUSH_HL",cycles:16,length:1},
+/* 0xe6 */ Instruction{encoding:Type::D8, mnemonic:"AND_D8",cycles:8,length:2},
 /* 0xe7 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xe8 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xe9 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -257,15 +257,15 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0xeb */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xec */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xed */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xee */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xee */ Instruction{encoding:Type::D8, mnemonic:"XOR_D8",cycles:8,length:2},
 /* 0xef */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xf0 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xf1 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xf1 */ Instruction{encoding:Type::N, mnemonic:"POP_AF",cycles:12,length:1},
 /* 0xf2 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xf3 */ Instruction{encoding:Type::N, mnemonic:"DI",cycles:4,length:1},
 /* 0xf4 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xf5 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xf6 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xf5 */ Instruction{encoding:Type::N, mnemonic:"PUSH_AF",cycles:16,length:1},
+/* 0xf6 */ Instruction{encoding:Type::D8, mnemonic:"OR_D8",cycles:8,length:2},
 /* 0xf7 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xf8 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xf9 */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
@@ -273,7 +273,7 @@ pub const INSTRUCTION_TABLE: [Instruction; 256] = [
 /* 0xfb */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xfc */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 /* 0xfd */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
-/* 0xfe */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
+/* 0xfe */ Instruction{encoding:Type::D8, mnemonic:"CP_D8",cycles:8,length:2},
 /* 0xff */ Instruction{encoding:Type::N, mnemonic:"NOP",cycles:4,length:1},
 ];
 
@@ -348,7 +348,7 @@ pub const INSTRUCTION_TABLE_CB: [Instruction; 256] = [
 /*0x43*/ Instruction{encoding:Type::CB,mnemonic:"BIT0_E",cycles:8,length:2},
 /*0x44*/ Instruction{encoding:Type::CB,mnemonic:"BIT0_H",cycles:8,length:2},
 /*0x45*/ Instruction{encoding:Type::CB,mnemonic:"BIT0_L",cycles:8,length:2},
-/*0x46*/ Instruction{encoding:Type::CB,mnemonic:"BIT0_HL",cycles:16,length:2},
+/*0x46*/ Instruction{encoding:Type::CB,mnemonic:"BIT0_HL",cycles:12,length:2},
 /*0x47*/ Instruction{encoding:Type::CB,mnemonic:"BIT0_A",cycles:8,length:2},
 /*0x48*/ Instruction{encoding:Type::CB,mnemonic:"BIT1_B",cycles:8,length:2},
 /*0x49*/ Instruction{encoding:Type::CB,mnemonic:"BIT1_C",cycles:8,length:2},
@@ -356,7 +356,7 @@ pub const INSTRUCTION_TABLE_CB: [Instruction; 256] = [
 /*0x4b*/ Instruction{encoding:Type::CB,mnemonic:"BIT1_E",cycles:8,length:2},
 /*0x4c*/ Instruction{encoding:Type::CB,mnemonic:"BIT1_H",cycles:8,length:2},
 /*0x4d*/ Instruction{encoding:Type::CB,mnemonic:"BIT1_L",cycles:8,length:2},
-/*0x4e*/ Instruction{encoding:Type::CB,mnemonic:"BIT1_HL",cycles:16,length:2},
+/*0x4e*/ Instruction{encoding:Type::CB,mnemonic:"BIT1_HL",cycles:12,length:2},
 /*0x4f*/ Instruction{encoding:Type::CB,mnemonic:"BIT1_A",cycles:8,length:2},
 /*0x50*/ Instruction{encoding:Type::CB,mnemonic:"BIT2_B",cycles:8,length:2},
 /*0x51*/ Instruction{encoding:Type::CB,mnemonic:"BIT2_C",cycles:8,length:2},
@@ -364,7 +364,7 @@ pub const INSTRUCTION_TABLE_CB: [Instruction; 256] = [
 /*0x53*/ Instruction{encoding:Type::CB,mnemonic:"BIT2_E",cycles:8,length:2},
 /*0x54*/ Instruction{encoding:Type::CB,mnemonic:"BIT2_H",cycles:8,length:2},
 /*0x55*/ Instruction{encoding:Type::CB,mnemonic:"BIT2_L",cycles:8,length:2},
-/*0x56*/ Instruction{encoding:Type::CB,mnemonic:"BIT2_HL",cycles:16,length:2},
+/*0x56*/ Instruction{encoding:Type::CB,mnemonic:"BIT2_HL",cycles:12,length:2},
 /*0x57*/ Instruction{encoding:Type::CB,mnemonic:"BIT2_A",cycles:8,length:2},
 /*0x58*/ Instruction{encoding:Type::CB,mnemonic:"BIT3_B",cycles:8,length:2},
 /*0x59*/ Instruction{encoding:Type::CB,mnemonic:"BIT3_C",cycles:8,length:2},
@@ -372,7 +372,7 @@ pub const INSTRUCTION_TABLE_CB: [Instruction; 256] = [
 /*0x5b*/ Instruction{encoding:Type::CB,mnemonic:"BIT3_E",cycles:8,length:2},
 /*0x5c*/ Instruction{encoding:Type::CB,mnemonic:"BIT3_H",cycles:8,length:2},
 /*0x5d*/ Instruction{encoding:Type::CB,mnemonic:"BIT3_L",cycles:8,length:2},
-/*0x5e*/ Instruction{encoding:Type::CB,mnemonic:"BIT3_HL",cycles:16,length:2},
+/*0x5e*/ Instruction{encoding:Type::CB,mnemonic:"BIT3_HL",cycles:12,length:2},
 /*0x5f*/ Instruction{encoding:Type::CB,mnemonic:"BIT3_A",cycles:8,length:2},
 /*0x60*/ Instruction{encoding:Type::CB,mnemonic:"BIT4_B",cycles:8,length:2},
 /*0x61*/ Instruction{encoding:Type::CB,mnemonic:"BIT4_C",cycles:8,length:2},
@@ -380,7 +380,7 @@ pub const INSTRUCTION_TABLE_CB: [Instruction; 256] = [
 /*0x63*/ Instruction{encoding:Type::CB,mnemonic:"BIT4_E",cycles:8,length:2},
 /*0x64*/ Instruction{encoding:Type::CB,mnemonic:"BIT4_H",cycles:8,length:2},
 /*0x65*/ Instruction{encoding:Type::CB,mnemonic:"BIT4_L",cycles:8,length:2},
-/*0x66*/ Instruction{encoding:Type::CB,mnemonic:"BIT4_HL",cycles:16,length:2},
+/*0x66*/ Instruction{encoding:Type::CB,mnemonic:"BIT4_HL",cycles:12,length:2},
 /*0x67*/ Instruction{encoding:Type::CB,mnemonic:"BIT4_A",cycles:8,length:2},
 /*0x68*/ Instruction{encoding:Type::CB,mnemonic:"BIT5_B",cycles:8,length:2},
 /*0x69*/ Instruction{encoding:Type::CB,mnemonic:"BIT5_C",cycles:8,length:2},
@@ -388,7 +388,7 @@ pub const INSTRUCTION_TABLE_CB: [Instruction; 256] = [
 /*0x6b*/ Instruction{encoding:Type::CB,mnemonic:"BIT5_E",cycles:8,length:2},
 /*0x6c*/ Instruction{encoding:Type::CB,mnemonic:"BIT5_H",cycles:8,length:2},
 /*0x6d*/ Instruction{encoding:Type::CB,mnemonic:"BIT5_L",cycles:8,length:2},
-/*0x6e*/ Instruction{encoding:Type::CB,mnemonic:"BIT5_HL",cycles:16,length:2},
+/*0x6e*/ Instruction{encoding:Type::CB,mnemonic:"BIT5_HL",cycles:12,length:2},
 /*0x6f*/ Instruction{encoding:Type::CB,mnemonic:"BIT5_A",cycles:8,length:2},
 /*0x70*/ Instruction{encoding:Type::CB,mnemonic:"BIT6_B",cycles:8,length:2},
 /*0x71*/ Instruction{encoding:Type::CB,mnemonic:"BIT6_C",cycles:8,length:2},
@@ -396,7 +396,7 @@ pub const INSTRUCTION_TABLE_CB: [Instruction; 256] = [
 /*0x73*/ Instruction{encoding:Type::CB,mnemonic:"BIT6_E",cycles:8,length:2},
 /*0x74*/ Instruction{encoding:Type::CB,mnemonic:"BIT6_H",cycles:8,length:2},
 /*0x75*/ Instruction{encoding:Type::CB,mnemonic:"BIT6_L",cycles:8,length:2},
-/*0x76*/ Instruction{encoding:Type::CB,mnemonic:"BIT6_HL",cycles:16,length:2},
+/*0x76*/ Instruction{encoding:Type::CB,mnemonic:"BIT6_HL",cycles:12,length:2},
 /*0x77*/ Instruction{encoding:Type::CB,mnemonic:"BIT6_A",cycles:8,length:2},
 /*0x78*/ Instruction{encoding:Type::CB,mnemonic:"BIT7_B",cycles:8,length:2},
 /*0x79*/ Instruction{encoding:Type::CB,mnemonic:"BIT7_C",cycles:8,length:2},
@@ -404,7 +404,7 @@ pub const INSTRUCTION_TABLE_CB: [Instruction; 256] = [
 /*0x7b*/ Instruction{encoding:Type::CB,mnemonic:"BIT7_E",cycles:8,length:2},
 /*0x7c*/ Instruction{encoding:Type::CB,mnemonic:"BIT7_H",cycles:8,length:2},
 /*0x7d*/ Instruction{encoding:Type::CB,mnemonic:"BIT7_L",cycles:8,length:2},
-/*0x7e*/ Instruction{encoding:Type::CB,mnemonic:"BIT7_HL",cycles:16,length:2},
+/*0x7e*/ Instruction{encoding:Type::CB,mnemonic:"BIT7_HL",cycles:12,length:2},
 /*0x7f*/ Instruction{encoding:Type::CB,mnemonic:"BIT7_A",cycles:8,length:2},
 /*0x80*/ Instruction{encoding:Type::CB,mnemonic:"RES0_B",cycles:8,length:2},
 /*0x81*/ Instruction{encoding:Type::CB,mnemonic:"RES0_C",cycles:8,length:2},
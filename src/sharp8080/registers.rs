@@ -0,0 +1,44 @@
+// Bit positions of the flags in F; the low nibble of F always reads zero.
+pub const FLAG_Z: u8 = 0x80;
+pub const FLAG_N: u8 = 0x40;
+pub const FLAG_H: u8 = 0x20;
+pub const FLAG_C: u8 = 0x10;
+
+/// A snapshot of the CPU's register file, as returned by
+/// `Sharp8080::registers` and accepted by `Sharp8080::set_registers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Registers {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+}
+
+impl Registers {
+    pub fn af(&self) -> u16 {
+        (self.a as u16) << 8 | self.f as u16
+    }
+
+    pub fn bc(&self) -> u16 {
+        (self.b as u16) << 8 | self.c as u16
+    }
+
+    pub fn de(&self) -> u16 {
+        (self.d as u16) << 8 | self.e as u16
+    }
+
+    pub fn hl(&self) -> u16 {
+        (self.h as u16) << 8 | self.l as u16
+    }
+
+    pub fn flag(&self, flag: u8) -> bool {
+        self.f & flag != 0
+    }
+}
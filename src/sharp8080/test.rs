@@ -9,7 +9,7 @@ impl BusTrait for BusTest {
         self.memory[addr as usize] = data;
     }
     fn read(&self, addr: u16) -> u8 {
-        return self.memory[addr as usize];
+        self.memory[addr as usize]
     }
 }
 
@@ -44,10 +44,7 @@ macro_rules! get_reg {
 macro_rules! assert_state {
     ($cpu: expr, $reg: expr, $state: expr) => {{
         assert!($reg == $state.reg_p);
-        assert!($cpu.cf == $state.cf);
-        assert!($cpu.hf == $state.hf);
-        assert!($cpu.nf == $state.nf);
-        assert!($cpu.zf == $state.zf);
+        assert!($cpu.f == $state.zf << 7 | $state.nf << 6 | $state.hf << 5 | $state.cf << 4);
     }}
 }
 
@@ -83,6 +80,17 @@ macro_rules! run_test {
     }}
 }
 
+// The Z, N, H and C flags as 0 or 1, in that order.
+fn flag_bits(cpu: &Sharp8080) -> [u8; 4] {
+    [FLAG_Z, FLAG_N, FLAG_H, FLAG_C].map(|flag| cpu.flag(flag) as u8)
+}
+
+fn set_flag_bits(cpu: &mut Sharp8080, bits: [u8; 4]) {
+    for (flag, bit) in [FLAG_Z, FLAG_N, FLAG_H, FLAG_C].into_iter().zip(bits) {
+        cpu.set_flag(flag, bit == 1);
+    }
+}
+
 struct State {
     reg: u8,
     reg_p: u8,
@@ -111,3 +119,309 @@ fn test_cb_rlc() {
     { State { reg: 0x00, reg_p: 0x00,            zf: 1, nf: 0, hf: 0, cf: 0 }}];
     run_test!(cpu, bus, STATE);
 }
+
+#[test]
+fn test_ld_b_b_breakpoint() {
+    let mut cpu = Sharp8080::new(0x0000);
+    let mut bus = BusTest::new();
+    bus.write(0x0000, 0x40);
+    bus.write(0x0001, 0x40);
+    cpu.b = 0x12;
+    let opcode = cpu.fetch_opcode(&bus);
+    cpu.execute(&mut bus, opcode);
+    assert!(!cpu.take_breakpoint());
+    cpu.set_ld_b_b_breakpoint(true);
+    let opcode = cpu.fetch_opcode(&bus);
+    cpu.execute(&mut bus, opcode);
+    assert_eq!(cpu.b(), 0x12);
+    assert_eq!(cpu.pc(), 0x0002);
+    assert!(cpu.take_breakpoint());
+    assert!(!cpu.take_breakpoint());
+}
+
+// Reference model for the CB instructions: returns the result (None when the
+// operand is not written back) and the Z/N/H/C flags for `op` applied to
+// `val` with the incoming flags `flags` (Z, N, H, C).
+fn cb_reference(op: u8, val: u8, flags: [u8; 4]) -> (Option<u8>, [u8; 4]) {
+    let bit = (op >> 3) & 0x07;
+    let carry_in = flags[3] as u16;
+    let wide = val as u16;
+    let (result, carry) = match op >> 3 {
+        0x00 => ((wide << 1 | wide >> 7) as u8, wide >> 7),
+        0x01 => ((wide >> 1 | wide << 7) as u8, wide & 1),
+        0x02 => ((wide << 1 | carry_in) as u8, wide >> 7),
+        0x03 => ((wide >> 1 | carry_in << 7) as u8, wide & 1),
+        0x04 => ((wide << 1) as u8, wide >> 7),
+        0x05 => (((wide >> 1) | (wide & 0x80)) as u8, wide & 1),
+        0x06 => (((wide << 4) | (wide >> 4)) as u8, 0),
+        0x07 => ((wide >> 1) as u8, wide & 1),
+        0x08..=0x0F => {
+            let zero = (val >> bit) & 1 == 0;
+            return (None, [zero as u8, 0, 1, flags[3]]);
+        }
+        0x10..=0x17 => return (Some(val & !(1 << bit)), flags),
+        _ => return (Some(val | (1 << bit)), flags),
+    };
+    (Some(result), [(result == 0) as u8, 0, 0, carry as u8])
+}
+
+#[test]
+fn test_cb_exhaustive() {
+    const HL: u16 = 0xC123;
+    const OTHERS: [u8; 8] = [0x11, 0x22, 0x33, 0x44, 0xC1, 0x23, 0x66, 0x77];
+    let mut bus = BusTest::new();
+    for op in 0x00..=0xFF_u8 {
+        let target = (op & 0x07) as usize;
+        let instruction = &INSTRUCTION_TABLE_CB[op as usize];
+        let expected_cycles = match (target, op >> 6) {
+            (6, 1) => 12,
+            (6, _) => 16,
+            _ => 8,
+        };
+        assert_eq!(instruction.cycles, expected_cycles, "CB {:02X} cycles", op);
+        for val in 0x00..=0xFF_u8 {
+            for flags in [[0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 1], [1, 1, 1, 0]] {
+                let mut cpu = Sharp8080::new(0x0100);
+                bus.write(0x0100, 0xCB);
+                bus.write(0x0101, op);
+                let mut regs = OTHERS;
+                if target != 6 {
+                    regs[target] = val;
+                }
+                cpu.b = regs[0];
+                cpu.c = regs[1];
+                cpu.d = regs[2];
+                cpu.e = regs[3];
+                cpu.h = regs[4];
+                cpu.l = regs[5];
+                cpu.a = regs[7];
+                bus.write(HL, if target == 6 { val } else { 0x5A });
+                set_flag_bits(&mut cpu, flags);
+
+                let opcode = cpu.fetch_opcode(&bus);
+                assert_eq!(opcode, 0xCB00 | op as u16);
+                let cycles = cpu.execute(&mut bus, opcode);
+                assert_eq!(cycles, expected_cycles);
+                assert_eq!(cpu.pc, 0x0102);
+
+                let (result, expected_flags) = cb_reference(op, val, flags);
+                let mut expected = regs;
+                let mut expected_hl = if target == 6 { val } else { 0x5A };
+                if let Some(result) = result {
+                    if target == 6 {
+                        expected_hl = result;
+                    } else {
+                        expected[target] = result;
+                    }
+                }
+                let context = format!("CB {:02X} value {:02X} flags {:?}", op, val, flags);
+                assert_eq!([cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l, 0x66, cpu.a], expected, "{} registers", context);
+                assert_eq!(bus.read(HL), expected_hl, "{} (HL)", context);
+                assert_eq!(flag_bits(&cpu), expected_flags, "{} flags", context);
+            }
+        }
+    }
+}
+
+// Reference model for the 8-bit ALU: returns A and the Z/N/H/C flags after
+// operation `alu` (ADD, ADC, SUB, SBC, AND, XOR, OR, CP) of `val` on `a`.
+// Half-carry and carry are derived from full-width arithmetic rather than
+// nibble comparisons.
+fn alu_reference(alu: u8, a: u8, val: u8, carry: u8) -> (u8, [u8; 4]) {
+    let (a32, val32, carry32) = (a as i32, val as i32, carry as i32);
+    let (result, n, h, c) = match alu {
+        0 | 1 => {
+            let carry32 = if alu == 1 { carry32 } else { 0 };
+            let sum = a32 + val32 + carry32;
+            (sum, 0, (a32 ^ val32 ^ sum) & 0x10 != 0, sum > 0xff)
+        }
+        2 | 3 | 7 => {
+            let carry32 = if alu == 3 { carry32 } else { 0 };
+            let diff = a32 - val32 - carry32;
+            (diff, 1, (a32 ^ val32 ^ diff) & 0x10 != 0, diff < 0)
+        }
+        4 => (a32 & val32, 0, true, false),
+        5 => (a32 ^ val32, 0, false, false),
+        _ => (a32 | val32, 0, false, false),
+    };
+    let result = (result & 0xff) as u8;
+    let a = if alu == 7 { a } else { result };
+    (a, [(result == 0) as u8, n, h as u8, c as u8])
+}
+
+// A small xorshift generator for sampling operands and flags.
+struct XorShift(u32);
+
+impl XorShift {
+    fn next(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 24) as u8
+    }
+}
+
+#[test]
+fn test_alu_register_exhaustive() {
+    let mut bus = BusTest::new();
+    let mut cpu = Sharp8080::new(0x0000);
+    for alu in 0..8_u8 {
+        // Operand from B, all values of A, B and the incoming carry.
+        let opcode = 0x80 | (alu as u16) << 3;
+        assert_eq!(INSTRUCTION_TABLE[opcode as usize].cycles, 4);
+        for a in 0x00..=0xFF_u8 {
+            for val in 0x00..=0xFF_u8 {
+                for carry in 0..2 {
+                    cpu.pc = 0;
+                    cpu.a = a;
+                    cpu.b = val;
+                    set_flag_bits(&mut cpu, [1 - carry, 1 - carry, 1 - carry, carry]);
+                    let cycles = cpu.execute(&mut bus, opcode);
+                    let (expected_a, flags) = alu_reference(alu, a, val, carry);
+                    assert_eq!(cycles, 4);
+                    assert_eq!(cpu.pc, 1);
+                    assert_eq!(cpu.b, val);
+                    assert_eq!((cpu.a, flag_bits(&cpu)), (expected_a, flags),
+                        "opcode {:02X} A={:02X} B={:02X} C={}", opcode, a, val, carry);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_alu_operand_forms() {
+    let mut bus = BusTest::new();
+    let mut cpu = Sharp8080::new(0x0000);
+    let mut rng = XorShift(0x1234_5678);
+    for alu in 0..8_u8 {
+        for _ in 0..2000 {
+            let (a, val, carry) = (rng.next(), rng.next(), rng.next() & 1);
+            let src = rng.next() % 8;
+            let register = 0x80 | (alu as u16) << 3 | src as u16;
+            let immediate = 0xC6 | (alu as u16) << 3;
+            for opcode in [register, immediate] {
+                cpu.pc = 0x0200;
+                cpu.b = 0x01;
+                cpu.c = 0x02;
+                cpu.d = 0x03;
+                cpu.e = 0x04;
+                cpu.h = 0xC0;
+                cpu.l = 0x00;
+                cpu.a = a;
+                cpu.set_flag(FLAG_C, carry == 1);
+                bus.write(0xC000, val);
+                bus.write(0x0201, val);
+                // Every register source except (HL) and the immediate is
+                // loaded with the operand; A op A uses A itself.
+                let operand = match (opcode == immediate, src) {
+                    (true, _) | (false, 6) => val,
+                    (false, 7) => a,
+                    (false, r) => {
+                        let value = if r == 4 || r == 5 { val } else { val ^ 0x5A };
+                        match r {
+                            0 => cpu.b = value,
+                            1 => cpu.c = value,
+                            2 => cpu.d = value,
+                            3 => cpu.e = value,
+                            4 => cpu.h = value,
+                            _ => cpu.l = value,
+                        }
+                        value
+                    }
+                };
+                let (expected_a, flags) = alu_reference(alu, a, operand, carry);
+                let cycles = cpu.execute(&mut bus, opcode);
+                let context = format!("opcode {:02X} A={:02X} operand={:02X} C={}", opcode, a, operand, carry);
+                assert_eq!((cpu.a, flag_bits(&cpu)), (expected_a, flags), "{}", context);
+                let (length, expected_cycles) = if opcode == immediate {
+                    (2, 8)
+                } else if src == 6 {
+                    (1, 8)
+                } else {
+                    (1, 4)
+                };
+                assert_eq!((cpu.pc, cycles), (0x0200 + length, expected_cycles), "{}", context);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_daa_bcd_arithmetic() {
+    let mut bus = BusTest::new();
+    let mut cpu = Sharp8080::new(0x0000);
+    let bcd = |n: u32| (((n / 10) << 4) | (n % 10)) as u8;
+    for x in 0..100_u32 {
+        for y in 0..100_u32 {
+            for carry in 0..2_u32 {
+                // ADC then DAA adds in decimal.
+                cpu.a = bcd(x);
+                cpu.b = bcd(y);
+                cpu.set_flag(FLAG_C, carry == 1);
+                cpu.execute(&mut bus, 0x88);
+                cpu.execute(&mut bus, 0x27);
+                let sum = x + y + carry;
+                assert_eq!((cpu.a, cpu.carry()), (bcd(sum % 100), (sum >= 100) as u8), "{} + {} + {}", x, y, carry);
+                assert_eq!(flag_bits(&cpu)[..3], [(sum % 100 == 0) as u8, 0, 0]);
+
+                // SBC then DAA subtracts in decimal.
+                cpu.a = bcd(x);
+                cpu.b = bcd(y);
+                cpu.set_flag(FLAG_C, carry == 1);
+                cpu.execute(&mut bus, 0x98);
+                cpu.execute(&mut bus, 0x27);
+                let diff = (x + 200 - y - carry) % 100;
+                assert_eq!((cpu.a, cpu.carry()), (bcd(diff), (x < y + carry) as u8), "{} - {} - {}", x, y, carry);
+                assert_eq!(flag_bits(&cpu)[..3], [(diff == 0) as u8, 1, 0]);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_register_pairs() {
+    let mut cpu = Sharp8080::new(0x0000);
+    cpu.set_af(0x12FF);
+    cpu.set_bc(0x3456);
+    cpu.set_de(0x789A);
+    cpu.set_hl(0xBCDE);
+    assert_eq!((cpu.a, cpu.f()), (0x12, 0xF0));
+    assert_eq!([cpu.af(), cpu.bc(), cpu.de(), cpu.hl()], [0x12F0, 0x3456, 0x789A, 0xBCDE]);
+    assert_eq!(flag_bits(&cpu), [1, 1, 1, 1]);
+    cpu.set_f(0xA5);
+    assert_eq!(cpu.f(), 0xA0);
+    assert_eq!(flag_bits(&cpu), [1, 0, 1, 0]);
+
+    let registers = cpu.registers();
+    assert_eq!((registers.af(), registers.hl()), (0x12A0, 0xBCDE));
+    assert!(registers.flag(FLAG_Z) && !registers.flag(FLAG_C));
+    let mut other = Sharp8080::new(0x1234);
+    other.set_registers(&Registers { f: 0x3F, ..registers });
+    assert_eq!(other.registers(), Registers { f: 0x30, ..registers });
+}
+
+#[test]
+fn test_push_pop() {
+    let mut cpu = Sharp8080::new(0x0000);
+    let mut bus = BusTest::new();
+    cpu.sp = 0xD000;
+    cpu.set_af(0x12F0);
+    cpu.set_bc(0x3456);
+    cpu.set_de(0x789A);
+    cpu.set_hl(0xBCDE);
+    for opcode in [0xF5, 0xC5, 0xD5, 0xE5] {
+        assert_eq!(cpu.execute(&mut bus, opcode), 16);
+    }
+    assert_eq!(cpu.sp, 0xCFF8);
+    assert_eq!([bus.read(0xCFFF), bus.read(0xCFFE)], [0x12, 0xF0]);
+    assert_eq!([bus.read(0xCFF9), bus.read(0xCFF8)], [0xBC, 0xDE]);
+
+    // Pop everything back rotated by one pair: AF <- HL, HL <- DE, ...
+    for opcode in [0xF1, 0xE1, 0xD1, 0xC1] {
+        assert_eq!(cpu.execute(&mut bus, opcode), 12);
+    }
+    assert_eq!(cpu.sp, 0xD000);
+    assert_eq!([cpu.af(), cpu.hl(), cpu.de(), cpu.bc()], [0xBCD0, 0x789A, 0x3456, 0x12F0]);
+    assert_eq!(cpu.pc, 8);
+}
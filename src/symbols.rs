@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Symbols loaded from an RGBDS or no$gmb style .sym file, where each line
+/// holds a `BB:AAAA Name` pair of hexadecimal bank and address followed by
+/// the symbol name. Comments start with `;`.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    // Keyed by (address, bank) so every bank's symbol for an address can be
+    // found with a single range query.
+    names: BTreeMap<(u16, u16), String>,
+    locations: HashMap<String, (u16, u16)>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    pub fn parse(text: &str) -> Result<SymbolTable, String> {
+        let mut table = SymbolTable::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let location = fields.next().unwrap_or("");
+            let name = fields.next().ok_or(format!("line {}: missing symbol name", number + 1))?;
+            let (bank, addr) = parse_location(location)
+                .ok_or(format!("line {}: invalid location '{}'", number + 1, location))?;
+            table.insert(bank, addr, name);
+        }
+        Ok(table)
+    }
+
+    pub fn load(path: &Path) -> Result<SymbolTable, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        SymbolTable::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// The symbol file conventionally written next to a ROM: the ROM path
+    /// with a .sym extension.
+    pub fn path_for_rom(rom: &Path) -> PathBuf {
+        rom.with_extension("sym")
+    }
+
+    /// Adds a symbol. When several symbols share a location the first one
+    /// added names it, but every name can still be looked up.
+    pub fn insert(&mut self, bank: u16, addr: u16, name: &str) {
+        self.names.entry((addr, bank)).or_insert_with(|| name.to_string());
+        self.locations.entry(name.to_string()).or_insert((bank, addr));
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// The symbol at an exact bank and address.
+    pub fn name(&self, bank: u16, addr: u16) -> Option<&str> {
+        self.names.get(&(addr, bank)).map(String::as_str)
+    }
+
+    /// The symbol for an address as the CPU currently sees it, with
+    /// `rom_bank` mapped at 0x4000-0x7FFF. Outside switchable ROM, where the
+    /// DMG has no other banking, a symbol from any bank matches, preferring
+    /// bank 0.
+    pub fn name_at(&self, addr: u16, rom_bank: u16) -> Option<&str> {
+        if (0x4000..0x8000).contains(&addr) {
+            return self.name(rom_bank, addr);
+        }
+        if addr < 0x4000 {
+            return self.name(0, addr);
+        }
+        self.names.range((addr, 0)..=(addr, u16::MAX)).next().map(|(_, name)| name.as_str())
+    }
+
+    /// The bank and address of a symbol.
+    pub fn lookup(&self, name: &str) -> Option<(u16, u16)> {
+        self.locations.get(name).copied()
+    }
+
+    /// Resolves a symbol name, a `BB:AAAA` location or a hexadecimal
+    /// address (`$AAAA`, `0xAAAA` or bare `AAAA`) to an optional bank and an
+    /// address.
+    pub fn resolve(&self, text: &str) -> Option<(Option<u16>, u16)> {
+        if let Some((bank, addr)) = self.lookup(text) {
+            return Some((Some(bank), addr));
+        }
+        if text.contains(':') {
+            return parse_location(text).map(|(bank, addr)| (Some(bank), addr));
+        }
+        parse_address(text).map(|addr| (None, addr))
+    }
+
+    /// Every symbol, ordered by address and then bank.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u16, &str)> {
+        self.names.iter().map(|(&(addr, bank), name)| (bank, addr, name.as_str()))
+    }
+}
+
+/// Parses a hexadecimal address written as `$AAAA`, `0xAAAA` or `AAAA`.
+pub fn parse_address(text: &str) -> Option<u16> {
+    let digits = text.strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+fn parse_location(text: &str) -> Option<(u16, u16)> {
+    let (bank, addr) = text.split_once(':')?;
+    Some((u16::from_str_radix(bank, 16).ok()?, parse_address(addr)?))
+}
@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+use crate::tracer::doctor_line;
+use crate::GameBoy;
+
+/// A CPU write to memory and the instruction that made it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub pc: u16,
+    pub addr: u16,
+    pub value: u8,
+}
+
+/// The first instruction where the emulator's trace differs from the
+/// reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The 1-based line number in the reference log.
+    pub line: usize,
+    pub expected: String,
+    pub actual: String,
+    /// The matching lines leading up to the divergence, oldest first.
+    pub context: Vec<String>,
+    /// Each differing field as (name, expected, actual).
+    pub fields: Vec<(String, String, String)>,
+    /// The most recent memory writes, oldest first.
+    pub writes: Vec<MemoryWrite>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    /// Every line of the reference matched; holds the number of lines.
+    Matched(usize),
+    Diverged(Box<Divergence>),
+}
+
+// Splits a Gameboy Doctor line into its NAME:VALUE fields.
+fn fields(line: &str) -> Vec<(&str, &str)> {
+    line.split_whitespace().filter_map(|field| field.split_once(':')).collect()
+}
+
+// The fields of `expected` whose values differ in `actual`. Fields missing
+// from the reference are not compared, so logs with fewer fields still
+// line up. A reference line without any fields is not a Doctor line at all.
+fn differences(expected: &str, actual: &str) -> Result<Vec<(String, String, String)>, String> {
+    let actual = fields(actual);
+    let expected = fields(expected);
+    if expected.is_empty() {
+        return Err("no NAME:VALUE fields; is this a Gameboy Doctor log?".to_string());
+    }
+    Ok(expected.into_iter().filter_map(|(name, value)| {
+        let other = actual.iter().find(|(other, _)| *other == name).map_or("", |(_, value)| value);
+        if value.eq_ignore_ascii_case(other) {
+            None
+        } else {
+            Some((name.to_string(), value.to_string(), other.to_string()))
+        }
+    }).collect())
+}
+
+/// Runs `gb` one instruction per line of `reference`, a Gameboy Doctor
+/// log, and stops at the first line that does not match. Keeps up to
+/// `context` previous lines and `writes` memory writes for the report.
+/// Undefined opcodes lock up the CPU rather than panicking, so they show up
+/// as a divergence.
+pub fn compare<R: BufRead>(gb: &mut GameBoy, reference: R, context: usize, writes: usize) -> Result<Comparison, String> {
+    let mut previous: VecDeque<String> = VecDeque::with_capacity(context);
+    let mut recent: VecDeque<MemoryWrite> = VecDeque::with_capacity(writes);
+    gb.bus_mut().log_writes(true);
+    gb.cpu_mut().set_trap_undefined(true);
+    let mut count = 0;
+    for (number, line) in reference.lines().enumerate() {
+        let expected = line.map_err(|err| format!("could not read the reference trace: {}", err))?;
+        let expected = expected.trim();
+        if expected.is_empty() {
+            continue;
+        }
+        let pc = gb.cpu().pc();
+        let pcmem = [0, 1, 2, 3].map(|i| gb.bus().peek(pc.wrapping_add(i)));
+        let actual = doctor_line(&gb.cpu().registers(), pcmem);
+        let fields = match differences(expected, &actual) {
+            Ok(fields) => fields,
+            Err(err) => {
+                gb.bus_mut().log_writes(false);
+                return Err(format!("reference line {}: {}", number + 1, err));
+            }
+        };
+        if !fields.is_empty() {
+            gb.bus_mut().log_writes(false);
+            return Ok(Comparison::Diverged(Box::new(Divergence {
+                line: number + 1,
+                expected: expected.to_string(),
+                actual,
+                context: previous.into(),
+                fields,
+                writes: recent.into(),
+            })));
+        }
+        if context > 0 {
+            if previous.len() == context {
+                previous.pop_front();
+            }
+            previous.push_back(actual);
+        }
+        gb.step_instruction();
+        for (addr, value) in gb.bus_mut().take_writes() {
+            if writes > 0 {
+                if recent.len() == writes {
+                    recent.pop_front();
+                }
+                recent.push_back(MemoryWrite { pc, addr, value });
+            }
+        }
+        count += 1;
+    }
+    gb.bus_mut().log_writes(false);
+    Ok(Comparison::Matched(count))
+}
@@ -0,0 +1,57 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::Registers;
+
+/// Formats CPU state as a Gameboy Doctor log line: the registers before an
+/// instruction executes and the four bytes at PC.
+pub fn doctor_line(registers: &Registers, pcmem: [u8; 4]) -> String {
+    format!("A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+        registers.a, registers.f, registers.b, registers.c, registers.d, registers.e, registers.h, registers.l,
+        registers.sp, registers.pc, pcmem[0], pcmem[1], pcmem[2], pcmem[3])
+}
+
+/// Writes one Gameboy Doctor line per executed instruction, so traces can
+/// be diffed against other emulators. Instructions can be limited to a PC
+/// range and to a switchable ROM bank.
+pub struct Tracer {
+    output: Box<dyn Write>,
+    pc_range: Option<RangeInclusive<u16>>,
+    bank: Option<u16>,
+}
+
+impl Tracer {
+    pub fn new<W: Write + 'static>(output: W) -> Tracer {
+        Tracer { output: Box::new(output), pc_range: None, bank: None }
+    }
+
+    /// Creates a tracer writing to a file.
+    pub fn create(path: &Path) -> io::Result<Tracer> {
+        Ok(Tracer::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Only traces instructions whose address is in `range`.
+    pub fn set_pc_range(&mut self, range: Option<RangeInclusive<u16>>) {
+        self.pc_range = range;
+    }
+
+    /// Only traces instructions executed from `bank`. Code outside
+    /// 0x4000-0x7FFF counts as bank 0.
+    pub fn set_bank(&mut self, bank: Option<u16>) {
+        self.bank = bank;
+    }
+
+    pub fn wants(&self, pc: u16, bank: u16) -> bool {
+        self.pc_range.as_ref().is_none_or(|range| range.contains(&pc)) && self.bank.is_none_or(|b| b == bank)
+    }
+
+    pub fn trace(&mut self, registers: &Registers, pcmem: [u8; 4]) -> io::Result<()> {
+        writeln!(self.output, "{}", doctor_line(registers, pcmem))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
@@ -0,0 +1,66 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+#[cfg(test)]
+mod test;
+
+const HEADER_SIZE: u32 = 44;
+// The RIFF size field counts everything after itself in 32 bits, which
+// caps the sample data at just under 4 GiB.
+const MAX_DATA_LEN: u32 = u32::MAX - (HEADER_SIZE - 8);
+
+/// Streams interleaved 16-bit PCM samples into a RIFF WAVE file. The size
+/// fields are rewritten on every flush, so the file stays playable if the
+/// process is killed.
+pub struct WavWriter {
+    out: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path, sample_rate: u32, channels: u16) -> io::Result<WavWriter> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let block_align = channels * 2;
+        out.write_all(b"RIFF")?;
+        out.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        out.write_all(b"WAVE")?;
+        out.write_all(b"fmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&channels.to_le_bytes())?;
+        out.write_all(&sample_rate.to_le_bytes())?;
+        out.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        out.write_all(&block_align.to_le_bytes())?;
+        out.write_all(&16u16.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        Ok(WavWriter { out, data_len: 0 })
+    }
+
+    /// Appends samples. Once the file is as large as a WAVE file can be,
+    /// the header is brought up to date and an error is returned instead.
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        let data_len = u32::try_from(samples.len() * 2).ok()
+            .and_then(|len| self.data_len.checked_add(len))
+            .filter(|&len| len <= MAX_DATA_LEN);
+        let Some(data_len) = data_len else {
+            self.flush()?;
+            return Err(io::Error::other("the WAVE file has reached its 4 GiB limit"));
+        };
+        for sample in samples {
+            self.out.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_len = data_len;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(HEADER_SIZE - 8 + self.data_len).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&self.data_len.to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()
+    }
+}
@@ -0,0 +1,25 @@
+use super::*;
+
+fn header_lengths(path: &Path) -> (u32, u32) {
+    let data = std::fs::read(path).unwrap();
+    let field = |pos: usize| u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+    (field(4), field(40))
+}
+
+#[test]
+fn stops_at_the_riff_size_limit() {
+    let path = std::env::temp_dir().join(format!("gbemu-wav-{}.wav", std::process::id()));
+    let mut wav = WavWriter::create(&path, 48000, 2).unwrap();
+    wav.write_samples(&[1, -1]).unwrap();
+    wav.flush().unwrap();
+    assert_eq!(header_lengths(&path), (HEADER_SIZE - 8 + 4, 4));
+
+    // Pretend the file is nearly full rather than writing 4 GiB.
+    wav.data_len = MAX_DATA_LEN - 4;
+    wav.write_samples(&[2, -2]).unwrap();
+    assert!(wav.write_samples(&[3]).is_err());
+    assert_eq!(wav.data_len, MAX_DATA_LEN);
+    let (riff_len, data_len) = header_lengths(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!((riff_len, data_len), (u32::MAX, MAX_DATA_LEN));
+}
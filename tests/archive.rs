@@ -0,0 +1,83 @@
+// Tests for loading ROMs from zip and gzip archives.
+mod common;
+
+use std::fs;
+
+use common::archives::{gzip, zip};
+use common::test_rom;
+use gbemu::archive::{gunzip, unpack_rom};
+use gbemu::Cartridge;
+
+fn rom(title: &[u8]) -> Vec<u8> {
+    let mut rom = test_rom(&[]);
+    rom[0x0134..0x0134 + title.len()].copy_from_slice(title);
+    rom
+}
+
+fn title(cartridge: &Cartridge) -> String {
+    cartridge.header().unwrap().title
+}
+
+#[test]
+fn loads_zipped_roms() {
+    let archive = zip(&[
+        ("readme.txt", b"not a rom", false),
+        ("roms/", b"", false),
+        ("roms/first.GB", &rom(b"FIRST"), true),
+        ("roms/second.gbc", &rom(b"SECOND"), false),
+    ]);
+    assert_eq!(&*unpack_rom(&archive, None).unwrap(), &rom(b"FIRST")[..]);
+
+    let mut cartridge = Cartridge::new();
+    assert_eq!(cartridge.load_cartridge_w_buffer(&archive), 0x8000);
+    assert_eq!(title(&cartridge), "FIRST");
+    cartridge.set_archive_entry(Some("roms/second.gbc".to_string()));
+    assert_eq!(cartridge.load_cartridge_w_buffer(&archive), 0x8000);
+    assert_eq!(title(&cartridge), "SECOND");
+    cartridge.set_archive_entry(Some("missing.gb".to_string()));
+    assert_eq!(cartridge.load_cartridge_w_buffer(&archive), 0);
+
+    let no_rom = zip(&[("readme.txt", b"not a rom", false)]);
+    assert!(unpack_rom(&no_rom, None).unwrap_err().contains(".gb"));
+    // Plain ROMs pass through untouched.
+    assert_eq!(&*unpack_rom(&rom(b"RAW"), None).unwrap(), &rom(b"RAW")[..]);
+}
+
+#[test]
+fn loads_gzipped_roms() {
+    let data = gzip("game.gb", &rom(b"GZIPPED"));
+    assert_eq!(gunzip(&data).unwrap(), rom(b"GZIPPED"));
+
+    let path = std::env::temp_dir().join(format!("gbemu-archive-{}.gb.gz", std::process::id()));
+    fs::write(&path, &data).unwrap();
+    let mut cartridge = Cartridge::new();
+    let loaded = cartridge.load_cartridge(&path.to_string_lossy().into_owned());
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, 0x8000);
+    assert_eq!(title(&cartridge), "GZIPPED");
+
+    let mut corrupt = data.clone();
+    let len = corrupt.len();
+    corrupt[len - 5] ^= 1;
+    assert!(gunzip(&corrupt).unwrap_err().contains("checksum"));
+    assert!(gunzip(&data[..12]).is_err());
+}
+
+#[test]
+fn refuses_to_expand_archive_bombs() {
+    // 16 MiB of zeros compresses more than a hundredfold and is bigger than
+    // any ROM.
+    let zeros = vec![0; 16 << 20];
+    let bomb = gzip("game.gb", &zeros);
+    assert!(bomb.len() < zeros.len() / 100, "{} bytes", bomb.len());
+    assert!(unpack_rom(&bomb, None).unwrap_err().contains("largest Game Boy ROM"));
+    let bomb = zip(&[("game.gb", &zeros, true)]);
+    assert!(unpack_rom(&bomb, None).unwrap_err().contains("largest Game Boy ROM"));
+
+    // A gzip file that understates its length stops at the length it
+    // claims rather than expanding the whole stream.
+    let mut lying = gzip("game.gb", &zeros);
+    let len = lying.len();
+    lying[len - 4..].copy_from_slice(&0x8000u32.to_le_bytes());
+    assert!(unpack_rom(&lying, None).unwrap_err().contains("larger than 32768 bytes"));
+}
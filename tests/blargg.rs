@@ -0,0 +1,106 @@
+// Runs Blargg's test ROMs headless and checks the result they report, either
+// as text over the serial port or, for the newer suites, as a signature in
+// cartridge RAM at 0xA000. ROMs are looked up in tests/roms/blargg (or
+// $GBEMU_BLARGG_DIR) keeping the directory layout of the official archive.
+mod common;
+
+use std::fs;
+
+use common::{report, rom_dir, run_guarded, Outcome};
+use gbemu::gameboy::FRAME_RATE;
+use gbemu::GameBoy;
+
+// Written to 0xA001-0xA003 once the result at 0xA000 is valid.
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const RUNNING: u8 = 0x80;
+
+fn memory_result(gb: &GameBoy) -> Option<Outcome> {
+    let ram = gb.bus().cartridge.ram();
+    if ram.len() < 4 || ram[1..4] != SIGNATURE || ram[0] == RUNNING {
+        return None;
+    }
+    let text: Vec<u8> = ram[4..].iter().copied().take_while(|&c| c != 0).collect();
+    let text = String::from_utf8_lossy(&text).trim().to_string();
+    Some(if ram[0] == 0 { Outcome::Passed } else { Outcome::Failed(text) })
+}
+
+fn serial_result(gb: &GameBoy) -> Option<Outcome> {
+    let text = String::from_utf8_lossy(gb.bus().serial.output());
+    if text.contains("Passed") {
+        Some(Outcome::Passed)
+    } else if text.contains("Failed") {
+        Some(Outcome::Failed(text.trim().replace('\n', " ")))
+    } else {
+        None
+    }
+}
+
+fn run_rom(rom: &[u8], timeout_seconds: u64) -> Outcome {
+    let mut gb = GameBoy::power_on();
+    if gb.load_buffer(rom) == 0 {
+        return Outcome::Crashed("could not load ROM".to_string());
+    }
+    for _ in 0..(timeout_seconds as f64 * FRAME_RATE) as u64 {
+        gb.run_frame();
+        if let Some(outcome) = memory_result(&gb).or_else(|| serial_result(&gb)) {
+            return outcome;
+        }
+    }
+    Outcome::Timeout
+}
+
+fn run_suite(suite: &str, roms: &[&str], timeout_seconds: u64) {
+    let dir = rom_dir("GBEMU_BLARGG_DIR", "blargg");
+    let results: Vec<(String, Outcome)> = roms.iter().map(|&name| {
+        let outcome = match fs::read(dir.join(name)) {
+            Ok(rom) => run_guarded(|| run_rom(&rom, timeout_seconds)),
+            Err(_) => Outcome::Skipped,
+        };
+        (name.to_string(), outcome)
+    }).collect();
+    let failures = report(suite, &results);
+    assert_eq!(failures, 0, "{} of the {} ROMs failed", failures, suite);
+}
+
+#[test]
+fn cpu_instrs() {
+    run_suite("cpu_instrs", &[
+        "cpu_instrs/individual/01-special.gb",
+        "cpu_instrs/individual/02-interrupts.gb",
+        "cpu_instrs/individual/03-op sp,hl.gb",
+        "cpu_instrs/individual/04-op r,imm.gb",
+        "cpu_instrs/individual/05-op rp.gb",
+        "cpu_instrs/individual/06-ld r,r.gb",
+        "cpu_instrs/individual/07-jr,jp,call,ret,rst.gb",
+        "cpu_instrs/individual/08-misc instrs.gb",
+        "cpu_instrs/individual/09-op r,r.gb",
+        "cpu_instrs/individual/10-bit ops.gb",
+        "cpu_instrs/individual/11-op a,(hl).gb",
+        "cpu_instrs/cpu_instrs.gb",
+    ], 70);
+}
+
+#[test]
+fn instr_timing() {
+    run_suite("instr_timing", &["instr_timing/instr_timing.gb"], 10);
+}
+
+#[test]
+fn mem_timing() {
+    run_suite("mem_timing", &[
+        "mem_timing/individual/01-read_timing.gb",
+        "mem_timing/individual/02-write_timing.gb",
+        "mem_timing/individual/03-modify_timing.gb",
+        "mem_timing/mem_timing.gb",
+    ], 10);
+}
+
+#[test]
+fn mem_timing_2() {
+    run_suite("mem_timing-2", &[
+        "mem_timing-2/rom_singles/01-read_timing.gb",
+        "mem_timing-2/rom_singles/02-write_timing.gb",
+        "mem_timing-2/rom_singles/03-modify_timing.gb",
+        "mem_timing-2/mem_timing.gb",
+    ], 10);
+}
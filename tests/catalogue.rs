@@ -0,0 +1,95 @@
+// Tests for cataloguing a directory of ROMs with `scan`.
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::archives::{gzip, zip};
+use common::test_rom;
+use gbemu::catalogue::{scan, to_csv, to_json, CatalogueFormat};
+use gbemu::Header;
+
+// A 32 KiB ROM with valid checksums.
+fn rom(title: &[u8], cartridge_type: u8) -> Vec<u8> {
+    let mut rom = test_rom(&[]);
+    rom[0x0134..0x0134 + title.len()].copy_from_slice(title);
+    rom[0x0147] = cartridge_type;
+    rom[0x014D] = Header::compute_header_checksum(&rom);
+    let global = Header::compute_global_checksum(&rom);
+    rom[0x014E..0x0150].copy_from_slice(&global.to_be_bytes());
+    rom
+}
+
+#[test]
+fn scans_a_rom_directory() {
+    let dir = std::env::temp_dir().join(format!("gbemu-catalogue-{}", std::process::id()));
+    fs::create_dir_all(dir.join("archives")).unwrap();
+    let mut bad = rom(b"BAD", 0x01);
+    bad[0x0200] = 0xFF;
+    fs::write(dir.join("good.gb"), rom(b"GOOD", 0x00)).unwrap();
+    fs::write(dir.join("bad.gb"), &bad).unwrap();
+    fs::write(dir.join("mbc3.GBC"), rom(b"MBC3", 0x13)).unwrap();
+    fs::write(dir.join("mmm01.gb"), rom(b"MMM01", 0x0B)).unwrap();
+    fs::write(dir.join("short.gb"), [0u8; 0x40]).unwrap();
+    fs::write(dir.join("notes.txt"), "not a rom").unwrap();
+    fs::write(dir.join("archives/zipped.zip"), zip(&[("game.gb", &rom(b"ZIPPED", 0x03), true)])).unwrap();
+    fs::write(dir.join("archives/gzipped.gb.gz"), gzip("game.gb", &rom(b"GZIPPED", 0x05))).unwrap();
+    // A link back up the tree is not followed.
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&dir, dir.join("archives/loop")).unwrap();
+    let results = scan(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    let results = results.unwrap();
+
+    let names: Vec<_> = results.iter()
+        .map(|(path, _)| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, ["archives/gzipped.gb.gz", "archives/zipped.zip", "bad.gb", "good.gb", "mbc3.GBC", "mmm01.gb",
+        "short.gb"]);
+
+    let info = |i: usize| results[i].1.as_ref().unwrap();
+    assert_eq!((info(0).title.as_str(), info(0).mbc, info(0).supported), ("GZIPPED", "MBC2", true));
+    assert_eq!((info(1).title.as_str(), info(1).mbc, info(1).supported), ("ZIPPED", "MBC1+RAM+BATTERY", true));
+    assert!(info(2).header_checksum_ok && !info(2).global_checksum_ok);
+    assert!(info(3).header_checksum_ok && info(3).global_checksum_ok);
+    assert_eq!((info(3).rom_size, info(3).file_size, info(3).ram_size), (0x8000, 0x8000, 0));
+    assert_eq!((info(4).cartridge_type, info(4).supported), (0x13, false));
+    assert_eq!((info(5).mbc, info(5).supported), ("MMM01", false));
+    assert!(results[6].1.as_ref().unwrap_err().contains("too small"));
+
+    assert!(scan(Path::new("/nonexistent/gbemu-roms")).is_err());
+}
+
+#[test]
+fn writes_json_and_csv_catalogues() {
+    let dir = std::env::temp_dir().join(format!("gbemu-catalogue-format-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.gb"), rom(b"SAY \"HI\",", 0x00)).unwrap();
+    fs::write(dir.join("b.gb"), [0u8; 4]).unwrap();
+    let results = scan(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    let results = results.unwrap();
+    let a = dir.join("a.gb").display().to_string();
+    let b = dir.join("b.gb").display().to_string();
+
+    let json = to_json(&results);
+    let lines: Vec<&str> = json.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!((lines[0], lines[3]), ("[", "]"));
+    assert!(lines[1].starts_with(&format!("  {{\"path\": \"{}\", \"title\": \"SAY \\\"HI\\\",\", ", a)));
+    assert!(lines[1].contains("\"cartridge_type\": \"0x00\", \"mbc\": \"ROM ONLY\", \"supported\": true"));
+    assert!(lines[1].ends_with("\"error\": null},"));
+    assert!(lines[2].starts_with(&format!("  {{\"path\": \"{}\", \"title\": null, ", b)));
+    assert!(lines[2].ends_with("\"error\": \"too small to be a ROM\"}"));
+
+    let csv = to_csv(&results);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "path,title,cartridge_type,mbc,supported,rom_size,file_size,ram_size,\
+        cgb_flag,sgb_flag,header_checksum_ok,global_checksum_ok,crc32,error");
+    assert!(lines[1].starts_with(&format!("{},\"SAY \"\"HI\"\",\",0x00,ROM ONLY,true,32768,32768,0,", a)));
+    assert!(lines[1].ends_with(","));
+    assert_eq!(lines[2], format!("{},,,,,,,,,,,,,too small to be a ROM", b));
+
+    assert_eq!(CatalogueFormat::from_path(Path::new("roms.CSV")), CatalogueFormat::Csv);
+    assert_eq!(CatalogueFormat::from_path(Path::new("roms.txt")), CatalogueFormat::Json);
+}
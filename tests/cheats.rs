@@ -0,0 +1,78 @@
+// Tests for Game Genie and GameShark cheats.
+mod common;
+
+use common::{game_boy_with_rom, test_rom};
+use gbemu::cheats::{parse_cheats, Cheat, Effect};
+use gbemu::GameBoy;
+
+fn game_boy() -> GameBoy {
+    // ld bc, $C025; loop: ld [bc], a; jp loop
+    let mut rom = test_rom(&[0x01, 0x25, 0xC0, 0x02, 0xC3, 0x53, 0x01]);
+    // MBC1 with 32 KiB of RAM.
+    rom[0x0147] = 0x02;
+    rom[0x0149] = 0x03;
+    rom[0x4A17] = 0xC8;
+    // Bank 2 wraps around to bank 0 in a 32 KiB ROM.
+    rom[0x0A17] = 0x77;
+    game_boy_with_rom(&rom)
+}
+
+#[test]
+fn decodes_codes() {
+    let genie = Cheat::parse("00a-17b-c49").unwrap();
+    assert_eq!(genie.code, "00A-17B-C49");
+    assert_eq!(genie.effect, Effect::RomPatch { addr: 0x4A17, value: 0x00, compare: Some(0xC8) });
+    assert_eq!(Cheat::parse("3E1-50F").unwrap().effect, Effect::RomPatch { addr: 0x0150, value: 0x3E, compare: None });
+    assert_eq!(Cheat::parse("01FF25C0").unwrap().effect, Effect::RamWrite { addr: 0xC025, value: 0xFF, bank: None });
+    assert_eq!(Cheat::parse("810512A0").unwrap().effect, Effect::RamWrite { addr: 0xA012, value: 0x05, bank: Some(1) });
+
+    for bad in ["", "00A-17B-C4", "00G-17B", "01FF25C", "01FF2580", "A1FF25C0", "00A-170"] {
+        assert!(Cheat::parse(bad).is_err(), "{}", bad);
+    }
+
+    let cheats = parse_cheats("# Lives\n01FF25C0 Infinite lives\n\n-00A-17B-C49 Walk through walls\n").unwrap();
+    assert_eq!(cheats.len(), 2);
+    assert_eq!((cheats[0].name.as_str(), cheats[0].enabled), ("Infinite lives", true));
+    assert_eq!((cheats[1].name.as_str(), cheats[1].enabled), ("Walk through walls", false));
+    assert!(parse_cheats("01FF25C0\nnonsense\n").unwrap_err().starts_with("line 2:"));
+}
+
+#[test]
+fn patches_rom_reads() {
+    let mut gb = game_boy();
+    gb.bus_mut().add_cheat(Cheat::parse("00A-17B-C49").unwrap());
+    gb.bus_mut().add_cheat(Cheat::parse("3E1-50F").unwrap());
+    assert_eq!(gb.bus().peek(0x4A17), 0x00);
+    assert_eq!(gb.bus().peek(0x0150), 0x3E);
+    assert_eq!(gb.bus().peek(0x0151), 0x25);
+
+    // The compare byte limits the patch to the bank holding that byte.
+    gb.bus_mut().poke(0x2000, 0x02);
+    assert_eq!(gb.bus().peek(0x4A17), 0x77);
+    gb.bus_mut().poke(0x2000, 0x01);
+
+    gb.bus_mut().set_cheat_enabled(0, false);
+    assert_eq!(gb.bus().peek(0x4A17), 0xC8);
+    assert!(gb.bus_mut().remove_cheat(1).is_some());
+    assert_eq!(gb.bus().peek(0x0150), 0x01);
+    assert!(!gb.bus_mut().set_cheat_enabled(5, true));
+}
+
+#[test]
+fn writes_ram_every_frame() {
+    let mut gb = game_boy();
+    gb.bus_mut().add_cheat(Cheat::parse("01FF25C0").unwrap());
+    gb.bus_mut().add_cheat(Cheat::parse("830512A0").unwrap());
+    gb.run_frame();
+    // The game keeps writing A, but the code puts its value back each frame.
+    assert_eq!(gb.bus().peek(0xC025), 0xFF);
+    assert_eq!(gb.bus().cartridge.ram()[3 * 0x2000 + 0x12], 0x05);
+    gb.bus_mut().poke(0xC025, 0x00);
+    gb.run_frame();
+    assert_eq!(gb.bus().peek(0xC025), 0xFF);
+
+    gb.bus_mut().set_cheat_enabled(0, false);
+    gb.bus_mut().poke(0xC025, 0x00);
+    gb.run_frame();
+    assert_ne!(gb.bus().peek(0xC025), 0xFF);
+}
@@ -0,0 +1,55 @@
+// Builders for the archives that ROMs and movies are loaded from.
+use gbemu::deflate::{compress, crc32};
+
+// Builds a zip archive, deflating the entries whose flag is set.
+pub fn zip(files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut directory = vec![];
+    for &(name, data, deflate) in files {
+        let stored = if deflate { compress(data) } else { data.to_vec() };
+        let mut fields = vec![];
+        fields.extend(20u16.to_le_bytes());
+        fields.extend(0u16.to_le_bytes());
+        fields.extend(if deflate { 8u16 } else { 0 }.to_le_bytes());
+        fields.extend([0; 4]);
+        fields.extend(crc32(data).to_le_bytes());
+        fields.extend((stored.len() as u32).to_le_bytes());
+        fields.extend((data.len() as u32).to_le_bytes());
+        fields.extend((name.len() as u16).to_le_bytes());
+        fields.extend(0u16.to_le_bytes());
+
+        directory.extend(0x02014B50u32.to_le_bytes());
+        directory.extend(20u16.to_le_bytes());
+        directory.extend(&fields);
+        // Comment length, disk number and attributes.
+        directory.extend([0; 10]);
+        directory.extend((out.len() as u32).to_le_bytes());
+        directory.extend(name.as_bytes());
+
+        out.extend(0x04034B50u32.to_le_bytes());
+        out.extend(&fields);
+        out.extend(name.as_bytes());
+        out.extend(&stored);
+    }
+    let offset = out.len() as u32;
+    out.extend(&directory);
+    out.extend(0x06054B50u32.to_le_bytes());
+    out.extend([0; 4]);
+    out.extend((files.len() as u16).to_le_bytes());
+    out.extend((files.len() as u16).to_le_bytes());
+    out.extend((directory.len() as u32).to_le_bytes());
+    out.extend(offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out
+}
+
+// Wraps data in a gzip file that records the original file name.
+pub fn gzip(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1F, 0x8B, 0x08, 0x08, 0, 0, 0, 0, 0x00, 0xFF];
+    out.extend(name.as_bytes());
+    out.push(0);
+    out.extend(compress(data));
+    out.extend(crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
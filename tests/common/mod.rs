@@ -0,0 +1,113 @@
+// Helpers shared by the integration tests, mostly the test ROM runners.
+// Test ROMs are not distributed with the emulator; each suite looks for
+// them in a directory under tests/roms that can be overridden with an
+// environment variable, and skips whatever is missing.
+#![allow(dead_code)]
+
+pub mod archives;
+
+use std::any::Any;
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+use gbemu::GameBoy;
+
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    Timeout,
+    Crashed(String),
+    Skipped,
+}
+
+impl Outcome {
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Outcome::Failed(_) | Outcome::Timeout | Outcome::Crashed(_))
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Passed => "PASS",
+            Outcome::Failed(_) => "FAIL",
+            Outcome::Timeout => "TIMEOUT",
+            Outcome::Crashed(_) => "CRASH",
+            Outcome::Skipped => "SKIP",
+        }
+    }
+
+    fn detail(&self) -> &str {
+        match self {
+            Outcome::Failed(detail) | Outcome::Crashed(detail) => detail,
+            _ => "",
+        }
+    }
+}
+
+/// The directory holding a suite's ROMs: `$var` if set, otherwise
+/// tests/roms/<default>.
+pub fn rom_dir(var: &str, default: &str) -> PathBuf {
+    match env::var_os(var) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/roms").join(default),
+    }
+}
+
+/// A 32 KiB ROM-only image whose entry point jumps to `program` at 0x0150.
+pub fn test_rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x0100..0x0103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    rom[0x0150..0x0150 + program.len()].copy_from_slice(program);
+    rom
+}
+
+/// A freshly powered on DMG with `rom` loaded.
+pub fn game_boy_with_rom(rom: &[u8]) -> GameBoy {
+    let mut gb = GameBoy::power_on();
+    assert_ne!(gb.load_buffer(rom), 0);
+    gb
+}
+
+/// A freshly powered on DMG running `program` from a test_rom.
+pub fn game_boy(program: &[u8]) -> GameBoy {
+    game_boy_with_rom(&test_rom(program))
+}
+
+/// ld bc, $C000; loop: add a, 5; ld [bc], a; inc bc; jp loop
+pub const FILL_LOOP: [u8; 10] = [0x01, 0x00, 0xC0, 0xC6, 0x05, 0x02, 0x03, 0xC3, 0x53, 0x01];
+
+/// Main: ld bc, $C000; add a, 5; ld [bc], a; nop; then an undefined opcode.
+pub const STORE_THEN_UNDEFINED: [u8; 8] = [0x01, 0x00, 0xC0, 0xC6, 0x05, 0x02, 0x00, 0xD3];
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panic".to_string()
+    }
+}
+
+/// Runs a single ROM, turning a panic inside the emulator into a crash
+/// result so the rest of the suite still runs.
+pub fn run_guarded<F: FnOnce() -> Outcome>(run: F) -> Outcome {
+    match panic::catch_unwind(AssertUnwindSafe(run)) {
+        Ok(outcome) => outcome,
+        Err(payload) => Outcome::Crashed(panic_message(payload).trim().to_string()),
+    }
+}
+
+/// Prints a results table and returns the number of failures.
+pub fn report(suite: &str, results: &[(String, Outcome)]) -> usize {
+    let width = results.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    println!("{}:", suite);
+    for (name, outcome) in results {
+        let detail = outcome.detail().lines().next().unwrap_or("");
+        println!("  {:width$}  {:7}  {}", name, outcome.label(), detail, width = width);
+    }
+    let count = |label: &str| results.iter().filter(|(_, outcome)| outcome.label() == label).count();
+    println!("  {} passed, {} failed, {} skipped",
+        count("PASS"), results.iter().filter(|(_, outcome)| outcome.is_failure()).count(), count("SKIP"));
+    results.iter().filter(|(_, outcome)| outcome.is_failure()).count()
+}
@@ -0,0 +1,109 @@
+// Drives the debugger's command prompt with scripted input on a small
+// synthetic ROM.
+mod common;
+
+use std::io::Cursor;
+use std::sync::atomic::Ordering;
+
+use gbemu::debugger::{self, Action, Debugger, Stop};
+use common::STORE_THEN_UNDEFINED;
+use gbemu::{GameBoy, SymbolTable};
+
+fn game_boy() -> GameBoy {
+    let mut gb = common::game_boy(&STORE_THEN_UNDEFINED);
+    gb.set_symbols(SymbolTable::parse("00:0150 Main\n00:c000 wCounter\n").unwrap());
+    gb.cpu_mut().set_trap_undefined(true);
+    gb
+}
+
+// Runs like the command-line frontend does until the script quits, and
+// returns everything the debugger printed.
+fn run_script(script: &str) -> String {
+    let mut gb = game_boy();
+    let mut debugger = Debugger::new();
+    let mut input = Cursor::new(script.as_bytes().to_vec());
+    let mut output = Vec::new();
+    for _ in 0..100 {
+        let (_, stopped) = gb.run_frame_until(|gb| debugger.check(gb));
+        if stopped && debugger.prompt(&mut gb, &mut input, &mut output).unwrap() == Action::Quit {
+            return String::from_utf8(output).unwrap();
+        }
+    }
+    panic!("script did not quit:\n{}", String::from_utf8_lossy(&output));
+}
+
+#[test]
+fn breakpoints_and_stepping() {
+    let output = run_script("b Main\nbl\nc\ns\n\nregs\nq\n");
+    assert!(output.starts_with("=> 00:0100  C3 50 01  JP Main\n(gbdb) "), "{}", output);
+    assert!(output.contains("Breakpoint 1 at 00:0150\n"), "{}", output);
+    assert!(output.contains("Breakpoint 1\nMain:\n=> 00:0150  01 00 C0"), "{}", output);
+    assert!(output.contains("=> 00:0153  C6 05     ADD A,$05"), "{}", output);
+    // The empty line repeats the step.
+    assert!(output.contains("=> 00:0155  02        LD (BC),A"), "{}", output);
+    assert!(output.contains("AF=06"), "{}", output);
+    assert!(output.contains("BC=C000"), "{}", output);
+    assert!(output.contains("PC=0155"), "{}", output);
+}
+
+#[test]
+fn watchpoints_memory_and_registers() {
+    let output = run_script("w wCounter\nc\nx wCounter 4\npoke C001 AB CD\nx C000 3\nset a 42\nset hl 1234\nr\nq\n");
+    assert!(output.contains("Watchpoint 1 at C000 (wCounter)\n"), "{}", output);
+    assert!(output.contains("Write of 06 at C000 (wCounter)\n=> 00:0156"), "{}", output);
+    assert!(output.contains("C000: 06 00 00 00"), "{}", output);
+    assert!(output.contains("C000: 06 AB CD"), "{}", output);
+    assert!(output.contains("AF=4200"), "{}", output);
+    assert!(output.contains("HL=1234"), "{}", output);
+}
+
+#[test]
+fn undefined_opcode_enters_prompt() {
+    let output = run_script("c\ndis\nset pc 150\ns\nbogus\nq\n");
+    assert!(output.contains("Undefined opcode D3; the CPU has locked up\n=> 00:0157"), "{}", output);
+    // Disassembly around PC starts a few instructions back.
+    assert!(output.contains("   00:0153  C6 05     ADD A,$05\n   00:0155  02        LD (BC),A\n"), "{}", output);
+    assert!(output.contains("=> 00:0153"), "{}", output);
+    assert!(output.contains("error: unknown command 'bogus'"), "{}", output);
+}
+
+#[test]
+fn interrupt_stops_execution() {
+    let mut gb = game_boy();
+    let mut debugger = Debugger::new();
+    let mut input = Cursor::new(b"c\n".to_vec());
+    let mut output = Vec::new();
+    assert!(gb.run_frame_until(|gb| debugger.check(gb)).1);
+    assert_eq!(debugger.prompt(&mut gb, &mut input, &mut output).unwrap(), Action::Resume);
+    debugger.interrupt_flag().store(true, Ordering::SeqCst);
+    assert!(gb.run_frame_until(|gb| debugger.check(gb)).1);
+    assert_eq!(debugger.stop_reason(), Some(Stop::Interrupted));
+}
+
+#[test]
+fn interrupt_stops_only_the_registered_debugger() {
+    let mut gb = game_boy();
+    let mut registered = Debugger::new();
+    let mut other = Debugger::new();
+    debugger::route_interrupts(&registered);
+    let mut output = Vec::new();
+    for debugger in [&mut registered, &mut other] {
+        assert!(gb.run_frame_until(|gb| debugger.check(gb)).1);
+        assert_eq!(debugger.prompt(&mut gb, &mut Cursor::new(b"c\n".to_vec()), &mut output).unwrap(), Action::Resume);
+    }
+    debugger::interrupt();
+    assert!(!other.check(&mut gb));
+    assert!(registered.check(&mut gb));
+    assert_eq!(registered.stop_reason(), Some(Stop::Interrupted));
+}
+
+#[test]
+fn cheat_commands() {
+    let output = run_script("cheats\ncheat 01ff25c0 Infinite lives\ncheat 00A-17B\ncheat off 1\ncheats\ncheat del 2\n\
+        cheat on 3\ncheats\nq\n");
+    assert!(output.contains("No cheats\n"), "{}", output);
+    assert!(output.contains("Cheat 2 added\n"), "{}", output);
+    assert!(output.contains("Cheat 1 01FF25C0 (off) Infinite lives\nCheat 2 00A-17B (on)\n"), "{}", output);
+    assert!(output.contains("error: no cheat '3'"), "{}", output);
+    assert!(output.ends_with("Cheat 1 01FF25C0 (off) Infinite lives\n(gbdb) "), "{}", output);
+}
@@ -0,0 +1,91 @@
+// Tests for the disassembler: operand formatting in both syntaxes, the
+// opcode map and tracing code through a banked ROM.
+use gbemu::disasm::{decode, Flow, RomDisassembly, Syntax};
+
+fn classic(bytes: &[u8], address: u16) -> String {
+    decode(bytes, address).format(Syntax::Classic)
+}
+
+fn rgbds(bytes: &[u8], address: u16) -> String {
+    decode(bytes, address).format(Syntax::Rgbds)
+}
+
+#[test]
+fn test_formats() {
+    assert_eq!(classic(&[0xF0, 0x44], 0), "LD A,($FF44)");
+    assert_eq!(rgbds(&[0xF0, 0x44], 0), "ldh a, [$FF44]");
+    assert_eq!(classic(&[0x20, 0x4E], 0x0100), "JR NZ,$0150");
+    assert_eq!(rgbds(&[0x18, 0xFE], 0x0200), "jr $0200");
+    assert_eq!(classic(&[0x22], 0), "LD (HL+),A");
+    assert_eq!(rgbds(&[0x3A], 0), "ld a, [hl-]");
+    assert_eq!(classic(&[0xE2], 0), "LD (C),A");
+    assert_eq!(rgbds(&[0xF2], 0), "ldh a, [c]");
+    assert_eq!(classic(&[0xF8, 0xFD], 0), "LD HL,SP-$03");
+    assert_eq!(rgbds(&[0xF8, 0x05], 0), "ld hl, sp + $05");
+    assert_eq!(rgbds(&[0xE8, 0x80], 0), "add sp, -$80");
+    assert_eq!(classic(&[0x08, 0x34, 0x12], 0), "LD ($1234),SP");
+    assert_eq!(classic(&[0x36, 0x7F], 0), "LD (HL),$7F");
+    assert_eq!(rgbds(&[0x88], 0), "adc a, b");
+    assert_eq!(rgbds(&[0x96], 0), "sub [hl]");
+    assert_eq!(classic(&[0xFE, 0x90], 0), "CP $90");
+    assert_eq!(classic(&[0xCB, 0x7C], 0), "BIT 7,H");
+    assert_eq!(rgbds(&[0xCB, 0x86], 0), "res 0, [hl]");
+    assert_eq!(rgbds(&[0xCB, 0x37], 0), "swap a");
+    assert_eq!(classic(&[0xFF], 0), "RST $38");
+    assert_eq!(rgbds(&[0xE9], 0), "jp hl");
+    assert_eq!(rgbds(&[0xC4, 0x00, 0x40], 0), "call nz, $4000");
+    assert_eq!(rgbds(&[0xF5], 0), "push af");
+    assert_eq!(rgbds(&[0xD3], 0), "db $D3");
+    assert_eq!(rgbds(&[0x10, 0x00], 0), "stop");
+    assert_eq!(rgbds(&[0x76], 0), "halt");
+}
+
+#[test]
+fn test_lengths_and_flow() {
+    // Every opcode decodes, with the lengths from the opcode map.
+    for opcode in 0..=0xFF_u8 {
+        let instruction = decode(&[opcode, 0x00, 0x00], 0);
+        let expected = match opcode {
+            0x01 | 0x08 | 0x11 | 0x21 | 0x31 | 0xC2 | 0xC3 | 0xC4 | 0xCA | 0xCC | 0xCD | 0xD2
+                | 0xD4 | 0xDA | 0xDC | 0xEA | 0xFA => 3,
+            0x06 | 0x0E | 0x10 | 0x16 | 0x18 | 0x1E | 0x20 | 0x26 | 0x28 | 0x2E | 0x30 | 0x36
+                | 0x38 | 0x3E | 0xC6 | 0xCB | 0xCE | 0xD6 | 0xDE | 0xE0 | 0xE6 | 0xE8 | 0xEE
+                | 0xF0 | 0xF6 | 0xF8 | 0xFE => 2,
+            _ => 1,
+        };
+        assert_eq!(instruction.length, expected, "opcode {:02X}", opcode);
+    }
+    assert_eq!(decode(&[0xC3, 0x50, 0x01], 0).flow(), Flow::Jump(0x0150));
+    assert_eq!(decode(&[0x38, 0x02], 0x10).flow(), Flow::ConditionalJump(0x14));
+    assert_eq!(decode(&[0xCD, 0x00, 0x20], 0).flow(), Flow::Call(0x2000));
+    assert_eq!(decode(&[0xEF], 0).flow(), Flow::Call(0x28));
+    assert_eq!(decode(&[0xC8], 0).flow(), Flow::ConditionalReturn);
+    assert_eq!(decode(&[0xD9], 0).flow(), Flow::Return);
+    assert_eq!(decode(&[0xE9], 0).flow(), Flow::IndirectJump);
+    assert_eq!(decode(&[0xFD], 0).flow(), Flow::Invalid);
+}
+
+#[test]
+fn test_trace_follows_bank_switch() {
+    let mut rom = vec![0u8; 0x10000];
+    // 0x0100: ld a, 3; ld [$2000], a; call $4000; jr @ (loop)
+    rom[0x0100..0x010A].copy_from_slice(&[0x3E, 0x03, 0xEA, 0x00, 0x20, 0xCD, 0x00, 0x40, 0x18, 0xFE]);
+    // Bank 3 at 0x4000: inc b; ret
+    rom[3 * 0x4000..3 * 0x4000 + 2].copy_from_slice(&[0x04, 0xC9]);
+    // Bank 1 at 0x4000 holds data that must not be decoded.
+    rom[0x4000] = 0xDD;
+    for vector in [0x40, 0x48, 0x50, 0x58, 0x60] {
+        rom[vector] = 0xD9;
+    }
+    let disassembly = RomDisassembly::trace(&rom);
+    assert!(disassembly.instructions().contains_key(&(3 * 0x4000 + 1)));
+    assert!(!disassembly.instructions().contains_key(&0x4000));
+    assert_eq!(disassembly.label(3 * 0x4000), Some("Call_003_4000"));
+    assert_eq!(disassembly.label(0x0108), Some("Jump_000_0108"));
+
+    let source = disassembly.to_rgbds();
+    assert!(source.contains("    call Call_003_4000\n"));
+    assert!(source.contains("Jump_000_0108:\n    jr Jump_000_0108\n"));
+    assert!(source.contains("SECTION \"ROM Bank $003\", ROMX[$4000], BANK[$3]\n\nCall_003_4000:\n    inc b\n    ret\n"));
+    assert!(source.contains("    ds 16382, $00\n"));
+}
@@ -0,0 +1,126 @@
+// Talks to the GDB stub over a loopback connection the way GDB would.
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use common::{game_boy, STORE_THEN_UNDEFINED};
+use gbemu::gdb::{End, GdbStub};
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    fn read_packet(&mut self) -> String {
+        while self.read_byte() != b'$' {}
+        let mut packet = vec![];
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                b'}' => packet.push(self.read_byte() ^ 0x20),
+                byte => packet.push(byte),
+            }
+        }
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        let sum = packet.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        assert_eq!(u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap(), sum);
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(packet).unwrap()
+    }
+
+    fn send(&mut self, packet: &str) {
+        let sum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", packet, sum).unwrap();
+        assert_eq!(self.read_byte(), b'+');
+    }
+
+    fn request(&mut self, packet: &str) -> String {
+        self.send(packet);
+        self.read_packet()
+    }
+}
+
+// Runs the stub on the given ROM while `script` drives it from another
+// thread.
+fn session<F: FnOnce(&mut Client) + Send + 'static>(program: &[u8], script: F) -> End {
+    let mut gb = game_boy(program);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let mut client = Client { stream: TcpStream::connect(addr).unwrap() };
+        script(&mut client);
+    });
+    let (stream, _) = listener.accept().unwrap();
+    let end = GdbStub::new(stream).unwrap().run(&mut gb).unwrap();
+    client.join().unwrap();
+    end
+}
+
+#[test]
+fn registers_memory_and_breakpoints() {
+    let end = session(&STORE_THEN_UNDEFINED, |gdb| {
+        assert!(gdb.request("qSupported:swbreak+;hwbreak+").contains("qXfer:features:read+"));
+        let xml = gdb.request("qXfer:features:read:target.xml:0,fff");
+        assert!(xml.starts_with("l<?xml"));
+        assert!(xml.contains(r#"<reg name="pc" bitsize="16" type="code_ptr"/>"#));
+        assert_eq!(gdb.request("qXfer:features:read:target.xml:10,10").len(), 17);
+        assert_eq!(gdb.request("?"), "S05");
+        // AF, BC, DE, HL, SP and PC, little-endian.
+        assert_eq!(gdb.request("g"), "b0011300d8004d01feff0001");
+        assert_eq!(gdb.request("m150,3"), "0100c0");
+        assert_eq!(gdb.request("m0,2000").len(), 0x4000);
+        assert_eq!(gdb.request("m0,2001"), "E01");
+        assert_eq!(gdb.request("m0,ffffffff"), "E01");
+
+        assert_eq!(gdb.request("Z0,150,1"), "OK");
+        assert_eq!(gdb.request("c"), "T05swbreak:;");
+        assert_eq!(gdb.request("p5"), "5001");
+        assert_eq!(gdb.request("z0,150,1"), "OK");
+        assert_eq!(gdb.request("s"), "S05");
+        assert_eq!(gdb.request("p5"), "5301");
+        assert_eq!(gdb.request("p1"), "00c0");
+
+        assert_eq!(gdb.request("Z2,c000,1"), "OK");
+        assert_eq!(gdb.request("vCont;c"), "T05watch:c000;");
+        assert_eq!(gdb.request("mc000,1"), "06");
+        assert_eq!(gdb.request("z2,c000,1"), "OK");
+        assert_eq!(gdb.request("Z2,0,10001"), "E01");
+        assert_eq!(gdb.request("Z2,0,ffffffff"), "E01");
+
+        assert_eq!(gdb.request("Mc000,2:aabb"), "OK");
+        assert_eq!(gdb.request("mc000,2"), "aabb");
+        assert_eq!(gdb.request("P0=0042"), "OK");
+        assert!(gdb.request("g").starts_with("0042"));
+
+        assert_eq!(gdb.request("Z1,157,1"), "OK");
+        assert_eq!(gdb.request("c"), "T05hwbreak:;");
+        assert_eq!(gdb.request("z1,157,1"), "OK");
+        assert_eq!(gdb.request("c"), "S04");
+        assert_eq!(gdb.request("p5"), "5701");
+        assert_eq!(gdb.request("qUnknownPacket"), "");
+        assert_eq!(gdb.request("D"), "OK");
+    });
+    assert_eq!(end, End::Detached);
+}
+
+#[test]
+fn interrupt_stops_a_running_target() {
+    // Main: jp Main
+    let end = session(&[0xC3, 0x50, 0x01], |gdb| {
+        assert_eq!(gdb.request("QStartNoAckMode"), "OK");
+        write!(gdb.stream, "$c#63").unwrap();
+        gdb.stream.write_all(&[0x03]).unwrap();
+        assert_eq!(gdb.read_packet(), "S02");
+        write!(gdb.stream, "$k#6b").unwrap();
+    });
+    assert_eq!(end, End::Killed);
+}
@@ -0,0 +1,107 @@
+// Tests for importing BizHawk and VisualBoyAdvance movies.
+mod common;
+
+use common::archives::zip;
+use common::test_rom;
+use gbemu::archive::ZipArchive;
+use gbemu::deflate::crc32;
+use gbemu::import::{import_bk2, import_vbm};
+use gbemu::{Button, Model};
+
+fn rom() -> Vec<u8> {
+    let mut rom = test_rom(&[]);
+    rom[0x014D..0x0150].copy_from_slice(&[0x12, 0xAB, 0xCD]);
+    rom
+}
+
+const INPUT_LOG: &str = "\
+[Input]
+LogKey:#P1 Up|P1 Down|P1 Left|P1 Right|P1 Start|P1 Select|P1 B|P1 A|#Power|
+|........|.|
+|.......A|.|
+|U.....B.|.|
+|...RS...|.|
+[/Input]
+";
+
+#[test]
+fn imports_bk2() {
+    let header = b"MovieVersion BizHawk v2.0.0\nPlatform GB\nCore Gambatte\n";
+    let bk2 = zip(&[("Header.txt", header, false), ("Input Log.txt", INPUT_LOG.as_bytes(), true)]);
+    let archive = ZipArchive::parse(&bk2).unwrap();
+    assert_eq!(archive.entries().len(), 2);
+    assert_eq!(archive.read(&archive.entries()[1]).unwrap(), INPUT_LOG.as_bytes());
+
+    let movie = import_bk2(&bk2, &rom()).unwrap();
+    assert_eq!(movie.rom_crc, crc32(&rom()));
+    assert_eq!(movie.model, Model::Dmg);
+    assert!(movie.start_state.is_none() && movie.hashes.is_empty());
+    assert_eq!(movie.inputs, [
+        0,
+        Button::A as u8,
+        Button::Up as u8 | Button::B as u8,
+        Button::Right as u8 | Button::Start as u8,
+    ]);
+
+    let sgb = zip(&[("Header.txt", b"Platform SGB\n", false), ("Input Log.txt", INPUT_LOG.as_bytes(), false)]);
+    assert_eq!(import_bk2(&sgb, &rom()).unwrap().model, Model::Sgb);
+    let state = zip(&[("Header.txt", b"StartsFromSavestate True\n", false), ("Input Log.txt", b"", false)]);
+    assert!(import_bk2(&state, &rom()).unwrap_err().contains("save state"));
+    let nes = zip(&[("Header.txt", b"Platform NES\n", false), ("Input Log.txt", b"", false)]);
+    assert!(import_bk2(&nes, &rom()).is_err());
+    assert!(import_bk2(b"not a zip", &rom()).is_err());
+
+    // A corrupted entry fails its checksum.
+    let mut corrupt = bk2.clone();
+    let pos = corrupt.windows(4).position(|window| window == b"Movi").unwrap();
+    corrupt[pos] = b'm';
+    assert!(import_bk2(&corrupt, &rom()).unwrap_err().contains("checksum"));
+}
+
+fn vbm(frames: &[u16], controllers: u8, system: u8) -> Vec<u8> {
+    let mut vbm = vec![0u8; 0x100];
+    vbm[0..4].copy_from_slice(b"VBM\x1A");
+    vbm[0x04] = 1;
+    vbm[0x0C..0x10].copy_from_slice(&(frames.len() as u32).to_le_bytes());
+    vbm[0x15] = controllers;
+    vbm[0x16] = system;
+    vbm[0x31] = 0x12;
+    vbm[0x32..0x34].copy_from_slice(&[0xAB, 0xCD]);
+    vbm[0x3C..0x40].copy_from_slice(&0x100u32.to_le_bytes());
+    for &frame in frames {
+        vbm.extend(frame.to_le_bytes());
+        // Other controllers' input is skipped.
+        for _ in 1..controllers.count_ones() {
+            vbm.extend(0xFFFFu16.to_le_bytes());
+        }
+    }
+    vbm
+}
+
+#[test]
+fn imports_vbm() {
+    let frames = [0x0000, 0x0001, 0x0042, 0x0088, 0x0400];
+    let expected = [
+        0,
+        Button::A as u8,
+        Button::B as u8 | Button::Up as u8,
+        Button::Start as u8 | Button::Down as u8,
+        0,
+    ];
+    let movie = import_vbm(&vbm(&frames, 0x01, 0), &rom()).unwrap();
+    assert_eq!(movie.inputs, expected);
+    assert_eq!(movie.model, Model::Dmg);
+    let movie = import_vbm(&vbm(&frames, 0x03, 0x04), &rom()).unwrap();
+    assert_eq!(movie.inputs, expected);
+    assert_eq!(movie.model, Model::Sgb);
+
+    assert!(import_vbm(&vbm(&frames, 0x01, 0x02), &rom()).unwrap_err().contains("Color"));
+    let mut other = rom();
+    other[0x014F] ^= 0xFF;
+    assert!(import_vbm(&vbm(&frames, 0x01, 0), &other).unwrap_err().contains("different ROM"));
+    let mut from_state = vbm(&frames, 0x01, 0);
+    from_state[0x14] = 0x01;
+    assert!(import_vbm(&from_state, &rom()).is_err());
+    let truncated = vbm(&frames, 0x01, 0);
+    assert!(import_vbm(&truncated[..truncated.len() - 1], &rom()).is_err());
+}
@@ -0,0 +1,118 @@
+// Runs the mooneye-test-suite ROMs headless. A test finishes by executing
+// LD B,B with the Fibonacci numbers 3/5/8/13/21/34 in B/C/D/E/H/L on success
+// or 0x42 in all six on failure. ROMs are looked up in tests/roms/mooneye
+// (or $GBEMU_MOONEYE_DIR); the whole tree is walked, and ROMs whose model
+// suffix rules out a DMG are skipped.
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{report, rom_dir, run_guarded, Outcome};
+use gbemu::gameboy::CLOCK_SPEED;
+use gbemu::GameBoy;
+
+const PASS_REGISTERS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+const FAIL_REGISTERS: [u8; 6] = [0x42; 6];
+const TIMEOUT_SECONDS: u64 = 20;
+
+// Directories of ROMs that need manual inspection or are not tests.
+const EXCLUDED_DIRS: [&str; 3] = ["utils", "manual-only", "madness"];
+
+// Model suffixes spelled out in full that do not name the DMG ABC revision.
+const OTHER_MODELS: [&str; 6] = ["dmg0", "mgb", "sgb", "cgb", "agb", "ags"];
+
+// Decides from a ROM name like `boot_regs-dmgABC.gb` or `di_timing-GS.gb`
+// whether the test applies to a DMG. Single letter suffixes list the model
+// groups: G (DMG/MGB), S (SGB), C (CGB) and A (AGB).
+fn runs_on_dmg(path: &Path) -> bool {
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+    let Some((_, suffix)) = stem.rsplit_once('-') else {
+        return true;
+    };
+    if suffix.contains("dmgABC") {
+        true
+    } else if !suffix.is_empty() && suffix.chars().all(|c| "GSCA".contains(c)) {
+        suffix.contains('G')
+    } else {
+        !OTHER_MODELS.iter().any(|model| suffix.starts_with(model))
+    }
+}
+
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            let name = entry.file_name();
+            if !EXCLUDED_DIRS.iter().any(|&excluded| name == excluded) {
+                find_roms(&path, roms);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "gb") {
+            roms.push(path);
+        }
+    }
+}
+
+fn run_rom(rom: &[u8]) -> Outcome {
+    let mut gb = GameBoy::power_on();
+    if gb.load_buffer(rom) == 0 {
+        return Outcome::Crashed("could not load ROM".to_string());
+    }
+    gb.cpu_mut().set_ld_b_b_breakpoint(true);
+    let mut cycles = 0;
+    while cycles < TIMEOUT_SECONDS * CLOCK_SPEED as u64 {
+        cycles += gb.step_instruction() as u64;
+        if gb.cpu_mut().take_breakpoint() {
+            let cpu = gb.cpu();
+            let registers = [cpu.b(), cpu.c(), cpu.d(), cpu.e(), cpu.h(), cpu.l()];
+            return if registers == PASS_REGISTERS {
+                Outcome::Passed
+            } else if registers == FAIL_REGISTERS {
+                Outcome::Failed("test reported failure".to_string())
+            } else {
+                Outcome::Failed(format!("unexpected registers B={:02X} C={:02X} D={:02X} E={:02X} H={:02X} L={:02X}",
+                    registers[0], registers[1], registers[2], registers[3], registers[4], registers[5]))
+            };
+        }
+    }
+    Outcome::Timeout
+}
+
+#[test]
+fn mooneye() {
+    let dir = rom_dir("GBEMU_MOONEYE_DIR", "mooneye");
+    let mut roms = vec![];
+    find_roms(&dir, &mut roms);
+    roms.sort();
+    let results: Vec<(String, Outcome)> = roms.iter().map(|path| {
+        let name = path.strip_prefix(&dir).unwrap_or(path).display().to_string();
+        let outcome = match fs::read(path) {
+            Ok(rom) if runs_on_dmg(path) => run_guarded(|| run_rom(&rom)),
+            _ => Outcome::Skipped,
+        };
+        (name, outcome)
+    }).collect();
+    let failures = report("mooneye", &results);
+    assert_eq!(failures, 0, "{} of the mooneye ROMs failed", failures);
+}
+
+#[test]
+fn model_suffixes() {
+    for (name, expected) in [
+        ("add_sp_e_timing.gb", true),
+        ("boot_regs-dmgABC.gb", true),
+        ("boot_div-dmgABCmgb.gb", true),
+        ("boot_regs-dmg0.gb", false),
+        ("boot_regs-mgb.gb", false),
+        ("boot_regs-sgb.gb", false),
+        ("di_timing-GS.gb", true),
+        ("boot_hwio-S.gb", false),
+        ("boot_sclk_align-dmgABCmgb.gb", true),
+        ("rapid_toggle.gb", true),
+    ] {
+        assert_eq!(runs_on_dmg(Path::new(name)), expected, "{}", name);
+    }
+}
@@ -0,0 +1,121 @@
+// Tests for recording and replaying input movies.
+mod common;
+
+use common::{game_boy_with_rom, test_rom};
+use gbemu::movie::Movie;
+use gbemu::{Button, GameBoy, Model};
+
+fn rom() -> Vec<u8> {
+    // ld bc, $FF00; push bc; pop hl; and 0; or $10; ld [bc], a (select the
+    // action buttons); loop: ld a, [hl]; add a, d; ld d, a; jp loop
+    test_rom(&[
+        0x01, 0x00, 0xFF, 0xC5, 0xE1, 0xE6, 0x00, 0xF6, 0x10, 0x02, 0x7E, 0x82, 0x57, 0xC3, 0x5A, 0x01,
+    ])
+}
+
+fn game_boy() -> GameBoy {
+    game_boy_with_rom(&rom())
+}
+
+// Holds a different set of buttons every few frames.
+fn press_for(gb: &mut GameBoy, frame: u64) {
+    gb.set_buttons(0);
+    if frame % 7 < 3 {
+        gb.press(Button::A);
+    }
+    if frame % 5 == 1 {
+        gb.press(Button::Start);
+    }
+}
+
+fn record(gb: &mut GameBoy, frames: u64) -> Movie {
+    gb.start_recording(false);
+    for frame in 0..frames {
+        press_for(gb, frame);
+        gb.run_frame();
+    }
+    gb.stop_recording().unwrap()
+}
+
+#[test]
+fn replays_recorded_input() {
+    let mut gb = game_boy();
+    let movie = record(&mut gb, 150);
+    assert_eq!(movie.inputs.len(), 150);
+    assert_eq!(movie.hashes.iter().map(|&(frame, _)| frame).collect::<Vec<_>>(), [0, 60, 120]);
+    assert!(movie.inputs.contains(&(Button::A as u8)));
+    let end = gb.save_state();
+
+    let mut replay = game_boy();
+    replay.start_playback(movie).unwrap();
+    while !replay.playback().unwrap().finished() {
+        replay.run_frame();
+    }
+    let playback = replay.stop_playback().unwrap();
+    assert_eq!((playback.frame, playback.desync), (150, None));
+    assert_eq!(replay.save_state(), end);
+}
+
+#[test]
+fn detects_desync() {
+    let mut gb = game_boy();
+    let mut movie = record(&mut gb, 130);
+    // Change the input on a frame between two hashes.
+    movie.inputs[70] ^= Button::B as u8;
+
+    let mut replay = game_boy();
+    replay.start_playback(movie.clone()).unwrap();
+    for _ in 0..130 {
+        replay.run_frame();
+    }
+    assert_eq!(replay.playback().unwrap().desync, Some(120));
+
+    // A machine that is not freshly powered on is out of step from the start.
+    let mut late = game_boy();
+    late.run_frame();
+    late.start_playback(movie).unwrap();
+    assert_eq!(late.playback().unwrap().desync, Some(0));
+}
+
+#[test]
+fn records_from_a_save_state() {
+    let mut gb = game_boy();
+    for _ in 0..10 {
+        gb.run_frame();
+    }
+    gb.start_recording(true);
+    for frame in 0..70 {
+        press_for(&mut gb, frame);
+        gb.run_frame();
+    }
+    let movie = gb.stop_recording().unwrap();
+    assert!(movie.start_state.is_some());
+    let end = gb.save_state();
+
+    // Playback loads the state, whatever the machine was doing.
+    let mut replay = game_boy();
+    replay.start_playback(movie).unwrap();
+    for _ in 0..70 {
+        replay.run_frame();
+    }
+    assert_eq!(replay.playback().unwrap().desync, None);
+    assert_eq!(replay.save_state(), end);
+}
+
+#[test]
+fn file_round_trip_and_checks() {
+    let mut gb = game_boy();
+    let movie = record(&mut gb, 61);
+    assert_eq!(Movie::parse(&movie.to_bytes()).unwrap(), movie);
+    assert!(Movie::parse(b"not a movie").is_err());
+    let bytes = movie.to_bytes();
+    assert!(Movie::parse(&bytes[..bytes.len() - 3]).is_err());
+
+    let mut other = GameBoy::power_on_model(Model::Mgb);
+    assert_ne!(other.load_buffer(&rom()), 0);
+    assert!(other.start_playback(movie.clone()).unwrap_err().contains("dmg"));
+    let mut changed = rom();
+    changed[0x0134] = b'X';
+    let mut other = game_boy_with_rom(&changed);
+    assert!(other.start_playback(movie).unwrap_err().contains("different ROM"));
+}
@@ -0,0 +1,190 @@
+// Tests for applying IPS, UPS and BPS patches to ROMs.
+use gbemu::deflate::crc32;
+use gbemu::patch::{apply_bps, apply_ips, apply_patch, apply_ups};
+use gbemu::Cartridge;
+
+fn rom() -> Vec<u8> {
+    let mut rom: Vec<u8> = (0..0x8000).map(|i| (i * 7 % 251) as u8).collect();
+    rom[0x0134..0x0144].fill(0);
+    rom[0x0134..0x0138].copy_from_slice(b"BASE");
+    rom[0x0147..0x014A].fill(0);
+    rom
+}
+
+fn varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let bits = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(bits | 0x80);
+            return;
+        }
+        out.push(bits);
+        value -= 1;
+    }
+}
+
+fn footer(patch: &mut Vec<u8>, source: &[u8], target: &[u8]) {
+    patch.extend(crc32(source).to_le_bytes());
+    patch.extend(crc32(target).to_le_bytes());
+    patch.extend(crc32(patch).to_le_bytes());
+}
+
+// Encodes the differences between two ROMs as a UPS patch.
+fn ups(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = b"UPS1".to_vec();
+    varint(&mut patch, source.len());
+    varint(&mut patch, target.len());
+    let byte = |data: &[u8], i: usize| data.get(i).copied().unwrap_or(0);
+    let mut pos = 0;
+    let mut i = 0;
+    while i < target.len() {
+        if byte(source, i) == target[i] {
+            i += 1;
+            continue;
+        }
+        varint(&mut patch, i - pos);
+        while i < target.len() && byte(source, i) != target[i] {
+            patch.push(byte(source, i) ^ target[i]);
+            i += 1;
+        }
+        patch.push(0);
+        i += 1;
+        pos = i;
+    }
+    footer(&mut patch, source, target);
+    patch
+}
+
+fn target() -> Vec<u8> {
+    let mut target = rom();
+    target[0x0134..0x0138].copy_from_slice(b"HACK");
+    target[0x4000] ^= 0xFF;
+    target.extend([0xAA; 0x10]);
+    target
+}
+
+#[test]
+fn applies_ips() {
+    let rom = rom();
+    let mut patch = b"PATCH".to_vec();
+    // Four bytes at 0x0134, a run of 0x100 bytes at 0x2000, and a record
+    // past the end that grows the ROM.
+    patch.extend([0x00, 0x01, 0x34, 0x00, 0x04]);
+    patch.extend(b"HACK");
+    patch.extend([0x00, 0x20, 0x00, 0x00, 0x00, 0x01, 0x00, 0xEE]);
+    patch.extend([0x00, 0x80, 0x00, 0x00, 0x02, 0x12, 0x34]);
+    patch.extend(b"EOF");
+    let patched = apply_ips(&rom, &patch).unwrap();
+    assert_eq!(patched.len(), 0x8002);
+    assert_eq!(&patched[0x0134..0x0138], b"HACK");
+    assert!(patched[0x2000..0x2100].iter().all(|&byte| byte == 0xEE));
+    assert_eq!(patched[0x2100], rom[0x2100]);
+    assert_eq!(&patched[0x8000..], [0x12, 0x34]);
+
+    // A size after EOF truncates the result.
+    patch.extend([0x00, 0x80, 0x00]);
+    assert_eq!(apply_patch(&rom, &patch).unwrap().len(), 0x8000);
+    assert!(apply_ips(&rom, &patch[..20]).is_err());
+}
+
+#[test]
+fn applies_ups_and_checks_crcs() {
+    let rom = rom();
+    let target = target();
+    let patch = ups(&rom, &target);
+    assert_eq!(apply_ups(&rom, &patch).unwrap(), target);
+    assert_eq!(apply_patch(&rom, &patch).unwrap(), target);
+
+    let mut other = rom.clone();
+    other[0x10] ^= 1;
+    assert!(apply_ups(&other, &patch).unwrap_err().contains("different ROM"));
+    let mut corrupt = patch.clone();
+    corrupt[10] ^= 1;
+    assert!(apply_ups(&rom, &corrupt).unwrap_err().contains("checksum mismatch"));
+}
+
+#[test]
+fn applies_bps_and_checks_crcs() {
+    let rom = rom();
+    let target = target();
+    let mut patch = b"BPS1".to_vec();
+    varint(&mut patch, rom.len());
+    varint(&mut patch, target.len());
+    varint(&mut patch, 5);
+    patch.extend(b"notes");
+    let action = |patch: &mut Vec<u8>, kind: usize, len: usize| varint(patch, (len - 1) << 2 | kind);
+    // SourceRead up to the title, TargetRead the new title.
+    action(&mut patch, 0, 0x0134);
+    action(&mut patch, 1, 4);
+    patch.extend(b"HACK");
+    // SourceCopy up to 0x4000 from the same place in the ROM, then the
+    // changed byte.
+    action(&mut patch, 2, 0x4000 - 0x0138);
+    varint(&mut patch, 0x0138 << 1);
+    action(&mut patch, 1, 1);
+    patch.push(rom[0x4000] ^ 0xFF);
+    // The rest of the ROM, copied back from a later offset.
+    action(&mut patch, 2, 0x3FFF);
+    varint(&mut patch, (0x4001 - 0x4000) << 1);
+    // TargetCopy repeats one byte of 0xAA into a run.
+    action(&mut patch, 1, 1);
+    patch.push(0xAA);
+    action(&mut patch, 3, 0xF);
+    varint(&mut patch, 0x8000 << 1);
+    footer(&mut patch, &rom, &target);
+
+    assert_eq!(apply_bps(&rom, &patch).unwrap(), target);
+    assert_eq!(apply_patch(&rom, &patch).unwrap(), target);
+    assert!(apply_bps(&target, &patch).unwrap_err().contains("different ROM"));
+    let mut corrupt = patch.clone();
+    corrupt[20] ^= 1;
+    assert!(apply_bps(&rom, &corrupt).unwrap_err().contains("checksum mismatch"));
+    assert!(apply_patch(&rom, b"not a patch").is_err());
+}
+
+#[test]
+fn rejects_oversized_results_and_far_copies() {
+    let rom = rom();
+    for magic in [b"UPS1", b"BPS1"] {
+        let mut patch = magic.to_vec();
+        varint(&mut patch, rom.len());
+        varint(&mut patch, 16 * 1024 * 1024);
+        if magic == b"BPS1" {
+            varint(&mut patch, 0);
+        }
+        footer(&mut patch, &rom, &[]);
+        assert!(apply_patch(&rom, &patch).unwrap_err().contains("more than the largest Game Boy ROM"));
+    }
+
+    // A SourceCopy from as far past the ROM as the offset can reach.
+    let mut patch = b"BPS1".to_vec();
+    varint(&mut patch, rom.len());
+    varint(&mut patch, rom.len());
+    varint(&mut patch, 0);
+    varint(&mut patch, (0x10 - 1) << 2 | 2);
+    varint(&mut patch, usize::MAX - 1);
+    footer(&mut patch, &rom, &rom);
+    assert!(apply_bps(&rom, &patch).unwrap_err().contains("outside the ROM"));
+}
+
+#[test]
+fn patches_before_decoding_the_header() {
+    let rom = rom();
+    let mut target = rom.clone();
+    target[0x0134..0x0138].copy_from_slice(b"HACK");
+    // An MBC1 with RAM, which the unpatched ROM does not have.
+    target[0x0147] = 0x02;
+    target[0x0149] = 0x02;
+
+    let mut cartridge = Cartridge::new();
+    cartridge.set_patch(Some(ups(&rom, &target)));
+    assert_eq!(cartridge.load_cartridge_w_buffer(&rom), 0x8000);
+    assert_eq!(cartridge.header().unwrap().title, "HACK");
+    assert_eq!(cartridge.ram().len(), 0x2000);
+    assert_eq!(cartridge.rom_crc(), crc32(&target));
+
+    // A patch that does not apply fails the load.
+    cartridge.set_patch(Some(ups(&target, &rom)));
+    assert_eq!(cartridge.load_cartridge_w_buffer(&rom), 0);
+}
@@ -0,0 +1,65 @@
+// Tests for stepping back through the rewind buffer.
+mod common;
+
+use common::{game_boy, FILL_LOOP};
+use gbemu::rewind::RewindBuffer;
+use gbemu::GameBoy;
+
+fn snapshot(gb: &GameBoy) -> (gbemu::Registers, Vec<u8>) {
+    (gb.cpu().registers(), (0xC000..=0xDFFF).map(|addr| gb.bus().peek(addr)).collect())
+}
+
+#[test]
+fn rewinds_to_earlier_frames() {
+    let mut gb = game_boy(&FILL_LOOP);
+    assert!(gb.rewind(1).is_err());
+    gb.enable_rewind(10.0, 64 << 20);
+    assert!(gb.rewind(1).is_err());
+
+    let mut frames = vec![];
+    for _ in 0..70 {
+        gb.run_frame();
+        frames.push(snapshot(&gb));
+    }
+    let buffer = gb.rewind_buffer().unwrap();
+    assert_eq!(buffer.len(), 70);
+    // Deltas are far smaller than whole states.
+    assert!(buffer.size() < buffer.len() * gb.save_state().len() / 4);
+
+    assert_eq!(gb.rewind(5).unwrap(), 5);
+    assert_eq!(snapshot(&gb), frames[64]);
+    // Running forward again replaces the dropped history.
+    gb.run_frame();
+    assert_eq!(snapshot(&gb), frames[65]);
+    assert_eq!(gb.rewind(1).unwrap(), 1);
+    assert_eq!(snapshot(&gb), frames[64]);
+
+    // Asking for more than is held goes back to the oldest frame.
+    assert_eq!(gb.rewind(1000).unwrap(), 64);
+    assert_eq!(snapshot(&gb), frames[0]);
+}
+
+#[test]
+fn limits_depth_and_memory() {
+    let mut gb = game_boy(&FILL_LOOP);
+    gb.enable_rewind(1.0, 64 << 20);
+    for _ in 0..200 {
+        gb.run_frame();
+    }
+    let buffer = gb.rewind_buffer().unwrap();
+    // One second is 60 frames; whole keyframe groups are dropped, so up to
+    // a group more is kept.
+    assert!((60..120).contains(&buffer.frames()), "{}", buffer.frames());
+
+    let state = gb.save_state();
+    let mut buffer = RewindBuffer::new(10_000, state.len() * 3);
+    for _ in 0..130 {
+        gb.run_frame();
+        buffer.push(gb.save_state());
+        assert!(buffer.size() <= state.len() * 3 || buffer.len() <= 60);
+    }
+    assert!(buffer.frames() < 130);
+    let (restored, back) = buffer.rewind(0).unwrap();
+    assert_eq!(back, 0);
+    assert_eq!(restored, gb.save_state());
+}
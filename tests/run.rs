@@ -0,0 +1,57 @@
+// Tests for driving the emulator a frame, a cycle budget or an instruction
+// at a time.
+mod common;
+
+use common::{game_boy, FILL_LOOP};
+use gbemu::gameboy::CYCLES_PER_FRAME;
+
+// The most T-cycles any one instruction takes.
+const LONGEST_INSTRUCTION: u32 = 24;
+
+#[test]
+fn step_instruction_returns_opcode_cycles() {
+    let mut gb = game_boy(&FILL_LOOP);
+    // jp $0150; ld bc, $C000; add a, 5; ld [bc], a; inc bc; jp loop
+    let cycles: Vec<u32> = (0..6).map(|_| gb.step_instruction()).collect();
+    assert_eq!(cycles, [16, 12, 8, 8, 8, 16]);
+    assert_eq!(gb.cpu().pc(), 0x0153);
+}
+
+#[test]
+fn run_for_cycles_overshoots_by_less_than_an_instruction() {
+    let mut gb = game_boy(&FILL_LOOP);
+    for budget in [1, 4, 100, 1000, 12345] {
+        let run = gb.run_for_cycles(budget);
+        assert!((budget..budget + LONGEST_INSTRUCTION).contains(&run), "ran {} for {}", run, budget);
+    }
+    assert_eq!(gb.run_for_cycles(0), 0);
+}
+
+#[test]
+fn run_frame_with_lcd_on() {
+    // loop: jp loop
+    let mut gb = game_boy(&[0xC3, 0x50, 0x01]);
+    assert!(gb.bus().ppu.lcd_enabled());
+    // The first frame starts part way through, at the boot ROM's handover.
+    assert!(gb.run_frame() <= CYCLES_PER_FRAME);
+    for _ in 0..10 {
+        let frame = gb.bus().ppu.frame_count();
+        let run = gb.run_frame();
+        assert!(run.abs_diff(CYCLES_PER_FRAME) < LONGEST_INSTRUCTION, "frame took {}", run);
+        assert_eq!(gb.bus().ppu.frame_count(), frame + 1);
+    }
+}
+
+#[test]
+fn run_frame_with_lcd_off() {
+    // ld bc, $FF40; and 0; ld [bc], a; loop: jp loop
+    let mut gb = game_boy(&[0x01, 0x40, 0xFF, 0xE6, 0x00, 0x02, 0xC3, 0x56, 0x01]);
+    gb.run_frame();
+    assert!(!gb.bus().ppu.lcd_enabled());
+    let frames = gb.bus().ppu.frame_count();
+    for _ in 0..10 {
+        let run = gb.run_frame();
+        assert!((CYCLES_PER_FRAME..CYCLES_PER_FRAME + LONGEST_INSTRUCTION).contains(&run), "frame took {}", run);
+    }
+    assert_eq!(gb.bus().ppu.frame_count(), frames);
+}
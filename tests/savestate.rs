@@ -0,0 +1,139 @@
+// Tests for saving and restoring the whole machine.
+mod common;
+
+use common::{game_boy_with_rom, test_rom, FILL_LOOP};
+use gbemu::savestate::{StateHeader, StateReader, StateWriter};
+use gbemu::GameBoy;
+
+fn rom(title: &[u8]) -> Vec<u8> {
+    let mut rom = test_rom(&FILL_LOOP);
+    rom[0x0134..0x0134 + title.len()].copy_from_slice(title);
+    // MBC1 with 8 KiB of battery-backed RAM.
+    rom[0x0147] = 0x03;
+    rom[0x0149] = 0x02;
+    rom
+}
+
+fn game_boy() -> GameBoy {
+    game_boy_with_rom(&rom(b"STATE"))
+}
+
+// Everything observable that a state should restore.
+fn snapshot(gb: &GameBoy) -> (gbemu::Registers, Vec<u8>, Vec<u8>, u64) {
+    let memory = (0..=0xFFFF).map(|addr| gb.bus().peek(addr)).collect();
+    (gb.cpu().registers(), memory, gb.bus().ppu.framebuffer().to_vec(), gb.bus().ppu.frame_count())
+}
+
+#[test]
+fn round_trip_is_deterministic() {
+    let mut gb = game_boy();
+    gb.run_frame();
+    gb.run_frame();
+    let state = gb.save_state();
+    let saved = snapshot(&gb);
+    gb.run_frame();
+    let after = snapshot(&gb);
+    assert_ne!(saved, after);
+
+    gb.load_state(&state).unwrap();
+    assert_eq!(snapshot(&gb), saved);
+    gb.run_frame();
+    assert_eq!(snapshot(&gb), after);
+
+    // A fresh machine with the same ROM picks up where the state left off.
+    let mut other = game_boy();
+    other.load_state(&state).unwrap();
+    assert_eq!(snapshot(&other), saved);
+}
+
+#[test]
+fn rejects_other_roms_and_bad_data() {
+    let mut gb = game_boy();
+    gb.run_frame();
+    let state = gb.save_state();
+
+    let mut other = game_boy_with_rom(&rom(b"OTHER"));
+    assert!(other.load_state(&state).unwrap_err().contains("different ROM"));
+    assert!(gb.load_state(b"not a state").is_err());
+
+    // A truncated state fails without changing the machine.
+    gb.run_frame();
+    let before = snapshot(&gb);
+    assert!(gb.load_state(&state[..state.len() - 10]).is_err());
+    assert_eq!(snapshot(&gb), before);
+}
+
+#[test]
+fn skips_unknown_sections_and_trailing_fields() {
+    let mut gb = game_boy();
+    gb.run_frame();
+    let saved = snapshot(&gb);
+    let state = gb.save_state();
+
+    // Rebuild the state as a newer version might write it: an extra
+    // section, and an extra field at the end of every existing one.
+    let mut reader = StateReader::new(&state);
+    let header = StateHeader::read(&mut reader).unwrap();
+    let mut writer = StateWriter::new();
+    StateHeader { version: header.version + 1, ..header }.write(&mut writer);
+    writer.section(b"NEW!", |state| state.u32(0xDEADBEEF));
+    while !reader.is_empty() {
+        let (tag, mut section) = reader.section().unwrap();
+        let mut data = vec![];
+        while !section.is_empty() {
+            data.push(section.u8().unwrap());
+        }
+        writer.section(&tag, |state| {
+            state.bytes(&data);
+            state.u16(0x1234);
+        });
+    }
+
+    gb.run_frame();
+    gb.load_state(&writer.finish()).unwrap();
+    assert_eq!(snapshot(&gb), saved);
+}
+
+// Rewrites `bytes` at `offset` into the section tagged `tag`.
+fn corrupt(state: &[u8], tag: &[u8; 4], offset: usize, bytes: &[u8]) -> Vec<u8> {
+    let mut reader = StateReader::new(state);
+    let mut writer = StateWriter::new();
+    StateHeader::read(&mut reader).unwrap().write(&mut writer);
+    while !reader.is_empty() {
+        let (section_tag, mut section) = reader.section().unwrap();
+        let mut data = section.rest().to_vec();
+        if &section_tag == tag {
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        writer.section(&section_tag, |state| state.bytes(&data));
+    }
+    writer.finish()
+}
+
+#[test]
+fn rejects_out_of_range_ppu_and_apu_state() {
+    let mut gb = game_boy();
+    gb.run_frame();
+    let state = gb.save_state();
+    gb.run_frame();
+    let before = snapshot(&gb);
+
+    // LY and the dot must be on the screen's timeline.
+    assert!(gb.load_state(&corrupt(&state, b"PPU ", 4, &[154])).unwrap_err().contains("PPU position"));
+    assert!(gb.load_state(&corrupt(&state, b"PPU ", 11, &[0xFF; 4])).unwrap_err().contains("PPU position"));
+    // Line 0 dot 0 is in OAM scan, so VBlank there is rejected.
+    let line_start = corrupt(&corrupt(&state, b"PPU ", 4, &[0]), b"PPU ", 11, &[0, 0, 0, 0, 2]);
+    gb.load_state(&line_start).unwrap();
+    assert!(gb.load_state(&corrupt(&line_start, b"PPU ", 15, &[1])).unwrap_err().contains("does not match"));
+
+    // The first square channel's duty position and volume follow the 48
+    // register bytes and its enabled flag, length and timer; the frame
+    // sequencer step comes after all four channels and the sweep and noise
+    // state.
+    gb.load_state(&state).unwrap();
+    gb.run_frame();
+    for (offset, value) in [(0x30 + 7, 8), (0x30 + 8, 16), (0x30 + 40 + 10, 8)] {
+        assert!(gb.load_state(&corrupt(&state, b"APU ", offset, &[value])).unwrap_err().contains("invalid APU"));
+    }
+    assert_eq!(snapshot(&gb), before);
+}
@@ -0,0 +1,139 @@
+// Screenshot regression tests: run a ROM for a fixed number of frames with a
+// scripted input sequence and compare the final frame against a golden PNG
+// in tests/golden. Set GBEMU_BLESS=1 to (re)write the golden images.
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use common::test_rom;
+use gbemu::image::{decode_png, encode_png, framebuffer_to_rgb};
+use gbemu::ppu::{LCD_HEIGHT, LCD_WIDTH};
+use gbemu::{Button, GameBoy};
+
+struct InputEvent {
+    frame: u64,
+    button: Button,
+    pressed: bool,
+}
+
+// Parses a script of whitespace separated `FRAME:+BUTTON` (press) and
+// `FRAME:-BUTTON` (release) events, applied before the given frame runs.
+fn parse_script(script: &str) -> Vec<InputEvent> {
+    script.split_whitespace().map(|event| {
+        let (frame, action) = event.split_once(':').expect("event must be FRAME:[+-]BUTTON");
+        let pressed = action.starts_with('+');
+        assert!(pressed || action.starts_with('-'), "event {} must press (+) or release (-)", event);
+        InputEvent {
+            frame: frame.parse().expect("invalid frame number"),
+            button: Button::from_name(&action[1..]).expect("unknown button"),
+            pressed,
+        }
+    }).collect()
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.png", name))
+}
+
+fn compare_golden(name: &str, framebuffer: &[u8]) {
+    let actual = framebuffer_to_rgb(framebuffer);
+    let path = golden_path(name);
+    if env::var_os("GBEMU_BLESS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, encode_png(LCD_WIDTH, LCD_HEIGHT, &actual)).unwrap();
+        return;
+    }
+    let data = fs::read(&path).unwrap_or_else(|err| {
+        panic!("missing golden image {} ({}); run with GBEMU_BLESS=1 to create it", path.display(), err)
+    });
+    let golden = decode_png(&data).unwrap();
+    assert_eq!((golden.width, golden.height), (LCD_WIDTH, LCD_HEIGHT), "golden image has the wrong size");
+
+    // Mismatching pixels are drawn red over a faded copy of the golden image.
+    let mut diff = Vec::with_capacity(actual.len());
+    let mut mismatches = 0;
+    for (want, got) in golden.rgb.chunks(3).zip(actual.chunks(3)) {
+        if want == got {
+            diff.extend(want.iter().map(|&c| 0xC0 + c / 4));
+        } else {
+            mismatches += 1;
+            diff.extend([0xFF, 0x00, 0x00]);
+        }
+    }
+    if mismatches > 0 {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("screenshots");
+        fs::create_dir_all(&dir).unwrap();
+        let actual_path = dir.join(format!("{}.actual.png", name));
+        let diff_path = dir.join(format!("{}.diff.png", name));
+        fs::write(&actual_path, encode_png(LCD_WIDTH, LCD_HEIGHT, &actual)).unwrap();
+        fs::write(&diff_path, encode_png(LCD_WIDTH, LCD_HEIGHT, &diff)).unwrap();
+        panic!("{}: {} pixels differ from {}\n  actual: {}\n  diff:   {}",
+            name, mismatches, path.display(), actual_path.display(), diff_path.display());
+    }
+}
+
+fn run_screenshot_test(name: &str, rom: &[u8], frames: u64, script: &str) {
+    let mut gb = GameBoy::power_on();
+    assert_ne!(gb.load_buffer(rom), 0, "could not load ROM for {}", name);
+    let events = parse_script(script);
+    for frame in 0..frames {
+        for event in events.iter().filter(|event| event.frame == frame) {
+            if event.pressed {
+                gb.press(event.button);
+            } else {
+                gb.release(event.button);
+            }
+        }
+        gb.run_frame();
+    }
+    compare_golden(name, gb.bus().ppu.framebuffer());
+}
+
+// Builds a 32 KiB ROM-only image that draws a tile chosen by the joypad.
+// The program reads P1 with the action buttons selected and writes the
+// value as the tile index of the first four background tiles, so with no
+// button held tile 0xDF (stripes) is shown and with Start held tile 0xD7
+// (solid).
+fn joypad_rom() -> Vec<u8> {
+    let mut code: Vec<u8> = vec![];
+    // Fill a tile's 16 bytes with the same value.
+    let fill_tile = |code: &mut Vec<u8>, tile: u16, value: u8| {
+        code.extend([0x01, value, 0x00, 0x79]);
+        let addr = 0x8000 + tile * 16;
+        code.extend([0x01, addr as u8, (addr >> 8) as u8]);
+        for _ in 0..16 {
+            code.extend([0x02, 0x03]);
+        }
+    };
+    fill_tile(&mut code, 0xDF, 0xAA);
+    fill_tile(&mut code, 0xD7, 0xFF);
+    // Select the action buttons.
+    code.extend([0x01, 0x10, 0x00, 0x79, 0x01, 0x00, 0xFF, 0x02]);
+    let main_loop = 0x0150 + code.len() as u16;
+    // LD H,B; LD L,C; LD A,(HL)
+    code.extend([0x60, 0x69, 0x7E]);
+    code.extend([0x01, 0x00, 0x98]);
+    for _ in 0..4 {
+        code.extend([0x02, 0x03]);
+    }
+    code.extend([0x01, 0x00, 0xFF]);
+    code.extend([0xC3, main_loop as u8, (main_loop >> 8) as u8]);
+    test_rom(&code)
+}
+
+#[test]
+fn joypad_idle() {
+    run_screenshot_test("joypad_idle", &joypad_rom(), 5, "");
+}
+
+#[test]
+fn joypad_start_held() {
+    run_screenshot_test("joypad_start_held", &joypad_rom(), 5, "2:+start");
+}
+
+#[test]
+fn joypad_start_released() {
+    run_screenshot_test("joypad_start_released", &joypad_rom(), 5, "1:+start 3:-start");
+}
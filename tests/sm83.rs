@@ -0,0 +1,366 @@
+// Runs the SM83 single step test vectors (one JSON file per opcode, named
+// like `3e.json` or `cb 3e.json`, each holding an array of cases with an
+// initial state, a final state and the expected bus activity per M-cycle).
+// The vectors are not distributed with the emulator; they are looked up in
+// tests/roms/sm83 (or $GBEMU_SM83_DIR) and the test passes trivially when
+// none are found.
+mod common;
+
+use std::cell::RefCell;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+
+use common::rom_dir;
+use gbemu::{BusTrait, Registers, Sharp8080};
+
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as u64),
+            Json::Bool(b) => Some(*b as u64),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    fn field(&self, key: &str) -> Result<u64, String> {
+        self.get(key).and_then(Json::as_u64).ok_or(format!("missing field '{}'", key))
+    }
+}
+
+// A small recursive-descent JSON parser; just enough for the test vectors.
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { text: text.as_bytes(), pos: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.text.len() {
+            return Err(format!("trailing data at offset {}", parser.pos));
+        }
+        Ok(value)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.text.len() && self.text[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.text.get(self.pos) != Some(&c) {
+            return Err(format!("expected '{}' at offset {}", c as char, self.pos));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if !self.text[self.pos..].starts_with(word.as_bytes()) {
+            return Err(format!("invalid literal at offset {}", self.pos));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.text.get(self.pos) {
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                self.skip_whitespace();
+                if self.text.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.text.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b']')?;
+                Ok(Json::Array(items))
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = vec![];
+                self.skip_whitespace();
+                if self.text.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.text.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b'}')?;
+                Ok(Json::Object(fields))
+            }
+            Some(_) => {
+                let start = self.pos;
+                while self.pos < self.text.len() && b"+-.0123456789eE".contains(&self.text[self.pos]) {
+                    self.pos += 1;
+                }
+                std::str::from_utf8(&self.text[start..self.pos]).unwrap().parse()
+                    .map(Json::Number)
+                    .map_err(|_| format!("invalid value at offset {}", start))
+            }
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut out = String::new();
+        loop {
+            let c = *self.text.get(self.pos).ok_or("unterminated string")?;
+            self.pos += 1;
+            match c {
+                b'"' => return Ok(out),
+                b'\\' => {
+                    let escape = *self.text.get(self.pos).ok_or("unterminated string")?;
+                    self.pos += 1;
+                    match escape {
+                        b'n' => out.push('\n'),
+                        b't' => out.push('\t'),
+                        b'u' => {
+                            let hex = std::str::from_utf8(self.text.get(self.pos..self.pos + 4)
+                                .ok_or("truncated escape")?).map_err(|_| "invalid escape")?;
+                            let code = u32::from_str_radix(hex, 16).map_err(|_| "invalid escape")?;
+                            out.push(char::from_u32(code).unwrap_or('?'));
+                            self.pos += 4;
+                        }
+                        other => out.push(other as char),
+                    }
+                }
+                _ => out.push(c as char),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+}
+
+// A flat 64 KiB bus that records every access in order.
+struct RecordingBus {
+    memory: [u8; 0x10000],
+    accesses: RefCell<Vec<(u16, u8, Access)>>,
+}
+
+impl BusTrait for RecordingBus {
+    fn write(&mut self, addr: u16, data: u8) {
+        self.accesses.borrow_mut().push((addr, data, Access::Write));
+        self.memory[addr as usize] = data;
+    }
+    fn read(&self, addr: u16) -> u8 {
+        let data = self.memory[addr as usize];
+        self.accesses.borrow_mut().push((addr, data, Access::Read));
+        data
+    }
+}
+
+fn set_state(cpu: &mut Sharp8080, bus: &mut RecordingBus, state: &Json) -> Result<(), String> {
+    cpu.set_registers(&Registers {
+        a: state.field("a")? as u8,
+        f: state.field("f")? as u8,
+        b: state.field("b")? as u8,
+        c: state.field("c")? as u8,
+        d: state.field("d")? as u8,
+        e: state.field("e")? as u8,
+        h: state.field("h")? as u8,
+        l: state.field("l")? as u8,
+        sp: state.field("sp")? as u16,
+        pc: state.field("pc")? as u16,
+        ime: state.field("ime").unwrap_or(0) != 0,
+    });
+    if let Ok(ie) = state.field("ie") {
+        bus.memory[0xFFFF] = ie as u8;
+    }
+    for entry in state.get("ram").map(Json::as_array).unwrap_or(&[]) {
+        let entry = entry.as_array();
+        let addr = entry.first().and_then(Json::as_u64).ok_or("invalid ram entry")?;
+        let value = entry.get(1).and_then(Json::as_u64).ok_or("invalid ram entry")?;
+        bus.memory[addr as usize] = value as u8;
+    }
+    Ok(())
+}
+
+fn check_state(cpu: &Sharp8080, bus: &RecordingBus, state: &Json) -> Result<(), String> {
+    let r = cpu.registers();
+    let registers: [(&str, u64); 11] = [
+        ("a", r.a as u64), ("b", r.b as u64), ("c", r.c as u64), ("d", r.d as u64),
+        ("e", r.e as u64), ("f", r.f as u64), ("h", r.h as u64), ("l", r.l as u64),
+        ("sp", r.sp as u64), ("pc", r.pc as u64), ("ime", r.ime as u64),
+    ];
+    let mut errors = vec![];
+    for (name, actual) in registers {
+        if let Ok(expected) = state.field(name) {
+            if expected != actual {
+                errors.push(format!("{}={:#x} (expected {:#x})", name, actual, expected));
+            }
+        }
+    }
+    for entry in state.get("ram").map(Json::as_array).unwrap_or(&[]) {
+        let entry = entry.as_array();
+        let addr = entry.first().and_then(Json::as_u64).ok_or("invalid ram entry")? as usize;
+        let expected = entry.get(1).and_then(Json::as_u64).ok_or("invalid ram entry")?;
+        if bus.memory[addr] as u64 != expected {
+            errors.push(format!("[{:#06x}]={:#04x} (expected {:#04x})", addr, bus.memory[addr], expected));
+        }
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors.join(" ")) }
+}
+
+// Expected bus activity: idle cycles are null or have no read/write flag
+// in their "r-m"/"-wm"/"---" style pins field.
+fn expected_accesses(cycles: &[Json]) -> Result<Vec<(u16, u8, Access)>, String> {
+    let mut accesses = vec![];
+    for cycle in cycles {
+        let cycle = cycle.as_array();
+        let pins = cycle.get(2).and_then(Json::as_str).unwrap_or("---");
+        let access = if pins.starts_with('r') {
+            Access::Read
+        } else if pins.contains('w') {
+            Access::Write
+        } else {
+            continue;
+        };
+        let addr = cycle.first().and_then(Json::as_u64).ok_or("invalid cycle address")?;
+        let data = cycle.get(1).and_then(Json::as_u64).ok_or("invalid cycle data")?;
+        accesses.push((addr as u16, data as u8, access));
+    }
+    Ok(accesses)
+}
+
+fn run_case(case: &Json) -> Result<(), String> {
+    let initial = case.get("initial").ok_or("missing initial state")?;
+    let last = case.get("final").ok_or("missing final state")?;
+    let cycles = case.get("cycles").map(Json::as_array).unwrap_or(&[]);
+
+    let mut cpu = Sharp8080::new(0);
+    let mut bus = RecordingBus { memory: [0; 0x10000], accesses: RefCell::new(vec![]) };
+    set_state(&mut cpu, &mut bus, initial)?;
+    let opcode = cpu.fetch_opcode(&bus);
+    let taken = panic::catch_unwind(AssertUnwindSafe(|| cpu.execute(&mut bus, opcode)))
+        .map_err(|_| "panicked".to_string())?;
+
+    check_state(&cpu, &bus, last)?;
+    let expected = expected_accesses(cycles)?;
+    let actual = bus.accesses.borrow();
+    if *actual != expected {
+        return Err(format!("bus activity {:x?} (expected {:x?})", actual, expected));
+    }
+    if taken as usize != cycles.len() * 4 {
+        return Err(format!("took {} cycles (expected {})", taken, cycles.len() * 4));
+    }
+    Ok(())
+}
+
+#[test]
+fn single_step_vectors() {
+    let dir = rom_dir("GBEMU_SM83_DIR", "sm83");
+    let mut files = 0;
+    let mut failures = vec![];
+    for opcode in 0x000..0x200_u16 {
+        let name = if opcode < 0x100 {
+            format!("{:02x}.json", opcode)
+        } else {
+            format!("cb {:02x}.json", opcode & 0xFF)
+        };
+        let Ok(text) = fs::read_to_string(dir.join(&name)) else {
+            continue;
+        };
+        files += 1;
+        let cases = match Parser::parse(&text) {
+            Ok(cases) => cases,
+            Err(err) => {
+                failures.push(format!("{}: {}", name, err));
+                continue;
+            }
+        };
+        // Only the first failing case of each opcode is reported.
+        for case in cases.as_array() {
+            if let Err(err) = run_case(case) {
+                let case_name = case.get("name").and_then(Json::as_str).unwrap_or("?");
+                failures.push(format!("{} ({}): {}", name, case_name, err));
+                break;
+            }
+        }
+    }
+    if files == 0 {
+        println!("no SM83 test vectors found in {}, skipping", dir.display());
+        return;
+    }
+    for failure in &failures {
+        println!("{}", failure);
+    }
+    println!("{} of {} opcodes passed", files - failures.len(), files);
+    assert!(failures.is_empty(), "{} of {} opcodes failed", failures.len(), files);
+}
+
+#[test]
+fn json_parser() {
+    let json = Parser::parse(r#"{"name": "3e 0000", "cycles": [[1, 2, "r-m"], null], "x": -1.5e1, "t": true}"#).unwrap();
+    assert_eq!(json.get("name").and_then(Json::as_str), Some("3e 0000"));
+    let cycles = json.get("cycles").unwrap().as_array();
+    assert_eq!(cycles.len(), 2);
+    assert_eq!(cycles[0].as_array()[1].as_u64(), Some(2));
+    assert!(matches!(cycles[1], Json::Null));
+    assert!(matches!(json.get("x"), Some(Json::Number(n)) if *n == -15.0));
+    assert_eq!(json.get("t").and_then(Json::as_u64), Some(1));
+    assert!(Parser::parse("[1, 2").is_err());
+}
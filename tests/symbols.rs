@@ -0,0 +1,68 @@
+// Tests for symbol file loading and its use by the disassembler and the
+// GameBoy's instruction formatting.
+mod common;
+
+use common::{game_boy_with_rom, test_rom};
+use gbemu::disasm::RomDisassembly;
+use gbemu::SymbolTable;
+
+const SYMBOLS: &str = "\
+; File generated by rgblink
+00:0150 Main
+00:0155 Main.loop
+01:4000 BankedRoutine
+02:4000 OtherBank
+00:c000 wCounter
+00:ff80 hScratch
+";
+
+#[test]
+fn parse_and_lookup() {
+    let symbols = SymbolTable::parse(SYMBOLS).unwrap();
+    assert_eq!(symbols.len(), 6);
+    assert_eq!(symbols.lookup("Main.loop"), Some((0, 0x0155)));
+    assert_eq!(symbols.lookup("Missing"), None);
+    assert_eq!(symbols.name(2, 0x4000), Some("OtherBank"));
+    assert_eq!(symbols.name_at(0x4000, 1), Some("BankedRoutine"));
+    assert_eq!(symbols.name_at(0x4000, 2), Some("OtherBank"));
+    assert_eq!(symbols.name_at(0x4000, 3), None);
+    assert_eq!(symbols.name_at(0xC000, 5), Some("wCounter"));
+
+    assert_eq!(symbols.resolve("BankedRoutine"), Some((Some(1), 0x4000)));
+    assert_eq!(symbols.resolve("02:4123"), Some((Some(2), 0x4123)));
+    assert_eq!(symbols.resolve("$FF44"), Some((None, 0xFF44)));
+    assert_eq!(symbols.resolve("0x150"), Some((None, 0x0150)));
+    assert_eq!(symbols.resolve("nonsense!"), None);
+
+    assert!(SymbolTable::parse("00:zz Bad").is_err());
+    assert!(SymbolTable::parse("00:0150").is_err());
+}
+
+fn rom() -> Vec<u8> {
+    // Main: ld a, [wCounter]; ldh [hScratch], a; .loop: jr .loop
+    test_rom(&[0xFA, 0x00, 0xC0, 0xE0, 0x80, 0x18, 0xFE])
+}
+
+#[test]
+fn disassembly_uses_symbols() {
+    let rom = rom();
+    let symbols = SymbolTable::parse(SYMBOLS).unwrap();
+    let mut disassembly = RomDisassembly::trace(&rom);
+    disassembly.apply_symbols(&symbols);
+    let source = disassembly.to_rgbds();
+    assert!(source.starts_with("DEF wCounter EQU $C000\nDEF hScratch EQU $FF80\n\n"));
+    assert!(source.contains("    jp Main\n"));
+    assert!(source.contains("\nMain:\n    ld a, [wCounter]\n    ldh [hScratch], a\n\nMain_loop:\n    jr Main_loop\n"));
+    assert!(source.contains("\nBankedRoutine:\n"));
+}
+
+#[test]
+fn gameboy_names_addresses() {
+    let mut gb = game_boy_with_rom(&rom());
+    gb.set_symbols(SymbolTable::parse(SYMBOLS).unwrap());
+    assert_eq!(gb.disassemble(0x0100), "JP Main");
+    assert_eq!(gb.disassemble(0x0150), "LD A,(wCounter)");
+    assert_eq!(gb.disassemble(0x0155), "JR Main.loop");
+    assert_eq!(gb.bank_at(0x4000), 1);
+    assert_eq!(gb.symbols().name_at(0x4000, gb.bank_at(0x4000)), Some("BankedRoutine"));
+}
@@ -0,0 +1,62 @@
+// Tests for finding where a trace departs from a reference log.
+mod common;
+
+use common::game_boy;
+use gbemu::tracediff::{compare, Comparison, MemoryWrite};
+use gbemu::tracer::doctor_line;
+
+// ld bc, $C000; add a, 5; ld [bc], a; nop; jp $0150
+const PROGRAM: [u8; 10] = [0x01, 0x00, 0xC0, 0xC6, 0x05, 0x02, 0x00, 0xC3, 0x50, 0x01];
+
+// The emulator's own trace for the first `count` instructions.
+fn reference(count: usize) -> Vec<String> {
+    let mut gb = game_boy(&PROGRAM);
+    (0..count).map(|_| {
+        let pc = gb.cpu().pc();
+        let pcmem = [0, 1, 2, 3].map(|i| gb.bus().peek(pc.wrapping_add(i)));
+        let line = doctor_line(&gb.cpu().registers(), pcmem);
+        gb.step_instruction();
+        line
+    }).collect()
+}
+
+#[test]
+fn matching_trace() {
+    let log = reference(8).join("\n");
+    assert_eq!(compare(&mut game_boy(&PROGRAM), log.as_bytes(), 4, 4).unwrap(), Comparison::Matched(8));
+
+    // Fields missing from the reference are not compared.
+    let log = "PC:0100\nPC:0150\n\nPC:0153\n";
+    assert_eq!(compare(&mut game_boy(&PROGRAM), log.as_bytes(), 4, 4).unwrap(), Comparison::Matched(3));
+}
+
+#[test]
+fn first_divergence() {
+    let mut lines = reference(8);
+    assert!(lines[4].starts_with("A:06 "));
+    lines[4] = lines[4].replacen("A:06", "A:07", 1);
+    let log = lines.join("\n");
+
+    let divergence = match compare(&mut game_boy(&PROGRAM), log.as_bytes(), 2, 4).unwrap() {
+        Comparison::Diverged(divergence) => divergence,
+        other => panic!("expected a divergence, got {:?}", other),
+    };
+    assert_eq!(divergence.line, 5);
+    assert_eq!(divergence.expected, lines[4]);
+    assert!(divergence.actual.starts_with("A:06 "));
+    assert_eq!(divergence.context, &lines[2..4]);
+    assert_eq!(divergence.fields, [("A".to_string(), "07".to_string(), "06".to_string())]);
+    assert_eq!(divergence.writes, [MemoryWrite { pc: 0x0155, addr: 0xC000, value: 0x06 }]);
+}
+
+#[test]
+fn rejects_a_log_that_is_not_from_gameboy_doctor() {
+    let log = "[0150] LD BC,$C000\n[0153] ADD A,$05\n";
+    let err = compare(&mut game_boy(&PROGRAM), log.as_bytes(), 4, 4).unwrap_err();
+    assert!(err.starts_with("reference line 1: no NAME:VALUE fields"), "{}", err);
+
+    // A stray line part way through is reported where it is.
+    let log = format!("{}\nnot a trace\n", reference(2).join("\n"));
+    let err = compare(&mut game_boy(&PROGRAM), log.as_bytes(), 4, 4).unwrap_err();
+    assert!(err.starts_with("reference line 3:"), "{}", err);
+}
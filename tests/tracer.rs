@@ -0,0 +1,70 @@
+// Tests for the Gameboy Doctor trace log.
+mod common;
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use common::game_boy;
+use gbemu::Tracer;
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.borrow().clone()).unwrap().lines().map(str::to_string).collect()
+    }
+}
+
+// ld bc, $C000; add a, 5; nop; jp $0150
+const PROGRAM: [u8; 9] = [0x01, 0x00, 0xC0, 0xC6, 0x05, 0x00, 0xC3, 0x50, 0x01];
+
+#[test]
+fn doctor_format() {
+    let mut gb = game_boy(&PROGRAM);
+    let buffer = SharedBuffer::default();
+    gb.set_tracer(Some(Tracer::new(buffer.clone())));
+    for _ in 0..3 {
+        gb.step_instruction();
+    }
+    assert_eq!(buffer.lines(), [
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:C3,50,01,00",
+        "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0150 PCMEM:01,00,C0,C6",
+        "A:01 F:B0 B:C0 C:00 D:00 E:D8 H:01 L:4D SP:FFFE PC:0153 PCMEM:C6,05,00,C3",
+    ]);
+}
+
+#[test]
+fn filters() {
+    let mut gb = game_boy(&PROGRAM);
+    let buffer = SharedBuffer::default();
+    let mut tracer = Tracer::new(buffer.clone());
+    tracer.set_pc_range(Some(0x0153..=0x0155));
+    gb.set_tracer(Some(tracer));
+    for _ in 0..9 {
+        gb.step_instruction();
+    }
+    let pcs: Vec<String> = buffer.lines().iter()
+        .map(|line| line.split(" PC:").nth(1).unwrap()[..4].to_string())
+        .collect();
+    assert_eq!(pcs, ["0153", "0155", "0153", "0155"]);
+
+    let buffer = SharedBuffer::default();
+    let mut tracer = Tracer::new(buffer.clone());
+    tracer.set_bank(Some(1));
+    gb.set_tracer(Some(tracer));
+    gb.step_instruction();
+    assert!(buffer.lines().is_empty());
+}